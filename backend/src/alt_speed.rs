@@ -0,0 +1,41 @@
+use crate::{
+    login::User,
+    torrents::{torrent_command, torrent_command_result},
+    Failure,
+};
+use actix_web::{web, HttpResponse, Responder};
+use rsbt_service::{RsbtCommand, RsbtCommandAltSpeedMode};
+use serde::Deserialize;
+use tokio::sync::mpsc::Sender;
+
+#[derive(Deserialize)]
+struct AltSpeedMode {
+    enabled: bool,
+}
+
+#[get("/alt-speed")]
+async fn alt_speed(event_sender: web::Data<Sender<RsbtCommand>>, _user: User) -> impl Responder {
+    torrent_command(event_sender, (), RsbtCommand::AltSpeed).await
+}
+
+#[put("/alt-speed")]
+async fn alt_speed_mode(
+    event_sender: web::Data<Sender<RsbtCommand>>,
+    body: web::Json<AltSpeedMode>,
+    _user: User,
+) -> impl Responder {
+    let result = torrent_command_result(
+        event_sender,
+        RsbtCommandAltSpeedMode {
+            enabled: body.enabled,
+        },
+        RsbtCommand::AltSpeedMode,
+    )
+    .await;
+    match result {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(err) => HttpResponse::InternalServerError().json(Failure {
+            error: format!("{}", err),
+        }),
+    }
+}
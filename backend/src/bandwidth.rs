@@ -0,0 +1,35 @@
+use crate::{
+    login::User,
+    torrents::{torrent_command, torrent_command_result},
+    Failure,
+};
+use actix_web::{web, HttpResponse, Responder};
+use rsbt_service::{RsbtBandwidthLimits, RsbtCommand, RsbtCommandBandwidthLimits};
+use tokio::sync::mpsc::Sender;
+
+#[get("/bandwidth")]
+async fn bandwidth(event_sender: web::Data<Sender<RsbtCommand>>, _user: User) -> impl Responder {
+    torrent_command(event_sender, (), RsbtCommand::Bandwidth).await
+}
+
+#[put("/bandwidth")]
+async fn bandwidth_limits(
+    event_sender: web::Data<Sender<RsbtCommand>>,
+    body: web::Json<RsbtBandwidthLimits>,
+    _user: User,
+) -> impl Responder {
+    let result = torrent_command_result(
+        event_sender,
+        RsbtCommandBandwidthLimits {
+            limits: body.into_inner(),
+        },
+        RsbtCommand::BandwidthLimits,
+    )
+    .await;
+    match result {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(err) => HttpResponse::InternalServerError().json(Failure {
+            error: format!("{}", err),
+        }),
+    }
+}
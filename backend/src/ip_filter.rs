@@ -0,0 +1,17 @@
+use crate::{login::User, torrents::torrent_command};
+use actix_web::{web, Responder};
+use rsbt_service::RsbtCommand;
+use tokio::sync::mpsc::Sender;
+
+#[get("/ip-filter")]
+async fn ip_filter(event_sender: web::Data<Sender<RsbtCommand>>, _user: User) -> impl Responder {
+    torrent_command(event_sender, (), RsbtCommand::IpFilter).await
+}
+
+#[post("/ip-filter/reload")]
+async fn ip_filter_reload(
+    event_sender: web::Data<Sender<RsbtCommand>>,
+    _user: User,
+) -> impl Responder {
+    torrent_command(event_sender, (), RsbtCommand::IpFilterReload).await
+}
@@ -12,8 +12,8 @@ use exitfailure::ExitFailure;
 use futures::{future::abortable, stream::select_all, StreamExt};
 use log::{debug, error};
 use rsbt_service::{
-    RsbtApp, RsbtCommand, RsbtCommandAddTorrent, RsbtError, RsbtRequestResponse, RsbtSettings,
-    RsbtTorrentProcess, RsbtTorrentStatisticsEvent,
+    RsbtApp, RsbtBandwidthLimits, RsbtCommand, RsbtCommandAddTorrent, RsbtError,
+    RsbtRequestResponse, RsbtSettings, RsbtTorrentProcess, RsbtTorrentStatisticsEvent,
 };
 use serde::{Deserialize, Serialize};
 use std::{
@@ -27,23 +27,31 @@ use tokio::{
     time::{delay_for, Duration},
 };
 
+mod alt_speed;
+mod bandwidth;
 mod cli;
 mod event_stream;
 mod file_download;
+mod ip_filter;
 mod login;
 mod model;
 #[cfg(feature = "sandbox")]
 mod sandbox;
 mod session;
+mod status;
 mod torrents;
 mod uploads;
 
+use alt_speed::*;
+use bandwidth::*;
 use event_stream::{stream, Broadcaster};
 use file_download::*;
+use ip_filter::*;
 use login::*;
 #[cfg(feature = "sandbox")]
 use sandbox::*;
 use session::*;
+use status::*;
 use torrents::*;
 use uploads::*;
 
@@ -128,6 +136,16 @@ async fn main() -> Result<(), ExitFailure> {
                 data,
                 filename: torrent.file,
                 state: torrent.state,
+                limits: RsbtBandwidthLimits {
+                    upload: torrent.upload_limit,
+                    download: torrent.download_limit,
+                },
+                file_priorities: torrent.file_priorities,
+                sequential: torrent.sequential,
+                first_last_pieces: torrent.first_last_pieces,
+                save_to: torrent.save_to,
+                folder: torrent.folder,
+                file_paths: torrent.file_paths,
             });
 
             rsbt_command_sender
@@ -148,6 +166,7 @@ async fn main() -> Result<(), ExitFailure> {
         }
     }
 
+    let mut shutdown_sender = rsbt_command_sender.clone();
     let sender = web::Data::new(rsbt_command_sender);
     let broadcaster_sender = web::Data::new(broadcaster_sender);
 
@@ -171,12 +190,27 @@ async fn main() -> Result<(), ExitFailure> {
                     .service(torrent_detail)
                     .service(torrent_delete)
                     .service(torrent_create_action)
+                    .service(torrent_bandwidth_limits)
                     .service(torrent_peer_list)
+                    .service(torrent_peer_add)
+                    .service(torrent_peer_ban)
+                    .service(torrent_peer_unban)
                     .service(torrent_announce_list)
                     .service(torrent_file_list)
+                    .service(torrent_file_priority)
+                    .service(torrent_move)
+                    .service(torrent_file_rename)
+                    .service(torrent_folder_rename)
                     .service(torrent_piece_list)
                     .service(torrent_file_download_head)
                     .service(torrent_file_download)
+                    .service(bandwidth)
+                    .service(bandwidth_limits)
+                    .service(alt_speed)
+                    .service(alt_speed_mode)
+                    .service(ip_filter)
+                    .service(ip_filter_reload)
+                    .service(status)
                     .service(upload)
                     .service(account)
                     .service(logout)
@@ -213,6 +247,14 @@ async fn main() -> Result<(), ExitFailure> {
     .run()
     .await?;
 
+    debug!("http server stopped, shutting down torrents process");
+    let (request_response, receiver) = RsbtRequestResponse::new(());
+    shutdown_sender
+        .send(RsbtCommand::Shutdown(request_response))
+        .await
+        .map_err(RsbtError::from)?;
+    receiver.await??;
+
     Ok(())
 }
 
@@ -270,6 +312,9 @@ fn init_broadcaster() -> (web::Data<Broadcaster>, Sender<BroadcasterMessage>) {
                                     read: x.bytes_read,
                                     write: x.bytes_write,
                                     left: x.pieces_left,
+                                    checking: x.checking,
+                                    moving: x.moving,
+                                    move_error: x.move_error,
                                 })
                                 .boxed(),
                             torrent_download
@@ -0,0 +1,9 @@
+use crate::{login::User, torrents::torrent_command};
+use actix_web::{web, Responder};
+use rsbt_service::RsbtCommand;
+use tokio::sync::mpsc::Sender;
+
+#[get("/status")]
+async fn status(event_sender: web::Data<Sender<RsbtCommand>>, _user: User) -> impl Responder {
+    torrent_command(event_sender, (), RsbtCommand::Status).await
+}
@@ -3,13 +3,20 @@ use actix::prelude::*;
 use actix_web::{dev::Payload, web, Error, FromRequest, HttpRequest, HttpResponse, Responder};
 use log::{error, info};
 use rsbt_service::{
-    RsbtCommand, RsbtCommandDeleteTorrent, RsbtCommandTorrentAction, RsbtCommandTorrentAnnounce,
-    RsbtCommandTorrentDetail, RsbtCommandTorrentFiles, RsbtCommandTorrentPeers,
-    RsbtCommandTorrentPieces, RsbtError, RsbtRequestResponse, RsbtTorrentAction,
-    RsbtTorrentDownloadView,
+    RsbtBandwidthLimits, RsbtCommand, RsbtCommandDeleteTorrent, RsbtCommandTorrentAction,
+    RsbtCommandTorrentAddPeer, RsbtCommandTorrentAnnounce, RsbtCommandTorrentBanPeer,
+    RsbtCommandTorrentBandwidthLimits, RsbtCommandTorrentDetail, RsbtCommandTorrentFilePriority,
+    RsbtCommandTorrentFiles, RsbtCommandTorrentMove, RsbtCommandTorrentPeers,
+    RsbtCommandTorrentPieces, RsbtCommandTorrentRename, RsbtCommandTorrentUnbanPeer, RsbtError,
+    RsbtFilePriority, RsbtRequestResponse, RsbtTorrentAction, RsbtTorrentDownloadView,
 };
 use serde::{Deserialize, Serialize};
-use std::{cmp::Ordering, pin::Pin};
+use std::{
+    cmp::Ordering,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    pin::Pin,
+};
 use tokio::sync::mpsc::Sender;
 
 struct Paging {
@@ -178,6 +185,9 @@ async fn torrent_create_action(
         Ok(Err(err @ RsbtError::TorrentNotFound(_))) => HttpResponse::NotFound().json(Failure {
             error: format!("{}", err),
         }),
+        Ok(Err(err @ RsbtError::SuperSeedNotComplete)) => HttpResponse::Conflict().json(Failure {
+            error: format!("{}", err),
+        }),
         Ok(Err(err)) => HttpResponse::InternalServerError().json(Failure {
             error: format!("{}", err),
         }),
@@ -187,6 +197,33 @@ async fn torrent_create_action(
     }
 }
 
+#[put("/torrent/{id}/bandwidth")]
+async fn torrent_bandwidth_limits(
+    event_sender: web::Data<Sender<RsbtCommand>>,
+    id: web::Path<usize>,
+    body: web::Json<RsbtBandwidthLimits>,
+    _user: User,
+) -> impl Responder {
+    let result = torrent_command_result(
+        event_sender,
+        RsbtCommandTorrentBandwidthLimits {
+            id: *id,
+            limits: body.into_inner(),
+        },
+        RsbtCommand::TorrentBandwidthLimits,
+    )
+    .await;
+    match result {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(err @ RsbtError::TorrentNotFound(_)) => HttpResponse::NotFound().json(Failure {
+            error: format!("{}", err),
+        }),
+        Err(err) => HttpResponse::InternalServerError().json(Failure {
+            error: format!("{}", err),
+        }),
+    }
+}
+
 #[derive(Deserialize)]
 struct DeleteQuery {
     #[serde(default)]
@@ -254,14 +291,14 @@ where
         let mut event_sender = event_sender.as_ref().clone();
         if let Err(err) = event_sender.send(cmd(request_response)).await {
             error!("cannot send to torrent process: {}", err);
-            return Err(RsbtError::SendToTorrentToken(err));
+            return Err(RsbtError::SendToTorrentToken(Box::new(err)));
         }
     }
 
     receiver.await.map_err(RsbtError::from)?
 }
 
-async fn torrent_command<T, F, R: Serialize>(
+pub(crate) async fn torrent_command<T, F, R: Serialize>(
     event_sender: web::Data<Sender<RsbtCommand>>,
     data: T,
     cmd: F,
@@ -309,6 +346,93 @@ async fn torrent_peer_list(
     .await
 }
 
+#[derive(Deserialize)]
+struct AddPeer {
+    addr: SocketAddr,
+}
+
+#[post("/torrent/{id}/peer")]
+async fn torrent_peer_add(
+    event_sender: web::Data<Sender<RsbtCommand>>,
+    id: web::Path<usize>,
+    body: web::Json<AddPeer>,
+    _user: User,
+) -> impl Responder {
+    let result = torrent_command_result(
+        event_sender,
+        RsbtCommandTorrentAddPeer {
+            id: *id,
+            addr: body.addr,
+        },
+        RsbtCommand::TorrentAddPeer,
+    )
+    .await;
+    peer_command_response(result)
+}
+
+#[derive(Deserialize)]
+struct BanQuery {
+    #[serde(default)]
+    global: bool,
+}
+
+#[put("/torrent/{id}/peer/ban/{ip}")]
+async fn torrent_peer_ban(
+    event_sender: web::Data<Sender<RsbtCommand>>,
+    path: web::Path<(usize, IpAddr)>,
+    query: web::Query<BanQuery>,
+    _user: User,
+) -> impl Responder {
+    let result = torrent_command_result(
+        event_sender,
+        RsbtCommandTorrentBanPeer {
+            id: path.0,
+            ip: path.1,
+            global: query.global,
+        },
+        RsbtCommand::TorrentBanPeer,
+    )
+    .await;
+    peer_command_response(result)
+}
+
+#[delete("/torrent/{id}/peer/ban/{ip}")]
+async fn torrent_peer_unban(
+    event_sender: web::Data<Sender<RsbtCommand>>,
+    path: web::Path<(usize, IpAddr)>,
+    query: web::Query<BanQuery>,
+    _user: User,
+) -> impl Responder {
+    let result = torrent_command_result(
+        event_sender,
+        RsbtCommandTorrentUnbanPeer {
+            id: path.0,
+            ip: path.1,
+            global: query.global,
+        },
+        RsbtCommand::TorrentUnbanPeer,
+    )
+    .await;
+    peer_command_response(result)
+}
+
+fn peer_command_response(result: Result<(), RsbtError>) -> HttpResponse {
+    match result {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(err @ RsbtError::TorrentNotFound(_)) => HttpResponse::NotFound().json(Failure {
+            error: format!("{}", err),
+        }),
+        Err(err @ RsbtError::PeerBanned(_)) | Err(err @ RsbtError::PeerBlocked(_)) => {
+            HttpResponse::BadRequest().json(Failure {
+                error: format!("{}", err),
+            })
+        }
+        Err(err) => HttpResponse::InternalServerError().json(Failure {
+            error: format!("{}", err),
+        }),
+    }
+}
+
 #[get("/torrent/{id}/announce")]
 async fn torrent_announce_list(
     event_sender: web::Data<Sender<RsbtCommand>>,
@@ -337,6 +461,145 @@ async fn torrent_file_list(
     .await
 }
 
+#[derive(Deserialize)]
+struct FilePriorityRequest {
+    priority: RsbtFilePriority,
+}
+
+#[put("/torrent/{id}/file/{file_id}")]
+async fn torrent_file_priority(
+    event_sender: web::Data<Sender<RsbtCommand>>,
+    path: web::Path<(usize, usize)>,
+    body: web::Json<FilePriorityRequest>,
+    _user: User,
+) -> impl Responder {
+    let (id, file_id) = path.into_inner();
+    let result = torrent_command_result(
+        event_sender,
+        RsbtCommandTorrentFilePriority {
+            id,
+            file_id,
+            priority: body.priority,
+        },
+        RsbtCommand::TorrentFilePriority,
+    )
+    .await;
+    match result {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(err @ RsbtError::TorrentNotFound(_)) | Err(err @ RsbtError::TorrentFileNotFound(_)) => {
+            HttpResponse::NotFound().json(Failure {
+                error: format!("{}", err),
+            })
+        }
+        Err(err) => HttpResponse::InternalServerError().json(Failure {
+            error: format!("{}", err),
+        }),
+    }
+}
+
+#[derive(Deserialize)]
+struct MoveRequest {
+    save_to: PathBuf,
+}
+
+#[post("/torrent/{id}/move")]
+async fn torrent_move(
+    event_sender: web::Data<Sender<RsbtCommand>>,
+    id: web::Path<usize>,
+    body: web::Json<MoveRequest>,
+    _user: User,
+) -> impl Responder {
+    let result = torrent_command_result(
+        event_sender,
+        RsbtCommandTorrentMove {
+            id: *id,
+            save_to: body.into_inner().save_to,
+        },
+        RsbtCommand::TorrentMove,
+    )
+    .await;
+    match result {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(err @ RsbtError::TorrentNotFound(_)) => HttpResponse::NotFound().json(Failure {
+            error: format!("{}", err),
+        }),
+        Err(err @ RsbtError::TorrentDataMoving) => HttpResponse::Conflict().json(Failure {
+            error: format!("{}", err),
+        }),
+        Err(err) => HttpResponse::InternalServerError().json(Failure {
+            error: format!("{}", err),
+        }),
+    }
+}
+
+#[derive(Deserialize)]
+struct RenameRequest {
+    name: String,
+}
+
+#[put("/torrent/{id}/file/{file_id}/name")]
+async fn torrent_file_rename(
+    event_sender: web::Data<Sender<RsbtCommand>>,
+    path: web::Path<(usize, usize)>,
+    body: web::Json<RenameRequest>,
+    _user: User,
+) -> impl Responder {
+    let (id, file_id) = path.into_inner();
+    let result = torrent_command_result(
+        event_sender,
+        RsbtCommandTorrentRename {
+            id,
+            file_id: Some(file_id),
+            name: body.into_inner().name,
+        },
+        RsbtCommand::TorrentRename,
+    )
+    .await;
+    rename_response(result)
+}
+
+#[put("/torrent/{id}/folder")]
+async fn torrent_folder_rename(
+    event_sender: web::Data<Sender<RsbtCommand>>,
+    id: web::Path<usize>,
+    body: web::Json<RenameRequest>,
+    _user: User,
+) -> impl Responder {
+    let result = torrent_command_result(
+        event_sender,
+        RsbtCommandTorrentRename {
+            id: *id,
+            file_id: None,
+            name: body.into_inner().name,
+        },
+        RsbtCommand::TorrentRename,
+    )
+    .await;
+    rename_response(result)
+}
+
+fn rename_response(result: Result<(), RsbtError>) -> HttpResponse {
+    match result {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(err @ RsbtError::TorrentNotFound(_)) | Err(err @ RsbtError::TorrentFileNotFound(_)) => {
+            HttpResponse::NotFound().json(Failure {
+                error: format!("{}", err),
+            })
+        }
+        Err(err @ RsbtError::TorrentFileNameInvalid(_)) => {
+            HttpResponse::BadRequest().json(Failure {
+                error: format!("{}", err),
+            })
+        }
+        Err(err @ RsbtError::TorrentDataMoving) => HttpResponse::Conflict().json(Failure {
+            error: format!("{}", err),
+        }),
+        Err(err) => HttpResponse::InternalServerError().json(Failure {
+            error: format!("{}", err),
+        }),
+    }
+}
+
 #[get("/torrent/{id}/piece")]
 async fn torrent_piece_list(
     event_sender: web::Data<Sender<RsbtCommand>>,
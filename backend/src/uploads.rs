@@ -8,6 +8,7 @@ use rsbt_service::{
 };
 
 use crate::{login::User, BroadcasterMessage, Failure};
+use std::path::PathBuf;
 use tokio::sync::mpsc::Sender;
 
 #[post("/upload")]
@@ -17,21 +18,39 @@ async fn upload(
     broadcaster_sender: web::Data<Sender<BroadcasterMessage>>,
     mut payload: Multipart,
 ) -> Result<HttpResponse, Error> {
-    if let Some(item) = payload.next().await {
+    let mut torrent = None;
+    let mut save_to = None;
+    while let Some(item) = payload.next().await {
         let mut field = item?;
-        let content_type = field.content_disposition().unwrap();
-        let filename = content_type.get_filename().unwrap();
 
-        let mut torrent = vec![];
+        let mut data = vec![];
         while let Some(chunk) = field.next().await {
-            let data = chunk?;
-            torrent.extend(&data);
+            data.extend(&chunk?);
         }
 
+        let content_disposition = field.content_disposition().unwrap();
+        if content_disposition.get_name() == Some("save_to") {
+            let path = String::from_utf8_lossy(&data).trim().to_string();
+            if !path.is_empty() {
+                save_to = Some(PathBuf::from(path));
+            }
+        } else if let Some(filename) = content_disposition.get_filename() {
+            torrent = Some((filename.to_string(), data));
+        }
+    }
+
+    if let Some((filename, torrent)) = torrent {
         let (request_response, receiver) = RsbtRequestResponse::new(RsbtCommandAddTorrent {
             data: torrent,
-            filename: filename.to_string(),
+            filename,
             state: RsbtTorrentProcessStatus::Enabled,
+            limits: Default::default(),
+            file_priorities: vec![],
+            sequential: false,
+            first_last_pieces: false,
+            save_to,
+            folder: None,
+            file_paths: vec![],
         });
         {
             let mut event_sender = event_sender.as_ref().clone();
@@ -9,6 +9,9 @@ pub(crate) struct Cli {
     /// Path to torrent
     #[structopt(parse(from_os_str))]
     pub torrent: PathBuf,
+    /// Directory of torrent data, download path if omitted
+    #[structopt(short, long, parse(from_os_str))]
+    pub output: Option<PathBuf>,
     #[structopt(flatten)]
     pub verbose: Verbosity,
     #[structopt(flatten)]
@@ -22,6 +22,8 @@ async fn main() -> Result<(), ExitFailure> {
                 level_filter,
             )
             .filter(Some("flat_storage_mmap"), level_filter)
+            .filter(Some("flat_storage_file"), level_filter)
+            .filter(Some("flat_storage_memory"), level_filter)
             .filter(None, Level::Warn.to_level_filter())
             .try_init()?;
     }
@@ -38,7 +40,7 @@ async fn main() -> Result<(), ExitFailure> {
 
     let mut app = RsbtApp::new(properties);
 
-    app.download(cli.torrent).await?;
+    app.download(cli.torrent, cli.output).await?;
 
     Ok(())
 }
@@ -0,0 +1,379 @@
+use flat_storage::*;
+use log::{debug, error};
+use std::{
+    fs::{create_dir_all, remove_file, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Flat storage with positional reads and writes.
+///
+/// Files are created on first access, at most `max_open_files` are kept open.
+pub struct FileFlatStorage {
+    download_path: PathBuf,
+    files: Vec<FlatStorageFile>,
+    mapping: Vec<FlatStorageMapping>,
+    saved: Mutex<Vec<usize>>,
+    /// Skipped files, which are not created
+    absent: Mutex<Vec<bool>>,
+    file_handles: Mutex<FileHandles>,
+    part_file: PartFile,
+}
+
+/// Open files, least recently used first.
+struct FileHandles {
+    capacity: usize,
+    handles: Vec<(usize, Arc<File>)>,
+}
+
+impl FileHandles {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            handles: vec![],
+        }
+    }
+
+    fn get(&mut self, file_index: usize) -> Option<Arc<File>> {
+        let position = self.handles.iter().position(|x| x.0 == file_index)?;
+        let handle = self.handles.remove(position);
+        let file = handle.1.clone();
+        self.handles.push(handle);
+        Some(file)
+    }
+
+    /// Least recently used file is synced and closed when there are too many open files.
+    fn insert(&mut self, file_index: usize, file: Arc<File>) {
+        if self.handles.len() >= self.capacity {
+            let (evicted, evicted_file) = self.handles.remove(0);
+            debug!("closing file: {}", evicted);
+            if let Err(err) = evicted_file.sync_data() {
+                error!("cannot sync file {}: {}", evicted, err);
+            }
+        }
+        self.handles.push((file_index, file));
+    }
+
+    fn clear(&mut self) {
+        self.handles.clear();
+    }
+}
+
+impl FileFlatStorage {
+    /// Skipped files are not created, their blocks in pieces of other files go to `part_file`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create<P: AsRef<Path>, Q: AsRef<Path>>(
+        download_path: P,
+        piece_count: usize,
+        piece_size: usize,
+        files: Vec<FlatStorageFile>,
+        downloaded: &[u8],
+        skipped: &[bool],
+        part_file: Q,
+        max_open_files: usize,
+    ) -> Result<Self, std::io::Error> {
+        let mapping = map_pieces_to_files(piece_size, &files);
+        let saved = (0..files.len())
+            .map(|index| calculate_saved(piece_count, index, &mapping, downloaded))
+            .collect();
+        let absent = files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| {
+                skipped.get(index) == Some(&true)
+                    && !download_path.as_ref().join(&file.path).is_file()
+            })
+            .collect();
+        let part_file = PartFile::new(part_file, piece_size, &mapping);
+        Ok(Self {
+            download_path: download_path.as_ref().to_path_buf(),
+            files,
+            mapping,
+            saved: Mutex::new(saved),
+            absent: Mutex::new(absent),
+            file_handles: Mutex::new(FileHandles::new(max_open_files)),
+            part_file,
+        })
+    }
+
+    pub fn delete_files<P: AsRef<Path>>(&self, download_path: P) -> Result<(), std::io::Error> {
+        self.file_handles.lock().unwrap().clear();
+        for file in &self.files {
+            let file_path = download_path.as_ref().join(&file.path);
+            debug!("deleting file: {:?}", file_path);
+            if file_path.is_file() {
+                remove_file(file_path)?
+            }
+        }
+        self.part_file.delete()
+    }
+
+    /// Syncs open files and part file, so saved pieces survive a crash.
+    pub fn flush(&self) -> Result<(), std::io::Error> {
+        for (_, file) in &self.file_handles.lock().unwrap().handles {
+            file.sync_data()?;
+        }
+        self.part_file.sync()
+    }
+
+    /// Syncs and closes open files, storage must be created again to access them.
+    pub fn close(&self) -> Result<(), std::io::Error> {
+        let mut file_handles = self.file_handles.lock().unwrap();
+        for (_, file) in &file_handles.handles {
+            file.sync_data()?;
+        }
+        file_handles.clear();
+        Ok(())
+    }
+
+    pub fn saved(&self) -> Vec<usize> {
+        self.saved.lock().unwrap().clone()
+    }
+
+    /// Recalculates saved bytes per file from downloaded pieces bitfield.
+    pub fn set_downloaded(&self, downloaded: &[u8]) {
+        let mut saved = self.saved.lock().unwrap();
+        for (index, saved) in saved.iter_mut().enumerate() {
+            *saved = calculate_saved(self.mapping.len(), index, &self.mapping, downloaded);
+        }
+    }
+
+    pub fn file_info(&self, file_id: usize) -> Option<FileInfo> {
+        file_info(&self.files, &self.mapping, file_id)
+    }
+
+    /// Creates file, which is not skipped anymore, with blocks from part file.
+    ///
+    /// Already created files are kept when skipped.
+    pub fn set_skipped(&self, file_index: usize, skipped: bool) -> Result<(), std::io::Error> {
+        let mut absent = self.absent.lock().unwrap();
+        if skipped || !absent[file_index] {
+            return Ok(());
+        }
+        let file = self.file(file_index)?;
+        for (piece, map_to_files) in self.mapping.iter().enumerate() {
+            if !self.part_file.contains(piece) {
+                continue;
+            }
+            for file_block in map_to_files.0.iter().filter(|x| x.file_index == file_index) {
+                let mut data = vec![0; file_block.size];
+                self.part_file.read(piece, file_block, &mut data)?;
+                write_at(&file, &data, file_block.file_offset as u64)?;
+            }
+        }
+        absent[file_index] = false;
+        Ok(())
+    }
+
+    fn is_absent(&self, file_index: usize) -> bool {
+        self.absent.lock().unwrap()[file_index]
+    }
+
+    fn file(&self, file_index: usize) -> Result<Arc<File>, std::io::Error> {
+        let mut file_handles = self.file_handles.lock().unwrap();
+        if let Some(file) = file_handles.get(file_index) {
+            return Ok(file);
+        }
+        let file = Arc::new(open_file(&self.download_path, &self.files[file_index])?);
+        file_handles.insert(file_index, file.clone());
+        Ok(file)
+    }
+}
+
+fn open_file(download_path: &Path, file: &FlatStorageFile) -> Result<File, std::io::Error> {
+    let file_path = download_path.join(&file.path);
+    debug!("opening file: {:?}", file_path);
+    if !file_path.is_file() {
+        if let Some(path) = file_path.parent() {
+            debug!("create dir {:?}", path);
+            create_dir_all(path)?;
+        }
+    }
+    let f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&file_path)?;
+    if f.metadata()?.len() != file.length as u64 {
+        debug!("set len");
+        f.set_len(file.length as u64)?;
+    }
+    Ok(f)
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(unix)]
+fn write_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::write_all_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            n => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn write_at(file: &File, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_write(buf, offset)? {
+            0 => return Err(io::ErrorKind::WriteZero.into()),
+            n => {
+                buf = &buf[n..];
+                offset += n as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl FlatStorage for FileFlatStorage {
+    fn files(&self) -> &[FlatStorageFile] {
+        &self.files
+    }
+
+    /// Piece, which belongs to skipped files only, is not available.
+    fn read_piece<I: Into<FlatStoragePieceIndex>>(
+        &self,
+        index: I,
+    ) -> Result<Option<Vec<u8>>, FlatStorageError> {
+        let index = *index.into();
+        let map_to_files = &self.mapping[index];
+        let size = map_to_files.0.iter().map(|x| x.size).sum();
+        let mut result = vec![0; size];
+        for file_block in &map_to_files.0 {
+            let data = &mut result[file_block.offset..file_block.offset + file_block.size];
+            let absent = self.is_absent(file_block.file_index);
+            if absent && !self.part_file.contains(index) {
+                return Ok(None);
+            }
+            let result = if absent {
+                self.part_file.read(index, file_block, data)
+            } else {
+                self.file(file_block.file_index)
+                    .and_then(|f| read_at(&f, data, file_block.file_offset as u64))
+            };
+            result.map_err(|err| {
+                error!("cannot read file {}: {}", file_block.file_index, err);
+                FlatStorageError::ReadBlock
+            })?;
+        }
+
+        Ok(Some(result))
+    }
+
+    fn write_piece<I: Into<FlatStoragePieceIndex>>(
+        &self,
+        index: I,
+        block: Vec<u8>,
+    ) -> Result<(), FlatStorageError> {
+        let index = *index.into();
+        let map_to_files = &self.mapping[index];
+        for file_block in &map_to_files.0 {
+            let data = &block[file_block.offset..file_block.offset + file_block.size];
+            let mut absent = self.is_absent(file_block.file_index);
+            // piece of skipped file without part file slot is streamed, so file is created
+            if absent && !self.part_file.contains(index) {
+                self.set_skipped(file_block.file_index, false)
+                    .map_err(|err| {
+                        error!("cannot create file {}: {}", file_block.file_index, err);
+                        FlatStorageError::WriteBlock
+                    })?;
+                absent = false;
+            }
+            let result = if absent {
+                self.part_file.write(index, file_block, data)
+            } else {
+                self.file(file_block.file_index)
+                    .and_then(|f| write_at(&f, data, file_block.file_offset as u64))
+            };
+            result.map_err(|err| {
+                error!("cannot write file {}: {}", file_block.file_index, err);
+                FlatStorageError::WriteBlock
+            })?;
+            self.saved.lock().unwrap()[file_block.file_index] += file_block.size;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_and_read_pieces() {
+        let download_path = std::env::temp_dir().join(format!(
+            "flat-storage-file-{}-{}",
+            std::process::id(),
+            line!()
+        ));
+        let files = vec![
+            FlatStorageFile {
+                path: "a".into(),
+                length: 7,
+            },
+            FlatStorageFile {
+                path: "b/c".into(),
+                length: 8,
+            },
+            FlatStorageFile {
+                path: "d".into(),
+                length: 5,
+            },
+        ];
+        let storage = FileFlatStorage::create(
+            &download_path,
+            2,
+            10,
+            files,
+            &[],
+            &[false, false, true],
+            download_path.join("torrent.parts"),
+            1,
+        )
+        .unwrap();
+
+        let data: Vec<u8> = (0..20).collect();
+        storage.write_piece(1, data[10..].to_vec()).unwrap();
+        storage.write_piece(0, data[..10].to_vec()).unwrap();
+
+        assert_eq!(storage.read_piece(0).unwrap(), Some(data[..10].to_vec()));
+        assert_eq!(storage.read_piece(1).unwrap(), Some(data[10..].to_vec()));
+        assert_eq!(storage.saved(), vec![7, 8, 5]);
+        assert!(!download_path.join("d").exists());
+        assert_eq!(storage.file_handles.lock().unwrap().handles.len(), 1);
+        assert_eq!(
+            std::fs::read(download_path.join("b/c")).unwrap(),
+            data[7..15].to_vec()
+        );
+
+        storage.set_skipped(2, false).unwrap();
+        assert_eq!(
+            std::fs::read(download_path.join("d")).unwrap(),
+            data[15..].to_vec()
+        );
+        assert_eq!(storage.read_piece(1).unwrap(), Some(data[10..].to_vec()));
+
+        storage.delete_files(&download_path).unwrap();
+        assert!(!download_path.join("a").exists());
+        std::fs::remove_dir_all(&download_path).unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+use flat_storage::*;
+use log::debug;
+use std::{collections::HashMap, path::Path, sync::Mutex};
+
+/// Flat storage, which keeps saved pieces in memory.
+///
+/// Nothing is written to disk, pieces are allocated on first write.
+pub struct MemoryFlatStorage {
+    files: Vec<FlatStorageFile>,
+    mapping: Vec<FlatStorageMapping>,
+    saved: Mutex<Vec<usize>>,
+    pieces: Mutex<HashMap<usize, Vec<u8>>>,
+}
+
+impl MemoryFlatStorage {
+    pub fn create(piece_size: usize, files: Vec<FlatStorageFile>) -> Self {
+        let mapping = map_pieces_to_files(piece_size, &files);
+        let saved = Mutex::new(vec![0; files.len()]);
+        Self {
+            files,
+            mapping,
+            saved,
+            pieces: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Drops all saved pieces, `download_path` is ignored.
+    pub fn delete_files<P: AsRef<Path>>(&self, _download_path: P) -> Result<(), std::io::Error> {
+        debug!("deleting pieces from memory");
+        self.pieces.lock().unwrap().clear();
+        self.saved.lock().unwrap().iter_mut().for_each(|x| *x = 0);
+        Ok(())
+    }
+
+    pub fn saved(&self) -> Vec<usize> {
+        self.saved.lock().unwrap().clone()
+    }
+
+    /// Recalculates saved bytes per file from downloaded pieces bitfield.
+    pub fn set_downloaded(&self, downloaded: &[u8]) {
+        let mut saved = self.saved.lock().unwrap();
+        for (index, saved) in saved.iter_mut().enumerate() {
+            *saved = calculate_saved(self.mapping.len(), index, &self.mapping, downloaded);
+        }
+    }
+
+    /// Nothing is created in memory storage, skipped pieces are kept.
+    pub fn set_skipped(&self, _file_index: usize, _skipped: bool) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    pub fn file_info(&self, file_id: usize) -> Option<FileInfo> {
+        file_info(&self.files, &self.mapping, file_id)
+    }
+}
+
+impl FlatStorage for MemoryFlatStorage {
+    fn files(&self) -> &[FlatStorageFile] {
+        &self.files
+    }
+
+    fn read_piece<I: Into<FlatStoragePieceIndex>>(
+        &self,
+        index: I,
+    ) -> Result<Option<Vec<u8>>, FlatStorageError> {
+        Ok(self.pieces.lock().unwrap().get(&*index.into()).cloned())
+    }
+
+    fn write_piece<I: Into<FlatStoragePieceIndex>>(
+        &self,
+        index: I,
+        block: Vec<u8>,
+    ) -> Result<(), FlatStorageError> {
+        let index = *index.into();
+        let map_to_files = &self.mapping[index];
+        let size = map_to_files.0.iter().map(|x| x.size).sum();
+        if block.len() != size {
+            return Err(FlatStorageError::WriteBlock);
+        }
+        let mut saved = self.saved.lock().unwrap();
+        for file_block in &map_to_files.0 {
+            saved[file_block.file_index] += file_block.size;
+        }
+        self.pieces.lock().unwrap().insert(index, block);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_and_read_pieces() {
+        let files = vec![
+            FlatStorageFile {
+                path: "a".into(),
+                length: 7,
+            },
+            FlatStorageFile {
+                path: "b".into(),
+                length: 8,
+            },
+        ];
+        let storage = MemoryFlatStorage::create(10, files);
+
+        assert_eq!(storage.read_piece(1).unwrap(), None);
+        assert!(storage.write_piece(1, vec![1; 10]).is_err());
+
+        storage.write_piece(1, vec![1; 5]).unwrap();
+        assert_eq!(storage.read_piece(1).unwrap(), Some(vec![1; 5]));
+        assert_eq!(storage.saved(), vec![0, 5]);
+        assert_eq!(storage.file_info(1).map(|x| x.piece_offset), Some(7));
+
+        storage.delete_files("").unwrap();
+        assert_eq!(storage.read_piece(1).unwrap(), None);
+        assert_eq!(storage.saved(), vec![0, 0]);
+    }
+}
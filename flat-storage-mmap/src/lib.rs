@@ -3,32 +3,16 @@ use log::debug;
 use memmap::MmapMut;
 use std::{
     fs::{create_dir_all, remove_file, OpenOptions},
-    path::Path,
+    path::{Path, PathBuf},
     sync::Mutex,
 };
 
 pub struct MmapFlatStorage {
+    download_path: PathBuf,
     files: Vec<FlatStorageFile>,
     file_handles: Vec<Mutex<FileHandle>>,
-    mapping: Vec<MmapFlatStorageMapping>,
-}
-
-#[derive(Debug, PartialEq)]
-struct MmapFlatStorageMapping(Vec<FileBlock>);
-
-#[derive(Debug, Clone, PartialEq)]
-struct FileBlock {
-    offset: usize,
-    file_index: usize,
-    file_offset: usize,
-    size: usize,
-}
-
-#[derive(Debug)]
-pub struct FileInfo {
-    pub file: FlatStorageFile,
-    pub piece: usize,
-    pub piece_offset: usize,
+    mapping: Vec<FlatStorageMapping>,
+    part_file: PartFile,
 }
 
 struct FileHandle {
@@ -37,30 +21,37 @@ struct FileHandle {
 }
 
 impl MmapFlatStorage {
-    pub fn create<P: AsRef<Path>>(
+    /// Skipped files are not created, their blocks in pieces of other files go to `part_file`.
+    pub fn create<P: AsRef<Path>, Q: AsRef<Path>>(
         download_path: P,
         piece_count: usize,
         piece_size: usize,
         files: Vec<FlatStorageFile>,
         downloaded: &[u8],
+        skipped: &[bool],
+        part_file: Q,
     ) -> Result<Self, std::io::Error> {
         let mapping = map_pieces_to_files(piece_size, &files);
-        let file_handles = load_files(&download_path, &files, downloaded, &mapping, piece_count)?;
+        let file_handles = load_files(
+            &download_path,
+            &files,
+            downloaded,
+            skipped,
+            &mapping,
+            piece_count,
+        )?;
+        let part_file = PartFile::new(part_file, piece_size, &mapping);
         Ok(Self {
+            download_path: download_path.as_ref().to_path_buf(),
             files,
             file_handles,
             mapping,
+            part_file,
         })
     }
 
     pub fn delete_files<P: AsRef<Path>>(&self, download_path: P) -> Result<(), std::io::Error> {
-        for file_handle in &self.file_handles {
-            if let Some(mut file_handle) = file_handle.lock().ok() {
-                if let Some(mmap) = file_handle.mmap.take() {
-                    mmap.flush()?;
-                }
-            }
-        }
+        self.close()?;
         for file in &self.files {
             let file_path = download_path.as_ref().join(&file.path);
             debug!("deleting file: {:?}", file_path);
@@ -68,6 +59,28 @@ impl MmapFlatStorage {
                 remove_file(file_path)?
             }
         }
+        self.part_file.delete()
+    }
+
+    /// Flushes mapped files and part file, so saved pieces survive a crash.
+    pub fn flush(&self) -> Result<(), std::io::Error> {
+        for file_handle in &self.file_handles {
+            if let Some(mmap) = &file_handle.lock().unwrap().mmap {
+                mmap.flush()?;
+            }
+        }
+        self.part_file.sync()
+    }
+
+    /// Flushes and unmaps files, storage must be created again to access them.
+    pub fn close(&self) -> Result<(), std::io::Error> {
+        for file_handle in &self.file_handles {
+            if let Some(mut file_handle) = file_handle.lock().ok() {
+                if let Some(mmap) = file_handle.mmap.take() {
+                    mmap.flush()?;
+                }
+            }
+        }
         Ok(())
     }
 
@@ -78,31 +91,68 @@ impl MmapFlatStorage {
             .collect()
     }
 
+    /// Recalculates saved bytes per file from downloaded pieces bitfield.
+    pub fn set_downloaded(&self, downloaded: &[u8]) {
+        for (index, file_handle) in self.file_handles.iter().enumerate() {
+            file_handle.lock().unwrap().saved =
+                calculate_saved(self.mapping.len(), index, &self.mapping, downloaded);
+        }
+    }
+
     pub fn file_info(&self, file_id: usize) -> Option<FileInfo> {
-        self.files.get(file_id).cloned().and_then(|file| {
-            self.mapping
-                .iter()
-                .enumerate()
-                .find_map(move |(piece, m)| {
-                    m.0.iter()
-                        .filter(|x| x.file_index == file_id)
-                        .map(|x| (piece, x.offset))
-                        .next()
-                })
-                .map(|(piece, piece_offset)| FileInfo {
-                    file,
-                    piece,
-                    piece_offset,
-                })
-        })
+        file_info(&self.files, &self.mapping, file_id)
+    }
+
+    /// Creates file, which is not skipped anymore, with blocks from part file.
+    ///
+    /// Already created files are kept when skipped.
+    pub fn set_skipped(&self, file_index: usize, skipped: bool) -> Result<(), std::io::Error> {
+        let mut file_handle = self.file_handles[file_index].lock().unwrap();
+        if skipped || file_handle.mmap.is_some() {
+            return Ok(());
+        }
+        let mut mmap = open_mmap(&self.download_path, &self.files[file_index])?;
+        for (piece, map_to_files) in self.mapping.iter().enumerate() {
+            if !self.part_file.contains(piece) {
+                continue;
+            }
+            for file_block in map_to_files.0.iter().filter(|x| x.file_index == file_index) {
+                let data =
+                    &mut mmap[file_block.file_offset..file_block.file_offset + file_block.size];
+                self.part_file.read(piece, file_block, data)?;
+            }
+        }
+        file_handle.mmap = Some(mmap);
+        Ok(())
+    }
+}
+
+fn open_mmap(download_path: &Path, file: &FlatStorageFile) -> Result<MmapMut, std::io::Error> {
+    let file_path = download_path.join(&file.path);
+    if !file_path.is_file() {
+        if let Some(path) = file_path.parent() {
+            debug!("create dir {:?}", path);
+            create_dir_all(path)?;
+        }
     }
+    debug!("create file");
+    let f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(&file_path)?;
+    debug!("set len");
+    f.set_len(file.length as u64)?;
+    debug!("creating mmap...");
+    unsafe { MmapMut::map_mut(&f) }
 }
 
 fn load_files<P: AsRef<Path>>(
     download_path: P,
     files: &[FlatStorageFile],
     downloaded: &[u8],
-    mapping: &[MmapFlatStorageMapping],
+    skipped: &[bool],
+    mapping: &[FlatStorageMapping],
     pieces_count: usize,
 ) -> Result<Vec<Mutex<FileHandle>>, std::io::Error> {
     let mut result = vec![];
@@ -110,22 +160,12 @@ fn load_files<P: AsRef<Path>>(
         let saved = calculate_saved(pieces_count, index, mapping, downloaded);
         let file_path = download_path.as_ref().join(&file.path);
         debug!("checking file: {:?}", file_path);
-        if !file_path.is_file() {
-            if let Some(path) = file_path.parent() {
-                debug!("create dir {:?}", path);
-                create_dir_all(path)?;
-            }
-        }
-        debug!("create file");
-        let f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(&file_path)?;
-        debug!("set len");
-        f.set_len(file.length as u64)?;
-        debug!("creating mmap...");
-        let mmap = Some(unsafe { MmapMut::map_mut(&f)? });
+        let mmap = if skipped.get(index) == Some(&true) && !file_path.is_file() {
+            debug!("skipped file");
+            None
+        } else {
+            Some(open_mmap(download_path.as_ref(), file)?)
+        };
         result.push(Mutex::new(FileHandle { mmap, saved }));
         debug!("processed file: {:?}", file_path);
     }
@@ -137,17 +177,26 @@ impl FlatStorage for MmapFlatStorage {
         &self.files
     }
 
+    /// Piece, which belongs to skipped files only, is not available.
     fn read_piece<I: Into<FlatStoragePieceIndex>>(
         &self,
         index: I,
     ) -> Result<Option<Vec<u8>>, FlatStorageError> {
-        let map_to_files = &self.mapping[*index.into()];
-        let mut result = vec![];
+        let index = *index.into();
+        let map_to_files = &self.mapping[index];
+        let mut result = vec![0; map_to_files.0.iter().map(|x| x.size).sum()];
         for file_block in &map_to_files.0 {
             let f = &self.file_handles[file_block.file_index];
+            let block = &mut result[file_block.offset..file_block.offset + file_block.size];
             if let Some(data) = &f.lock().unwrap().mmap {
                 let data = &data[file_block.file_offset..file_block.file_offset + file_block.size];
-                result.extend_from_slice(data);
+                block.copy_from_slice(data);
+            } else if self.part_file.contains(index) {
+                self.part_file
+                    .read(index, file_block, block)
+                    .map_err(|_| FlatStorageError::ReadBlock)?;
+            } else {
+                return Ok(None);
             }
         }
 
@@ -159,15 +208,26 @@ impl FlatStorage for MmapFlatStorage {
         index: I,
         block: Vec<u8>,
     ) -> Result<(), FlatStorageError> {
-        let map_to_files = &self.mapping[*index.into()];
+        let index = *index.into();
+        let map_to_files = &self.mapping[index];
         for file_block in &map_to_files.0 {
+            // piece of skipped file without part file slot is streamed, so file is created
+            if !self.part_file.contains(index) {
+                self.set_skipped(file_block.file_index, false)
+                    .map_err(|_| FlatStorageError::WriteBlock)?;
+            }
             let f = &self.file_handles[file_block.file_index];
             let mut f_lock = f.lock().unwrap();
             f_lock.saved += file_block.size;
+            let block = &block[file_block.offset..file_block.offset + file_block.size];
             if let Some(data) = f_lock.mmap.as_mut() {
                 let data =
                     &mut data[file_block.file_offset..file_block.file_offset + file_block.size];
-                data.copy_from_slice(&block[file_block.offset..file_block.offset + file_block.size])
+                data.copy_from_slice(block)
+            } else if self.part_file.contains(index) {
+                self.part_file
+                    .write(index, file_block, block)
+                    .map_err(|_| FlatStorageError::WriteBlock)?;
             }
         }
 
@@ -175,215 +235,61 @@ impl FlatStorage for MmapFlatStorage {
     }
 }
 
-fn map_pieces_to_files(
-    piece_size: usize,
-    files: &[FlatStorageFile],
-) -> Vec<MmapFlatStorageMapping> {
-    let mut current_piece_left = piece_size;
-    let mut current_piece = MmapFlatStorageMapping(vec![]);
-    let mut offset = 0;
-
-    let mut mapping = vec![];
-
-    for (file_index, file) in files.iter().enumerate() {
-        let mut file_remaining_length = file.length;
-        let mut file_offset = 0;
-        while current_piece_left < file_remaining_length {
-            current_piece.0.push(FileBlock {
-                offset,
-                file_index,
-                file_offset,
-                size: current_piece_left,
-            });
-
-            file_remaining_length -= current_piece_left;
-            file_offset += current_piece_left;
-            current_piece_left = piece_size;
-
-            mapping.push(current_piece);
-            current_piece = MmapFlatStorageMapping(vec![]);
-            offset = 0;
-        }
-        if current_piece_left >= file_remaining_length {
-            current_piece.0.push(FileBlock {
-                offset,
-                file_index,
-                file_offset,
-                size: file_remaining_length,
-            });
-            current_piece_left -= file_remaining_length;
-            offset += file_remaining_length;
-        }
-    }
-
-    if !current_piece.0.is_empty() {
-        mapping.push(current_piece);
-    }
-
-    mapping
-}
-
-fn calculate_saved(
-    pieces_count: usize,
-    file_index: usize,
-    mapping: &[MmapFlatStorageMapping],
-    downloaded: &[u8],
-) -> usize {
-    let mut saved = 0;
-    for piece in 0..pieces_count {
-        if bit_by_index(piece, downloaded).is_some() {
-            let mapping_block = &mapping[piece];
-            for file_block in &mapping_block.0 {
-                if file_block.file_index == file_index {
-                    saved += file_block.size;
-                }
-            }
-        }
-    }
-    saved
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn pieces_to_files() {
-        let result = map_pieces_to_files(
-            100,
-            &[FlatStorageFile {
-                path: "test".into(),
-                length: 1000,
-            }],
-        );
-        assert_eq!(result.len(), 10);
+    fn skipped_file_blocks_in_part_file() {
+        let download_path = std::env::temp_dir().join(format!(
+            "flat-storage-mmap-{}-{}",
+            std::process::id(),
+            line!()
+        ));
+        let files = vec![
+            FlatStorageFile {
+                path: "a".into(),
+                length: 15,
+            },
+            FlatStorageFile {
+                path: "b".into(),
+                length: 15,
+            },
+        ];
+        let part_file = download_path.join("torrent.parts");
+        let storage = MmapFlatStorage::create(
+            &download_path,
+            3,
+            10,
+            files,
+            &[],
+            &[false, true],
+            &part_file,
+        )
+        .unwrap();
 
-        let result = map_pieces_to_files(
-            1000,
-            &[FlatStorageFile {
-                path: "test".into(),
-                length: 1000,
-            }],
-        );
-        assert_eq!(
-            result,
-            vec![MmapFlatStorageMapping(vec![FileBlock {
-                offset: 0,
-                file_index: 0,
-                file_offset: 0,
-                size: 1000,
-            }])]
-        );
+        assert!(!download_path.join("b").exists());
 
-        let result = map_pieces_to_files(
-            1000,
-            &[FlatStorageFile {
-                path: "test".into(),
-                length: 800,
-            }],
-        );
-        assert_eq!(
-            result,
-            vec![MmapFlatStorageMapping(vec![FileBlock {
-                offset: 0,
-                file_index: 0,
-                file_offset: 0,
-                size: 800,
-            }])]
-        );
+        let data: Vec<u8> = (0..30).collect();
+        for (piece, data) in data.chunks(10).enumerate().take(2) {
+            storage.write_piece(piece, data.to_vec()).unwrap();
+        }
 
-        let result = map_pieces_to_files(
-            333,
-            &[FlatStorageFile {
-                path: "test".into(),
-                length: 1000,
-            }],
-        );
-        assert_eq!(
-            result,
-            vec![
-                MmapFlatStorageMapping(vec![FileBlock {
-                    offset: 0,
-                    file_index: 0,
-                    file_offset: 0,
-                    size: 333,
-                }]),
-                MmapFlatStorageMapping(vec![FileBlock {
-                    offset: 0,
-                    file_index: 0,
-                    file_offset: 333,
-                    size: 333,
-                }]),
-                MmapFlatStorageMapping(vec![FileBlock {
-                    offset: 0,
-                    file_index: 0,
-                    file_offset: 666,
-                    size: 333,
-                }]),
-                MmapFlatStorageMapping(vec![FileBlock {
-                    offset: 0,
-                    file_index: 0,
-                    file_offset: 999,
-                    size: 1,
-                }])
-            ]
-        );
+        assert_eq!(storage.read_piece(1).unwrap(), Some(data[10..20].to_vec()));
+        assert_eq!(storage.read_piece(2).unwrap(), None);
+        assert!(part_file.is_file());
+        assert!(!download_path.join("b").exists());
 
-        let result = map_pieces_to_files(
-            500,
-            &[
-                FlatStorageFile {
-                    path: "test1".into(),
-                    length: 300,
-                },
-                FlatStorageFile {
-                    path: "test2".into(),
-                    length: 400,
-                },
-                FlatStorageFile {
-                    path: "test3".into(),
-                    length: 500,
-                },
-            ],
-        );
+        // last piece has no part file slot, so skipped file is created for it
+        storage.write_piece(2, data[20..].to_vec()).unwrap();
+        assert_eq!(storage.read_piece(2).unwrap(), Some(data[20..].to_vec()));
         assert_eq!(
-            result,
-            vec![
-                MmapFlatStorageMapping(vec![
-                    FileBlock {
-                        offset: 0,
-                        file_index: 0,
-                        file_offset: 0,
-                        size: 300,
-                    },
-                    FileBlock {
-                        offset: 300,
-                        file_index: 1,
-                        file_offset: 0,
-                        size: 200,
-                    }
-                ]),
-                MmapFlatStorageMapping(vec![
-                    FileBlock {
-                        offset: 0,
-                        file_index: 1,
-                        file_offset: 200,
-                        size: 200,
-                    },
-                    FileBlock {
-                        offset: 200,
-                        file_index: 2,
-                        file_offset: 0,
-                        size: 300,
-                    }
-                ]),
-                MmapFlatStorageMapping(vec![FileBlock {
-                    offset: 0,
-                    file_index: 2,
-                    file_offset: 300,
-                    size: 200,
-                }])
-            ]
+            std::fs::read(download_path.join("b")).unwrap()[..5],
+            data[15..20]
         );
+
+        storage.delete_files(&download_path).unwrap();
+        assert!(!part_file.exists());
+        std::fs::remove_dir_all(&download_path).unwrap();
     }
 }
@@ -2,6 +2,14 @@ use failure::Fail;
 use serde::{Deserialize, Serialize};
 use std::{ops::Deref, path::PathBuf};
 
+mod mapping;
+mod part_file;
+
+pub use mapping::{
+    calculate_saved, file_info, map_pieces_to_files, FileBlock, FileInfo, FlatStorageMapping,
+};
+pub use part_file::PartFile;
+
 #[inline]
 pub fn index_in_bitarray(index: usize) -> (usize, u8) {
     (index / 8, 128 >> (index % 8))
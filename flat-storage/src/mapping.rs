@@ -0,0 +1,262 @@
+use crate::{bit_by_index, FlatStorageFile};
+
+/// Blocks of files, which compose single piece.
+#[derive(Debug, PartialEq)]
+pub struct FlatStorageMapping(pub Vec<FileBlock>);
+
+/// Part of piece stored in file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileBlock {
+    /// Offset in piece
+    pub offset: usize,
+    pub file_index: usize,
+    pub file_offset: usize,
+    pub size: usize,
+}
+
+/// First piece of file and offset of file in it.
+#[derive(Debug)]
+pub struct FileInfo {
+    pub file: FlatStorageFile,
+    pub piece: usize,
+    pub piece_offset: usize,
+}
+
+/// Splits pieces into blocks of files.
+pub fn map_pieces_to_files(
+    piece_size: usize,
+    files: &[FlatStorageFile],
+) -> Vec<FlatStorageMapping> {
+    let mut current_piece_left = piece_size;
+    let mut current_piece = FlatStorageMapping(vec![]);
+    let mut offset = 0;
+
+    let mut mapping = vec![];
+
+    for (file_index, file) in files.iter().enumerate() {
+        let mut file_remaining_length = file.length;
+        let mut file_offset = 0;
+        while current_piece_left < file_remaining_length {
+            current_piece.0.push(FileBlock {
+                offset,
+                file_index,
+                file_offset,
+                size: current_piece_left,
+            });
+
+            file_remaining_length -= current_piece_left;
+            file_offset += current_piece_left;
+            current_piece_left = piece_size;
+
+            mapping.push(current_piece);
+            current_piece = FlatStorageMapping(vec![]);
+            offset = 0;
+        }
+        if current_piece_left >= file_remaining_length {
+            current_piece.0.push(FileBlock {
+                offset,
+                file_index,
+                file_offset,
+                size: file_remaining_length,
+            });
+            current_piece_left -= file_remaining_length;
+            offset += file_remaining_length;
+        }
+    }
+
+    if !current_piece.0.is_empty() {
+        mapping.push(current_piece);
+    }
+
+    mapping
+}
+
+/// Bytes of file `file_index` covered by downloaded pieces.
+pub fn calculate_saved(
+    pieces_count: usize,
+    file_index: usize,
+    mapping: &[FlatStorageMapping],
+    downloaded: &[u8],
+) -> usize {
+    let mut saved = 0;
+    for piece in 0..pieces_count {
+        if bit_by_index(piece, downloaded).is_some() {
+            let mapping_block = &mapping[piece];
+            for file_block in &mapping_block.0 {
+                if file_block.file_index == file_index {
+                    saved += file_block.size;
+                }
+            }
+        }
+    }
+    saved
+}
+
+/// Finds where file starts in pieces.
+pub fn file_info(
+    files: &[FlatStorageFile],
+    mapping: &[FlatStorageMapping],
+    file_id: usize,
+) -> Option<FileInfo> {
+    files.get(file_id).cloned().and_then(|file| {
+        mapping
+            .iter()
+            .enumerate()
+            .find_map(move |(piece, m)| {
+                m.0.iter()
+                    .filter(|x| x.file_index == file_id)
+                    .map(|x| (piece, x.offset))
+                    .next()
+            })
+            .map(|(piece, piece_offset)| FileInfo {
+                file,
+                piece,
+                piece_offset,
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pieces_to_files() {
+        let result = map_pieces_to_files(
+            100,
+            &[FlatStorageFile {
+                path: "test".into(),
+                length: 1000,
+            }],
+        );
+        assert_eq!(result.len(), 10);
+
+        let result = map_pieces_to_files(
+            1000,
+            &[FlatStorageFile {
+                path: "test".into(),
+                length: 1000,
+            }],
+        );
+        assert_eq!(
+            result,
+            vec![FlatStorageMapping(vec![FileBlock {
+                offset: 0,
+                file_index: 0,
+                file_offset: 0,
+                size: 1000,
+            }])]
+        );
+
+        let result = map_pieces_to_files(
+            1000,
+            &[FlatStorageFile {
+                path: "test".into(),
+                length: 800,
+            }],
+        );
+        assert_eq!(
+            result,
+            vec![FlatStorageMapping(vec![FileBlock {
+                offset: 0,
+                file_index: 0,
+                file_offset: 0,
+                size: 800,
+            }])]
+        );
+
+        let result = map_pieces_to_files(
+            333,
+            &[FlatStorageFile {
+                path: "test".into(),
+                length: 1000,
+            }],
+        );
+        assert_eq!(
+            result,
+            vec![
+                FlatStorageMapping(vec![FileBlock {
+                    offset: 0,
+                    file_index: 0,
+                    file_offset: 0,
+                    size: 333,
+                }]),
+                FlatStorageMapping(vec![FileBlock {
+                    offset: 0,
+                    file_index: 0,
+                    file_offset: 333,
+                    size: 333,
+                }]),
+                FlatStorageMapping(vec![FileBlock {
+                    offset: 0,
+                    file_index: 0,
+                    file_offset: 666,
+                    size: 333,
+                }]),
+                FlatStorageMapping(vec![FileBlock {
+                    offset: 0,
+                    file_index: 0,
+                    file_offset: 999,
+                    size: 1,
+                }])
+            ]
+        );
+
+        let result = map_pieces_to_files(
+            500,
+            &[
+                FlatStorageFile {
+                    path: "test1".into(),
+                    length: 300,
+                },
+                FlatStorageFile {
+                    path: "test2".into(),
+                    length: 400,
+                },
+                FlatStorageFile {
+                    path: "test3".into(),
+                    length: 500,
+                },
+            ],
+        );
+        assert_eq!(
+            result,
+            vec![
+                FlatStorageMapping(vec![
+                    FileBlock {
+                        offset: 0,
+                        file_index: 0,
+                        file_offset: 0,
+                        size: 300,
+                    },
+                    FileBlock {
+                        offset: 300,
+                        file_index: 1,
+                        file_offset: 0,
+                        size: 200,
+                    }
+                ]),
+                FlatStorageMapping(vec![
+                    FileBlock {
+                        offset: 0,
+                        file_index: 1,
+                        file_offset: 200,
+                        size: 200,
+                    },
+                    FileBlock {
+                        offset: 200,
+                        file_index: 2,
+                        file_offset: 0,
+                        size: 300,
+                    }
+                ]),
+                FlatStorageMapping(vec![FileBlock {
+                    offset: 0,
+                    file_index: 2,
+                    file_offset: 300,
+                    size: 200,
+                }])
+            ]
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{FileBlock, FlatStorageMapping};
+use std::{
+    collections::HashMap,
+    fs::{remove_file, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Blocks of skipped files, which share pieces with other files.
+///
+/// Every piece of several files has own slot, so layout does not depend on priorities.
+/// File is created on first write.
+pub struct PartFile {
+    path: PathBuf,
+    piece_size: usize,
+    slots: HashMap<usize, usize>,
+    file: Mutex<Option<File>>,
+}
+
+impl PartFile {
+    pub fn new<P: AsRef<Path>>(path: P, piece_size: usize, mapping: &[FlatStorageMapping]) -> Self {
+        let slots = mapping
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.0.len() > 1)
+            .enumerate()
+            .map(|(slot, (piece, _))| (piece, slot))
+            .collect();
+        Self {
+            path: path.as_ref().to_path_buf(),
+            piece_size,
+            slots,
+            file: Mutex::new(None),
+        }
+    }
+
+    /// Piece can be stored with blocks in part file.
+    pub fn contains(&self, piece: usize) -> bool {
+        self.slots.contains_key(&piece)
+    }
+
+    fn offset(&self, piece: usize, block: &FileBlock) -> io::Result<u64> {
+        self.slots
+            .get(&piece)
+            .map(|slot| (slot * self.piece_size + block.offset) as u64)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "piece has no part slot"))
+    }
+
+    /// Reads block, never written data is returned as zeroes.
+    pub fn read(&self, piece: usize, block: &FileBlock, data: &mut [u8]) -> io::Result<()> {
+        let offset = self.offset(piece, block)?;
+        let mut file = self.file.lock().unwrap();
+        if file.is_none() {
+            if !self.path.is_file() {
+                data.iter_mut().for_each(|x| *x = 0);
+                return Ok(());
+            }
+            *file = Some(self.open()?);
+        }
+        let file = file.as_mut().unwrap();
+        file.seek(SeekFrom::Start(offset))?;
+        let mut read = 0;
+        while read < data.len() {
+            match file.read(&mut data[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        data[read..].iter_mut().for_each(|x| *x = 0);
+        Ok(())
+    }
+
+    pub fn write(&self, piece: usize, block: &FileBlock, data: &[u8]) -> io::Result<()> {
+        let offset = self.offset(piece, block)?;
+        let mut file = self.file.lock().unwrap();
+        if file.is_none() {
+            *file = Some(self.open()?);
+        }
+        let file = file.as_mut().unwrap();
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(data)
+    }
+
+    /// Syncs written blocks to disk, nothing is done if file was never opened.
+    pub fn sync(&self) -> io::Result<()> {
+        if let Some(file) = self.file.lock().unwrap().as_ref() {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    pub fn delete(&self) -> io::Result<()> {
+        self.file.lock().unwrap().take();
+        if self.path.is_file() {
+            remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+
+    fn open(&self) -> io::Result<File> {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map_pieces_to_files, FlatStorageFile};
+
+    #[test]
+    fn part_file_slots() {
+        let mapping = map_pieces_to_files(
+            10,
+            &[
+                FlatStorageFile {
+                    path: "a".into(),
+                    length: 25,
+                },
+                FlatStorageFile {
+                    path: "b".into(),
+                    length: 20,
+                },
+            ],
+        );
+        let path = std::env::temp_dir().join(format!("flat-storage-{}.parts", std::process::id()));
+        let part_file = PartFile::new(&path, 10, &mapping);
+
+        assert!(!part_file.contains(0));
+        assert!(part_file.contains(2));
+        assert!(!part_file.contains(3));
+
+        let block = &mapping[2].0[1];
+        let mut data = vec![1; block.size];
+        part_file.read(2, block, &mut data).unwrap();
+        assert_eq!(data, vec![0; 5]);
+
+        part_file.write(2, block, &[1, 2, 3, 4, 5]).unwrap();
+        part_file.read(2, block, &mut data).unwrap();
+        assert_eq!(data, vec![1, 2, 3, 4, 5]);
+        assert!(part_file.write(0, &mapping[0].0[0], &[0; 10]).is_err());
+
+        part_file.delete().unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,3 @@
+pub(crate) enum AltSpeedMessage {
+    Check,
+}
@@ -0,0 +1,139 @@
+use crate::{
+    alt_speed::AltSpeedMessage,
+    command::{Command, CommandAltSpeedMode},
+    event_loop::{EventLoopCommand, EventLoopRunner, EventLoopSender},
+    request_response::RequestResponse,
+    types::public::AltSpeedPeriod,
+    RsbtError,
+};
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use log::debug;
+use tokio::time::{delay_for, Duration};
+
+const ALT_SPEED_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Switches alternative speed limits on schedule boundaries.
+///
+/// Only transitions are sent, so manual switch stays until next boundary.
+pub(crate) struct AltSpeedScheduler {
+    schedule: Vec<AltSpeedPeriod>,
+    scheduled: bool,
+    timer: Option<EventLoopCommand>,
+    sender: Option<EventLoopSender<AltSpeedMessage, Command>>,
+}
+
+impl AltSpeedScheduler {
+    pub(crate) fn new(schedule: Vec<AltSpeedPeriod>) -> Self {
+        Self {
+            schedule,
+            scheduled: false,
+            timer: None,
+            sender: None,
+        }
+    }
+
+    fn scheduled_at(&self, now: NaiveDateTime) -> bool {
+        self.schedule.iter().any(|x| x.contains(now))
+    }
+
+    async fn check(&mut self) -> Result<(), RsbtError> {
+        let scheduled = self.scheduled_at(Local::now().naive_local());
+        if scheduled != self.scheduled {
+            debug!("alternative speed limits scheduled: {}", scheduled);
+            self.scheduled = scheduled;
+            self.feedback(Command::AltSpeedMode(RequestResponse::RequestOnly(
+                CommandAltSpeedMode { enabled: scheduled },
+            )))
+            .await?;
+        }
+
+        self.timer = self.command(
+            async {
+                delay_for(ALT_SPEED_CHECK_INTERVAL).await;
+                Ok(())
+            },
+            |_: Result<(), RsbtError>| AltSpeedMessage::Check,
+        );
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventLoopRunner<AltSpeedMessage, Command> for AltSpeedScheduler {
+    fn set_sender(&mut self, sender: EventLoopSender<AltSpeedMessage, Command>) {
+        self.sender = Some(sender);
+    }
+
+    fn sender(&mut self) -> Option<&mut EventLoopSender<AltSpeedMessage, Command>> {
+        self.sender.as_mut()
+    }
+
+    async fn start(&mut self) -> Result<(), RsbtError> {
+        self.check().await
+    }
+
+    async fn stop(&mut self) -> Result<(), RsbtError> {
+        if let Some(timer) = self.timer.take() {
+            timer.abort();
+        }
+        Ok(())
+    }
+
+    async fn handle(&mut self, message: AltSpeedMessage) -> Result<(), RsbtError> {
+        match message {
+            AltSpeedMessage::Check => self.check().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AltSpeedPeriod, AltSpeedScheduler};
+    use chrono::{NaiveDate, NaiveTime, Weekday};
+
+    fn period(days: Vec<Weekday>, from: (u32, u32), to: (u32, u32)) -> AltSpeedPeriod {
+        AltSpeedPeriod {
+            days,
+            from: NaiveTime::from_hms(from.0, from.1, 0),
+            to: NaiveTime::from_hms(to.0, to.1, 0),
+        }
+    }
+
+    #[test]
+    fn alt_speed_office_hours() {
+        let scheduler = AltSpeedScheduler::new(vec![period(
+            vec![Weekday::Mon, Weekday::Tue],
+            (9, 0),
+            (18, 0),
+        )]);
+        // 2020-06-01 is monday
+        let monday = NaiveDate::from_ymd(2020, 6, 1);
+        assert!(!scheduler.scheduled_at(monday.and_hms(8, 59, 59)));
+        assert!(scheduler.scheduled_at(monday.and_hms(9, 0, 0)));
+        assert!(!scheduler.scheduled_at(monday.and_hms(18, 0, 0)));
+        assert!(!scheduler.scheduled_at(NaiveDate::from_ymd(2020, 6, 3).and_hms(12, 0, 0)));
+    }
+
+    #[test]
+    fn alt_speed_over_midnight() {
+        let scheduler = AltSpeedScheduler::new(vec![period(vec![Weekday::Fri], (22, 0), (6, 0))]);
+        let friday = NaiveDate::from_ymd(2020, 6, 5);
+        let saturday = NaiveDate::from_ymd(2020, 6, 6);
+        assert!(!scheduler.scheduled_at(friday.and_hms(3, 0, 0)));
+        assert!(scheduler.scheduled_at(friday.and_hms(23, 0, 0)));
+        assert!(scheduler.scheduled_at(saturday.and_hms(5, 59, 0)));
+        assert!(!scheduler.scheduled_at(saturday.and_hms(22, 30, 0)));
+    }
+
+    #[test]
+    fn alt_speed_every_day() {
+        let scheduler = AltSpeedScheduler::new(vec![period(vec![], (0, 0), (7, 0))]);
+        for day in 1..=7 {
+            let date = NaiveDate::from_ymd(2020, 6, day);
+            assert!(scheduler.scheduled_at(date.and_hms(6, 0, 0)));
+            assert!(!scheduler.scheduled_at(date.and_hms(7, 0, 0)));
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod alt_speed_message;
+mod alt_speed_scheduler;
+
+pub(crate) use alt_speed_message::AltSpeedMessage;
+pub(crate) use alt_speed_scheduler::AltSpeedScheduler;
@@ -2,8 +2,8 @@ use crate::{
     announce::Announcement,
     errors::RsbtError,
     process::TorrentToken,
+    proxy::ProxyConnector,
     types::{Properties, TrackerAnnounce},
-    PEER_ID,
 };
 use http_body::Body;
 use hyper::Client;
@@ -22,26 +22,44 @@ pub(crate) async fn http_announce(
     torrent_process: Arc<TorrentToken>,
     announce_url: &str,
 ) -> Result<Announcement, RsbtError> {
-    let client: Client<_> = Client::new();
-
     let left = torrent_process.info.len();
+    let port = torrent_process
+        .external_ip
+        .port()
+        .unwrap_or(properties.port);
     let mut url = {
         format!(
             "{}?info_hash={}&peer_id={}&left={}&port={}",
             announce_url,
             url_encode(&torrent_process.hash_id[..]),
-            url_encode(&PEER_ID[..]),
+            url_encode(&properties.peer_id[..]),
             left,
-            properties.port,
+            port,
         )
     };
 
+    if let Some(ip) = torrent_process
+        .external_ip
+        .get()
+        .filter(|_| !properties.anonymous)
+    {
+        url += &format!("&ip={}", ip);
+    }
+
     if let Some(compact) = properties.compact {
         url += &format!("&compact={}", if compact { 1 } else { 0 });
     }
 
     let uri = url.parse()?;
-    let res = client.get(uri).await;
+    let res = match properties.proxy.as_ref().filter(|x| x.trackers) {
+        Some(proxy) => {
+            Client::builder()
+                .build::<_, hyper::Body>(ProxyConnector::new(proxy.clone()))
+                .get(uri)
+                .await
+        }
+        None => Client::new().get(uri).await,
+    };
 
     debug!("Got tracker announce from: {}", url);
 
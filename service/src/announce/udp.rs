@@ -1,13 +1,18 @@
 use crate::{
     announce::{Announcement, UdpTrackerClient},
     process::TorrentToken,
-    types::Properties,
-    RsbtError,
+    proxy::{socks5_udp_associate, Socks5UdpFramed},
+    types::{udp_tracker::UdpTrackerCodec, Properties, ProxyConfig, ProxyKind},
+    RsbtError, PEER_CONNECT_TIMEOUT,
 };
 use log::debug;
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
 use tokio::net::lookup_host;
 use tokio::net::UdpSocket;
+use tokio::time::timeout;
 
 const UDP_PREFIX: &str = "udp://";
 
@@ -16,15 +21,20 @@ pub(crate) async fn udp_announce(
     torrent_process: Arc<TorrentToken>,
     announce_url: &str,
 ) -> Result<Announcement, RsbtError> {
-    let addr = SocketAddr::new(properties.listen, properties.port);
-    let udp_socket = UdpSocket::bind(addr).await?;
-
     let announce_url = &announce_url[UDP_PREFIX.len()..];
     debug!("connecting to {}", announce_url);
 
+    if let Some(proxy) = properties.proxy.as_ref().filter(|x| x.trackers) {
+        return proxied_udp_announce(proxy, properties.clone(), torrent_process, announce_url)
+            .await;
+    }
+
     let mut addrs = lookup_host(announce_url).await?;
     if let Some(addr) = addrs.next() {
         debug!("resolved addr: {}", addr);
+
+        let udp_socket =
+            UdpSocket::bind(SocketAddr::new(properties.listen, properties.port)).await?;
         let mut udp_tracker_client = UdpTrackerClient::new(udp_socket, addr);
 
         udp_tracker_client
@@ -34,3 +44,78 @@ pub(crate) async fn udp_announce(
         Err(RsbtError::UdpTrackerImplementation)
     }
 }
+
+/// Announce through SOCKS5 UDP relay, HTTP proxy cannot relay datagrams.
+///
+/// Tracker host name is resolved by proxy, so no DNS requests leave this host.
+async fn proxied_udp_announce(
+    proxy: &ProxyConfig,
+    properties: Arc<Properties>,
+    torrent_process: Arc<TorrentToken>,
+    announce_url: &str,
+) -> Result<Announcement, RsbtError> {
+    if proxy.kind != ProxyKind::Socks5 {
+        return Err(RsbtError::ProxyUdpUnsupported);
+    }
+
+    let (host, port) = split_host_port(announce_url)
+        .ok_or_else(|| RsbtError::UdpTrackerAddress(announce_url.into()))?;
+
+    let (control, relay) = timeout(PEER_CONNECT_TIMEOUT, socks5_udp_associate(proxy)).await??;
+    debug!("udp relay: {}", relay);
+
+    let unspecified: IpAddr = if relay.is_ipv4() {
+        Ipv4Addr::UNSPECIFIED.into()
+    } else {
+        Ipv6Addr::UNSPECIFIED.into()
+    };
+    let udp_socket = UdpSocket::bind(SocketAddr::new(unspecified, 0)).await?;
+
+    let framed = Socks5UdpFramed::new(
+        udp_socket,
+        UdpTrackerCodec,
+        relay,
+        control,
+        Some(host.into()),
+    );
+    // address is replaced with host name by codec, only port is sent
+    let addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port);
+    let mut udp_tracker_client = UdpTrackerClient::with_framed(framed, addr);
+
+    udp_tracker_client
+        .announce(properties, torrent_process)
+        .await
+}
+
+/// Splits `host:port` of tracker, path after port is ignored.
+fn split_host_port(announce_url: &str) -> Option<(&str, u16)> {
+    let address = announce_url.split('/').next()?;
+    let position = address.rfind(':')?;
+    let port = address[position + 1..].parse().ok()?;
+    let host = &address[..position];
+
+    if host.is_empty() {
+        None
+    } else {
+        Some((host, port))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_tracker_host_port() {
+        assert_eq!(
+            split_host_port("tracker.org:6969/announce"),
+            Some(("tracker.org", 6969))
+        );
+        assert_eq!(
+            split_host_port("[2001:db8::1]:80"),
+            Some(("[2001:db8::1]", 80))
+        );
+        assert_eq!(split_host_port("tracker.org"), None);
+        assert_eq!(split_host_port(":6969"), None);
+    }
+}
@@ -43,6 +43,14 @@ where
         + Unpin
         + Send,
 {
+    pub(crate) fn with_framed(framed: T, addr: SocketAddr) -> Self {
+        Self {
+            connection_id: None,
+            framed,
+            addr,
+        }
+    }
+
     async fn connection_id<P, TT>(
         &mut self,
         properties: Arc<P>,
@@ -251,6 +259,14 @@ mod tests {
         fn port(&self) -> u16 {
             9999
         }
+
+        fn peer_id(&self) -> &[u8; 20] {
+            b"-rs0100-zzzzxxxxyyyy"
+        }
+
+        fn anonymous(&self) -> bool {
+            false
+        }
     }
 
     struct TestTorrentToken(TorrentInfo);
@@ -263,6 +279,14 @@ mod tests {
         fn hash_id(&self) -> &[u8; crate::SHA1_SIZE] {
             &[0; crate::SHA1_SIZE]
         }
+
+        fn external_ip(&self) -> Option<IpAddr> {
+            None
+        }
+
+        fn external_port(&self) -> Option<u16> {
+            None
+        }
     }
 
     #[tokio::test]
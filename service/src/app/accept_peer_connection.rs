@@ -1,27 +1,54 @@
-use crate::{command::Command, event::TorrentEvent, types::Handshake, RsbtError};
+use crate::{
+    command::Command,
+    event::TorrentEvent,
+    mse::{mse_accept, MseStream},
+    types::{EncryptionMode, Handshake, HANDSHAKE_PREFIX},
+    RsbtError, PEER_HANDSHAKE_TIMEOUT, SHA1_SIZE,
+};
 use log::{debug, error};
 use std::convert::TryInto;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::TcpStream,
     sync::{mpsc::Sender, oneshot},
+    time::timeout,
 };
 
+/// Plaintext handshake starts with protocol name, anything else is MSE public key.
+const PROTOCOL_PREFIX_SIZE: usize = 20;
+
 pub(crate) async fn accept_peer_connection(
-    mut socket: TcpStream,
+    socket: TcpStream,
     mut sender: Sender<Command>,
+    encryption: EncryptionMode,
 ) -> Result<(), RsbtError> {
+    let mut stream = match timeout(
+        PEER_HANDSHAKE_TIMEOUT,
+        accept_stream(socket, sender.clone(), encryption),
+    )
+    .await
+    .map_err(|_| RsbtError::PeerHandshakeTimeout)??
+    {
+        Some(stream) => stream,
+        None => return Ok(()),
+    };
+
     let mut handshake_request = vec![0u8; 68];
 
-    socket.read_exact(&mut handshake_request).await?;
+    timeout(
+        PEER_HANDSHAKE_TIMEOUT,
+        stream.read_exact(&mut handshake_request),
+    )
+    .await
+    .map_err(|_| RsbtError::PeerHandshakeTimeout)??;
 
-    let handshake_request: Handshake = handshake_request.try_into()?;
+    let handshake: Handshake = handshake_request.try_into()?;
 
     let (handshake_sender, handshake_receiver) = oneshot::channel();
 
     sender
         .send(Command::TorrentHandshake {
-            handshake_request,
+            handshake_request: handshake.clone(),
             handshake_sender,
         })
         .await?;
@@ -38,15 +65,120 @@ pub(crate) async fn accept_peer_connection(
         }
     };
 
-    socket.write_all(&torrent_process.handshake).await?;
+    // dialing side reads our peer id too, so it does not connect to its own address again
+    stream.write_all(&torrent_process.handshake).await?;
+
+    if handshake.peer_id == torrent_process.peer_id {
+        debug!("connection to self, closing connection");
+        return Ok(());
+    }
 
     debug!("handshake done, connected with peer");
 
     torrent_process
         .broker_sender
         .clone()
-        .send(TorrentEvent::PeerForwarded(socket))
+        .send(TorrentEvent::PeerForwarded(stream, handshake))
         .await?;
 
     Ok(())
 }
+
+/// Detects encrypted connection and performs MSE handshake if allowed.
+///
+/// Connections not matching encryption mode are dropped.
+async fn accept_stream(
+    mut socket: TcpStream,
+    sender: Sender<Command>,
+    encryption: EncryptionMode,
+) -> Result<Option<MseStream>, RsbtError> {
+    let mut prefix = [0u8; PROTOCOL_PREFIX_SIZE];
+    socket.read_exact(&mut prefix).await?;
+
+    let plaintext = prefix[..] == HANDSHAKE_PREFIX[..PROTOCOL_PREFIX_SIZE];
+
+    match encryption {
+        EncryptionMode::Forced if plaintext => {
+            debug!("plaintext connection refused, encryption is forced");
+            Ok(None)
+        }
+        EncryptionMode::Disabled if !plaintext => {
+            debug!("encrypted connection refused, encryption is disabled");
+            Ok(None)
+        }
+        _ if plaintext => Ok(Some(MseStream::new(socket, None, None, prefix[..].into()))),
+        _ => Ok(Some(
+            mse_accept(socket, &prefix, encryption, |obfuscated_hash| {
+                find_info_hash(sender, obfuscated_hash)
+            })
+            .await?,
+        )),
+    }
+}
+
+async fn find_info_hash(
+    mut sender: Sender<Command>,
+    obfuscated_hash: [u8; SHA1_SIZE],
+) -> Result<Option<[u8; SHA1_SIZE]>, RsbtError> {
+    let (hash_sender, hash_receiver) = oneshot::channel();
+
+    sender
+        .send(Command::TorrentObfuscatedHash {
+            obfuscated_hash,
+            hash_sender,
+        })
+        .await?;
+
+    Ok(hash_receiver.await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{peer::connect_to_peer, process::TorrentToken, DEFAULT_CHANNEL_BUFFER};
+    use futures::StreamExt;
+    use tokio::{net::TcpListener, sync::mpsc};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn check_self_connection() -> Result<(), RsbtError> {
+        let (broker_sender, mut broker_receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        let torrent_process = TorrentToken::sample(broker_sender);
+        let (command_sender, mut command_receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+
+        let mut listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.expect("accepted connection");
+            accept_peer_connection(socket, command_sender, EncryptionMode::Disabled)
+                .await
+                .expect("accepted handshake");
+        });
+        let accepted_torrent_process = torrent_process.clone();
+        tokio::spawn(async move {
+            if let Some(Command::TorrentHandshake {
+                handshake_sender, ..
+            }) = command_receiver.next().await
+            {
+                let _ = handshake_sender.send(Some(accepted_torrent_process));
+            }
+        });
+
+        let peer_id = Uuid::new_v4();
+        connect_to_peer(
+            torrent_process,
+            peer_id,
+            addr.into(),
+            None,
+            EncryptionMode::Disabled,
+        )
+        .await?;
+
+        match broker_receiver.next().await {
+            Some(TorrentEvent::PeerSelfConnection(id)) => assert_eq!(id, peer_id),
+            Some(event) => panic!("unexpected event {}", event),
+            None => panic!("no event"),
+        }
+        Ok(())
+    }
+}
@@ -1,27 +1,43 @@
 use crate::{
+    alt_speed::AltSpeedScheduler,
     app::{accept_peer_connection, CurrentTorrents},
+    bandwidth::{BandwidthLimiter, TorrentBandwidth},
     command::{
-        Command, CommandAddTorrent, CommandDeleteTorrent, CommandTorrentAction,
-        CommandTorrentAnnounce, CommandTorrentDetail, CommandTorrentFileDownload,
-        CommandTorrentFiles, CommandTorrentPeers, CommandTorrentPieces,
+        Command, CommandAddTorrent, CommandAltSpeedMode, CommandBandwidthLimits,
+        CommandDeleteTorrent, CommandTorrentAction, CommandTorrentAddPeer, CommandTorrentAnnounce,
+        CommandTorrentBanPeer, CommandTorrentBandwidthLimits, CommandTorrentDetail,
+        CommandTorrentFileDownload, CommandTorrentFilePriority, CommandTorrentFiles,
+        CommandTorrentMove, CommandTorrentPeers, CommandTorrentPieces, CommandTorrentRename,
+        CommandTorrentUnbanPeer,
     },
     event::{torrent_event_loop, TorrentEvent},
+    event_loop::EventLoop,
     file_download::FileDownloadStream,
+    mse::obfuscated_info_hash,
     parser::parse_torrent,
+    peer::{BanList, ConnectionSlots, IpFilter},
+    port_mapping::{ExternalIp, PortMappingRunner},
     process::{
         find_process_by_id, TorrentProcess, TorrentProcessHeader, TorrentProcessStatus,
         TorrentToken,
     },
     request_response::RequestResponse,
-    storage::TorrentStorage,
+    storage::{TorrentStorage, TorrentStorageState},
     types::{
-        public::{AnnounceView, FileView, PeerView, TorrentAction, TorrentDownloadView},
-        Properties, HANDSHAKE_PREFIX,
+        public::{
+            AltSpeedView, AnnounceView, BandwidthLimits, BandwidthView, FileView, IpFilterView,
+            PeerView, StatusView, TorrentAction, TorrentDownloadView,
+        },
+        EncryptionMode, Properties, StorageBackend, HANDSHAKE_PREFIX,
     },
-    RsbtError, DEFAULT_CHANNEL_BUFFER, PEER_ID, TORRENTS_TOML,
+    RsbtError, DEFAULT_CHANNEL_BUFFER, TORRENTS_TOML,
 };
-use futures::{future::join, prelude::*};
-use log::{debug, error};
+use futures::{
+    future::{select, Either},
+    pin_mut,
+    prelude::*,
+};
+use log::{debug, error, warn};
 use std::{
     net::SocketAddr,
     path::{Path, PathBuf},
@@ -30,10 +46,18 @@ use std::{
 use tokio::{
     fs,
     net::TcpListener,
-    sync::mpsc::{self, Receiver, Sender},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        watch,
+    },
 };
 
-async fn command_loop(app: &mut App, mut events: Receiver<Command>) {
+/// Handles commands until shutdown, shutdown request is returned to respond after cleanup.
+async fn command_loop(
+    app: &mut App,
+    sender: Sender<Command>,
+    mut events: Receiver<Command>,
+) -> Option<RequestResponse<(), Result<(), RsbtError>>> {
     while let Some(event) = events.next().await {
         match event {
             Command::AddTorrent(request_response) => {
@@ -64,6 +88,24 @@ async fn command_loop(app: &mut App, mut events: Receiver<Command>) {
                     error!("cannot send handshake, receiver is dropped");
                 }
             }
+            Command::TorrentObfuscatedHash {
+                obfuscated_hash,
+                hash_sender,
+            } => {
+                debug!("searching for a torrent matching the obfuscated hash");
+
+                if hash_sender
+                    .send(
+                        app.torrents
+                            .iter()
+                            .map(|x| x.process.hash_id)
+                            .find(|x| obfuscated_info_hash(x) == obfuscated_hash),
+                    )
+                    .is_err()
+                {
+                    error!("cannot send info hash, receiver is dropped");
+                }
+            }
             Command::TorrentList(request_response) => {
                 debug!("collecting torrent list");
                 let torrents_view = app.torrents.iter().map(TorrentDownloadView::from).collect();
@@ -97,6 +139,30 @@ async fn command_loop(app: &mut App, mut events: Receiver<Command>) {
                     error!("cannot send response for torrent's peers: {}", err);
                 }
             }
+            Command::TorrentAddPeer(request_response) => {
+                debug!("torrent add peer");
+                let response = app.torrent_add_peer(request_response.request()).await;
+
+                if let Err(err) = request_response.response(response) {
+                    error!("cannot send response for torrent add peer: {}", err);
+                }
+            }
+            Command::TorrentBanPeer(request_response) => {
+                debug!("torrent ban peer");
+                let response = app.torrent_ban_peer(request_response.request()).await;
+
+                if let Err(err) = request_response.response(response) {
+                    error!("cannot send response for torrent ban peer: {}", err);
+                }
+            }
+            Command::TorrentUnbanPeer(request_response) => {
+                debug!("torrent unban peer");
+                let response = app.torrent_unban_peer(request_response.request()).await;
+
+                if let Err(err) = request_response.response(response) {
+                    error!("cannot send response for torrent unban peer: {}", err);
+                }
+            }
             Command::TorrentAnnounces(request_response) => {
                 debug!("torrent's announces");
                 let response = app.torrent_announces(request_response.request()).await;
@@ -145,43 +211,217 @@ async fn command_loop(app: &mut App, mut events: Receiver<Command>) {
                     error!("cannot send response for torrent's detail: {}", err);
                 }
             }
+            Command::TorrentBandwidthLimits(request_response) => {
+                debug!("torrent's bandwidth limits");
+                let response = app
+                    .torrent_bandwidth_limits(request_response.request())
+                    .await;
+
+                if let Err(err) = request_response.response(response) {
+                    error!(
+                        "cannot send response for torrent's bandwidth limits: {}",
+                        err
+                    );
+                }
+            }
+            Command::TorrentFilePriority(request_response) => {
+                debug!("torrent's file priority");
+                let response = app.torrent_file_priority(request_response.request()).await;
+
+                if let Err(err) = request_response.response(response) {
+                    error!("cannot send response for torrent's file priority: {}", err);
+                }
+            }
+            Command::TorrentMove(request_response) => {
+                debug!("torrent move");
+                let response = app
+                    .torrent_move(request_response.request(), sender.clone())
+                    .await;
+
+                if let Err(err) = request_response.response(response) {
+                    error!("cannot send response for torrent move: {}", err);
+                }
+            }
+            Command::TorrentMoved { id, save_to } => {
+                debug!("torrent moved");
+                if let Err(err) = app.torrent_moved(id, save_to).await {
+                    error!("cannot save moved torrent: {}", err);
+                }
+            }
+            Command::TorrentRename(request_response) => {
+                debug!("torrent rename");
+                let response = app
+                    .torrent_rename(request_response.request(), sender.clone())
+                    .await;
+
+                if let Err(err) = request_response.response(response) {
+                    error!("cannot send response for torrent rename: {}", err);
+                }
+            }
+            Command::TorrentRenamed {
+                id,
+                folder,
+                file_paths,
+            } => {
+                debug!("torrent renamed");
+                if let Err(err) = app.torrent_renamed(id, folder, file_paths).await {
+                    error!("cannot save renamed torrent: {}", err);
+                }
+            }
+            Command::Bandwidth(request_response) => {
+                debug!("bandwidth");
+                let response = Ok(app.bandwidth());
+
+                if let Err(err) = request_response.response(response) {
+                    error!("cannot send response for bandwidth: {}", err);
+                }
+            }
+            Command::BandwidthLimits(request_response) => {
+                debug!("bandwidth limits");
+                let response = app.bandwidth_limits(request_response.request());
+
+                if let Err(err) = request_response.response(response) {
+                    error!("cannot send response for bandwidth limits: {}", err);
+                }
+            }
+            Command::AltSpeed(request_response) => {
+                debug!("alternative speed");
+                let response = Ok(app.alt_speed_view());
+
+                if let Err(err) = request_response.response(response) {
+                    error!("cannot send response for alternative speed: {}", err);
+                }
+            }
+            Command::AltSpeedMode(request_response) => {
+                debug!("alternative speed mode");
+                let response = app.alt_speed_mode(request_response.request()).await;
+
+                if let Err(err) = request_response.response(response) {
+                    error!("cannot send response for alternative speed mode: {}", err);
+                }
+            }
+            Command::IpFilter(request_response) => {
+                debug!("ip filter");
+                let response = Ok(app.ip_filter.view());
+
+                if let Err(err) = request_response.response(response) {
+                    error!("cannot send response for ip filter: {}", err);
+                }
+            }
+            Command::IpFilterReload(request_response) => {
+                debug!("ip filter reload");
+                let response = app.ip_filter_reload().await;
+
+                if let Err(err) = request_response.response(response) {
+                    error!("cannot send response for ip filter reload: {}", err);
+                }
+            }
+            Command::Status(request_response) => {
+                debug!("status");
+                let response = Ok(app.status());
+
+                if let Err(err) = request_response.response(response) {
+                    error!("cannot send response for status: {}", err);
+                }
+            }
+            Command::Shutdown(request_response) => {
+                debug!("shutdown");
+                app.save_state().await;
+                return Some(request_response);
+            }
         }
     }
 
     debug!("download_events_loop done");
+
+    None
 }
 
 pub(crate) async fn accept_connections_loop(
-    addr: SocketAddr,
+    mut listener: TcpListener,
     sender: Sender<Command>,
+    ip_filter: Arc<IpFilter>,
+    refuse_incoming: bool,
+    encryption: EncryptionMode,
 ) -> Result<(), RsbtError> {
-    debug!("listening on: {}", &addr);
-    let mut listener = TcpListener::bind(addr).await?;
-
     loop {
-        let (socket, _) = listener.accept().await?;
+        let (socket, addr) = listener.accept().await?;
+        if refuse_incoming {
+            debug!(
+                "drop connection from {}, incoming connections are refused",
+                addr
+            );
+            continue;
+        }
+        if ip_filter.is_blocked(&addr.ip()) {
+            debug!("drop connection from {} blocked by ip filter", addr);
+            continue;
+        }
         let sender_task = sender.clone();
         tokio::spawn(async move {
-            if let Err(err) = accept_peer_connection(socket, sender_task).await {
-                format!("peer connection {} failed: {}", addr, err);
+            if let Err(err) = accept_peer_connection(socket, sender_task, encryption).await {
+                error!("peer connection {} failed: {}", addr, err);
             }
         });
     }
 }
 
+/// Waits until torrent files are moved or renamed, `moved` is sent back on success to be saved.
+async fn watch_move(
+    mut storage_state_watch: watch::Receiver<TorrentStorageState>,
+    mut sender: Sender<Command>,
+    moved: Command,
+) {
+    while let Some(state) = storage_state_watch.recv().await {
+        if state.moving.is_some() {
+            continue;
+        }
+        if state.move_error.is_none() {
+            if let Err(err) = sender.send(moved).await {
+                error!("cannot send moved torrent: {}", err);
+            }
+        }
+        break;
+    }
+}
+
 pub struct App {
     pub properties: Arc<Properties>,
     pub(crate) torrents: Vec<TorrentProcess>,
     pub(crate) id: usize,
+    pub(crate) connection_slots: Arc<ConnectionSlots>,
+    pub(crate) ban_list: Arc<BanList>,
+    pub(crate) ip_filter: Arc<IpFilter>,
+    pub(crate) external_ip: Arc<ExternalIp>,
+    pub(crate) bandwidth: Arc<BandwidthLimiter>,
+    pub(crate) limits: BandwidthLimits,
+    pub(crate) alt_limits: BandwidthLimits,
+    pub(crate) alt_speed: bool,
 }
 
 impl App {
     pub fn new(properties: Properties) -> Self {
+        let connection_slots = Arc::new(ConnectionSlots::from_properties(&properties));
+        let bandwidth = Arc::new(BandwidthLimiter::from_properties(&properties));
+        let ip_filter = Arc::new(IpFilter::from_properties(&properties));
+        let limits = bandwidth.limits();
+        let alt_limits = BandwidthLimits {
+            upload: properties.alt_upload_limit,
+            download: properties.alt_download_limit,
+        };
         let properties = Arc::new(properties);
         Self {
             properties,
             torrents: vec![],
             id: 0,
+            connection_slots,
+            ban_list: Default::default(),
+            ip_filter,
+            external_ip: Default::default(),
+            bandwidth,
+            limits,
+            alt_limits,
+            alt_speed: false,
         }
     }
 
@@ -190,19 +430,110 @@ impl App {
         sender: Sender<Command>,
         receiver: Receiver<Command>,
     ) -> Result<(), RsbtError> {
-        let addr = SocketAddr::new(self.properties.listen, self.properties.port);
+        if let Err(err) = self.ip_filter_reload().await {
+            warn!("ip filter is not loaded, all peers are allowed: {}", err);
+        }
 
-        let commands = command_loop(self, receiver);
+        let listener = self.bind_listener().await?;
+
+        let refuse_incoming = self
+            .properties
+            .proxy
+            .as_ref()
+            .map(|x| x.refuse_incoming)
+            .unwrap_or_default();
+
+        let accept_incoming_connections = accept_connections_loop(
+            listener,
+            sender.clone(),
+            self.ip_filter.clone(),
+            refuse_incoming,
+            self.properties.encryption,
+        );
+
+        let _alt_speed_scheduler = if self.properties.alt_speed_schedule.is_empty() {
+            None
+        } else {
+            let mut scheduler = EventLoop::spawn(
+                AltSpeedScheduler::new(self.properties.alt_speed_schedule.clone()),
+                sender.clone(),
+            )?;
+            scheduler.start().await?;
+            Some(scheduler)
+        };
 
-        let accept_incoming_connections = accept_connections_loop(addr, sender.clone());
+        let mut port_mapping = if self.properties.port_mapping && !refuse_incoming {
+            let mut port_mapping = EventLoop::spawn(
+                PortMappingRunner::new(
+                    self.properties.port,
+                    self.properties.port_mapping_gateway,
+                    self.external_ip.clone(),
+                ),
+                sender.clone(),
+            )?;
+            port_mapping.start().await?;
+            Some(port_mapping)
+        } else {
+            None
+        };
 
-        join(accept_incoming_connections, commands).await.0?;
+        let commands = command_loop(self, sender, receiver);
 
-        Ok(())
+        pin_mut!(accept_incoming_connections, commands);
+
+        let (result, shutdown) = match select(accept_incoming_connections, commands).await {
+            Either::Left((result, _)) => (result, None),
+            Either::Right((shutdown, _)) => (Ok(()), shutdown),
+        };
+
+        if let Some(port_mapping) = port_mapping.as_mut() {
+            if let Err(err) = port_mapping.quit().await {
+                error!("cannot remove port mapping: {}", err);
+            }
+        }
+
+        if let Some(shutdown) = shutdown {
+            if let Err(err) = shutdown.response(Ok(())) {
+                error!("cannot send response for shutdown: {}", err);
+            }
+        }
+
+        result
+    }
+
+    /// Binds first free port between `port` and `port_max`.
+    ///
+    /// Properties are replaced with bound port, so torrents announce the port in use.
+    async fn bind_listener(&mut self) -> Result<TcpListener, RsbtError> {
+        let listen = self.properties.listen;
+        let port = self.properties.port;
+        let port_max = self.properties.port_max.max(port);
+
+        for candidate in port..=port_max {
+            match TcpListener::bind(SocketAddr::new(listen, candidate)).await {
+                Ok(listener) => {
+                    let bound = listener.local_addr()?.port();
+                    if bound != port {
+                        let mut properties = self.properties.as_ref().clone();
+                        properties.port = bound;
+                        self.properties = Arc::new(properties);
+                    }
+                    debug!("listening on: {}", SocketAddr::new(listen, bound));
+                    return Ok(listener);
+                }
+                Err(err) => warn!("cannot listen on port {}: {}", candidate, err),
+            }
+        }
+
+        Err(RsbtError::NoFreePort { port, port_max })
     }
 
+    /// Creates data directories, memory storage does not touch disk.
     pub async fn init_storage(&self) -> Result<CurrentTorrents, RsbtError> {
         let properties = &self.properties;
+        if properties.storage_backend == StorageBackend::Memory {
+            return Ok(Default::default());
+        }
         if !properties.save_to.exists() {
             fs::create_dir_all(&properties.save_to).await?;
         }
@@ -220,7 +551,12 @@ impl App {
         Ok(Default::default())
     }
 
-    pub async fn download<P: AsRef<Path>>(&mut self, torrent_file: P) -> Result<(), RsbtError> {
+    /// Downloads torrent to `save_to` directory, or to download path if missing.
+    pub async fn download<P: AsRef<Path>>(
+        &mut self,
+        torrent_file: P,
+        save_to: Option<PathBuf>,
+    ) -> Result<(), RsbtError> {
         let (mut download_events_sender, download_events_receiver) =
             mpsc::channel(DEFAULT_CHANNEL_BUFFER);
 
@@ -238,18 +574,42 @@ impl App {
                         .unwrap_or_default()
                         .into(),
                     state: TorrentProcessStatus::Enabled,
+                    limits: Default::default(),
+                    file_priorities: vec![],
+                    sequential: false,
+                    first_last_pieces: false,
+                    save_to,
+                    folder: None,
+                    file_paths: vec![],
                 },
             )))
             .await?;
 
+        let mut shutdown_sender = download_events_sender.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                debug!("interrupted, shutting down");
+                if let Err(err) = shutdown_sender
+                    .send(Command::Shutdown(RequestResponse::RequestOnly(())))
+                    .await
+                {
+                    error!("cannot send shutdown: {}", err);
+                }
+            }
+        });
+
         self.processing_loop(download_events_sender, download_events_receiver)
             .await
     }
 
+    /// Torrents of memory storage are not persisted, their data is lost on exit anyway.
     pub(crate) async fn add_to_current_torrents(
         &mut self,
         torrent_header: TorrentProcessHeader,
     ) -> Result<(), RsbtError> {
+        if self.properties.storage_backend == StorageBackend::Memory {
+            return Ok(());
+        }
         let torrents_toml = self.properties.config_dir.join(TORRENTS_TOML);
         let mut current_torrents: CurrentTorrents = if torrents_toml.exists() {
             toml::from_str(&fs::read_to_string(&torrents_toml).await?)?
@@ -276,6 +636,9 @@ impl App {
         &mut self,
         torrent_header: TorrentProcessHeader,
     ) -> Result<(), RsbtError> {
+        if self.properties.storage_backend == StorageBackend::Memory {
+            return Ok(());
+        }
         let torrents_toml = self.properties.config_dir.join(TORRENTS_TOML);
         let mut current_torrents: CurrentTorrents = if torrents_toml.exists() {
             toml::from_str(&fs::read_to_string(&torrents_toml).await?)?
@@ -303,6 +666,13 @@ impl App {
             data,
             filename,
             state,
+            limits,
+            file_priorities,
+            sequential,
+            first_last_pieces,
+            save_to,
+            folder,
+            file_paths,
         } = request;
         debug!("we need to download {:?}", filename);
         let filepath = PathBuf::from(&filename);
@@ -316,11 +686,12 @@ impl App {
         debug!("piece length: {}", info.piece_length);
         debug!("total pieces: {}", info.pieces.len());
 
+        let peer_id = self.properties.peer_id;
         let mut handshake =
-            Vec::with_capacity(HANDSHAKE_PREFIX.len() + hash_id.len() + PEER_ID.len());
+            Vec::with_capacity(HANDSHAKE_PREFIX.len() + hash_id.len() + peer_id.len());
         handshake.extend_from_slice(&HANDSHAKE_PREFIX);
         handshake.extend_from_slice(&hash_id);
-        handshake.extend_from_slice(&PEER_ID);
+        handshake.extend_from_slice(&peer_id);
 
         let (broker_sender, broker_receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
 
@@ -329,27 +700,50 @@ impl App {
         let torrent_token = Arc::new(TorrentToken {
             info,
             hash_id,
+            peer_id,
             torrent,
             handshake,
             broker_sender,
+            bandwidth: TorrentBandwidth {
+                torrent: BandwidthLimiter::new(*limits),
+                global: self.bandwidth.clone(),
+            },
+            external_ip: self.external_ip.clone(),
         });
 
+        let torrent_header = TorrentProcessHeader {
+            file: filename.clone(),
+            state: state.clone(),
+            upload_limit: limits.upload,
+            download_limit: limits.download,
+            file_priorities: file_priorities.clone(),
+            sequential: false,
+            first_last_pieces: false,
+            save_to: save_to.clone(),
+            folder: folder.clone(),
+            file_paths: file_paths.clone(),
+        };
+
         let torrent_storage = TorrentStorage::new(
             self.properties.clone(),
             filename.clone(),
             torrent_token.clone(),
+            file_priorities.clone(),
+            save_to
+                .clone()
+                .unwrap_or_else(|| self.properties.save_to.clone()),
+            torrent_header.files(&torrent_token.info),
         )
         .await?;
 
-        let torrent_header = TorrentProcessHeader {
-            file: filename.clone(),
-            state: state.clone(),
-        };
         let storage_state_watch = torrent_storage.receiver.clone();
         tokio::spawn(torrent_event_loop(
             self.properties.clone(),
             torrent_storage,
             torrent_token.clone(),
+            self.connection_slots.clone(),
+            self.ban_list.clone(),
+            self.ip_filter.clone(),
             broker_receiver,
         ));
 
@@ -363,21 +757,33 @@ impl App {
 
         let statistics_watch = statistics_receiver.await?;
 
-        let torrent_process = TorrentProcess {
+        let queued = state == &TorrentProcessStatus::Enabled && !self.can_activate();
+
+        let mut torrent_process = TorrentProcess {
             id: self.id,
             name,
-            header: torrent_header.clone(),
+            header: torrent_header,
             process: torrent_token.clone(),
             properties: self.properties.clone(),
             storage_state_watch,
             statistics_watch,
+            queued,
+            super_seed: false,
         };
 
-        self.add_to_current_torrents(torrent_header).await?;
+        if *sequential {
+            torrent_process.set_sequential(true).await?;
+        }
+        if *first_last_pieces {
+            torrent_process.set_first_last_pieces(true).await?;
+        }
+
+        self.add_to_current_torrents(torrent_process.header.clone())
+            .await?;
 
         self.torrents.push(torrent_process.clone());
 
-        if state == &TorrentProcessStatus::Enabled {
+        if state == &TorrentProcessStatus::Enabled && !queued {
             debug!("sending activation event");
             let (enable_request, response) = RequestResponse::new(());
             torrent_token
@@ -395,16 +801,84 @@ impl App {
     async fn torrent_action(&mut self, request: &CommandTorrentAction) -> Result<(), RsbtError> {
         let id = request.id;
 
+        let can_activate = self.can_activate();
+
         let torrent_header = if let Some(torrent) = self.torrents.iter_mut().find(|x| x.id == id) {
             match request.action {
-                TorrentAction::Enable => torrent.enable().await,
+                TorrentAction::Enable if torrent.queued => Ok(()),
+                TorrentAction::Enable
+                    if torrent.header.state == TorrentProcessStatus::Enabled || can_activate =>
+                {
+                    torrent.enable().await
+                }
+                TorrentAction::Enable => torrent.enable_queued().await,
                 TorrentAction::Disable => torrent.disable().await,
+                TorrentAction::SuperSeed => torrent.set_super_seed(true).await,
+                TorrentAction::NormalSeed => torrent.set_super_seed(false).await,
+                TorrentAction::Sequential => torrent.set_sequential(true).await,
+                TorrentAction::NonSequential => torrent.set_sequential(false).await,
+                TorrentAction::FirstLastPieces => torrent.set_first_last_pieces(true).await,
+                TorrentAction::NoFirstLastPieces => torrent.set_first_last_pieces(false).await,
+                TorrentAction::Recheck => torrent.recheck().await,
             }?;
             Ok(torrent.header.clone())
         } else {
             Err(RsbtError::TorrentNotFound(id))
         }?;
-        self.add_to_current_torrents(torrent_header).await
+        self.add_to_current_torrents(torrent_header).await?;
+        self.apply_queue().await
+    }
+
+    fn max_active_torrents(&self) -> Option<usize> {
+        if self.alt_speed {
+            self.properties.alt_max_active_torrents
+        } else {
+            self.properties.max_active_torrents
+        }
+    }
+
+    fn active_torrents(&self) -> usize {
+        self.torrents
+            .iter()
+            .filter(|x| x.header.state == TorrentProcessStatus::Enabled && !x.queued)
+            .count()
+    }
+
+    fn can_activate(&self) -> bool {
+        self.max_active_torrents()
+            .map(|max| self.active_torrents() < max)
+            .unwrap_or(true)
+    }
+
+    /// Queues running torrents over the limit, then starts queued ones while there are free slots.
+    async fn apply_queue(&mut self) -> Result<(), RsbtError> {
+        let mut slots = self.max_active_torrents().unwrap_or(usize::MAX);
+
+        for torrent in self
+            .torrents
+            .iter_mut()
+            .filter(|x| x.header.state == TorrentProcessStatus::Enabled && !x.queued)
+        {
+            if slots > 0 {
+                slots -= 1;
+            } else {
+                torrent.queue().await?;
+            }
+        }
+
+        for torrent in self
+            .torrents
+            .iter_mut()
+            .filter(|x| x.header.state == TorrentProcessStatus::Enabled && x.queued)
+        {
+            if slots == 0 {
+                break;
+            }
+            slots -= 1;
+            torrent.unqueue().await?;
+        }
+
+        Ok(())
     }
 
     async fn delete_torrent(&mut self, request: &CommandDeleteTorrent) -> Result<(), RsbtError> {
@@ -423,7 +897,7 @@ impl App {
 
             self.torrents.remove(torrent_index);
 
-            Ok(())
+            self.apply_queue().await
         } else {
             Err(RsbtError::TorrentNotFound(id))
         }
@@ -437,6 +911,40 @@ impl App {
         torrent.peers().await
     }
 
+    async fn torrent_add_peer(&mut self, request: &CommandTorrentAddPeer) -> Result<(), RsbtError> {
+        let torrent = find_process_by_id(&self.torrents, request.id)?;
+        torrent.add_peer(request.addr).await
+    }
+
+    /// Global ban disconnects peer from all torrents.
+    async fn torrent_ban_peer(&mut self, request: &CommandTorrentBanPeer) -> Result<(), RsbtError> {
+        let CommandTorrentBanPeer { id, ip, global } = *request;
+        let torrent = find_process_by_id(&self.torrents, id)?;
+        if global {
+            self.ban_list.ban(ip);
+            for torrent in &self.torrents {
+                torrent.ban_peer(ip, true).await?;
+            }
+            Ok(())
+        } else {
+            torrent.ban_peer(ip, false).await
+        }
+    }
+
+    async fn torrent_unban_peer(
+        &mut self,
+        request: &CommandTorrentUnbanPeer,
+    ) -> Result<(), RsbtError> {
+        let CommandTorrentUnbanPeer { id, ip, global } = *request;
+        let torrent = find_process_by_id(&self.torrents, id)?;
+        if global {
+            self.ban_list.unban(&ip);
+            Ok(())
+        } else {
+            torrent.unban_peer(ip).await
+        }
+    }
+
     async fn torrent_announces(
         &mut self,
         request: &CommandTorrentAnnounce,
@@ -501,4 +1009,282 @@ impl App {
         let torrent = find_process_by_id(&self.torrents, *id)?;
         torrent.download_file(*file_id, range.clone()).await
     }
+
+    async fn torrent_bandwidth_limits(
+        &mut self,
+        request: &CommandTorrentBandwidthLimits,
+    ) -> Result<(), RsbtError> {
+        let CommandTorrentBandwidthLimits { id, limits } = request;
+        let torrent_header = if let Some(torrent) = self.torrents.iter_mut().find(|x| x.id == *id) {
+            torrent.process.bandwidth.torrent.set_limits(*limits);
+            let limits = torrent.process.bandwidth.torrent.limits();
+            torrent.header.upload_limit = limits.upload;
+            torrent.header.download_limit = limits.download;
+            Ok(torrent.header.clone())
+        } else {
+            Err(RsbtError::TorrentNotFound(*id))
+        }?;
+        self.add_to_current_torrents(torrent_header).await
+    }
+
+    async fn torrent_file_priority(
+        &mut self,
+        request: &CommandTorrentFilePriority,
+    ) -> Result<(), RsbtError> {
+        let CommandTorrentFilePriority {
+            id,
+            file_id,
+            priority,
+        } = request;
+        let torrent_header = if let Some(torrent) = self.torrents.iter_mut().find(|x| x.id == *id) {
+            torrent.set_file_priority(*file_id, *priority).await?;
+            Ok(torrent.header.clone())
+        } else {
+            Err(RsbtError::TorrentNotFound(*id))
+        }?;
+        self.add_to_current_torrents(torrent_header).await
+    }
+
+    /// Starts moving torrent data, new directory is saved when files are moved.
+    async fn torrent_move(
+        &mut self,
+        request: &CommandTorrentMove,
+        sender: Sender<Command>,
+    ) -> Result<(), RsbtError> {
+        let CommandTorrentMove { id, save_to } = request;
+        let torrent = self
+            .torrents
+            .iter_mut()
+            .find(|x| x.id == *id)
+            .ok_or(RsbtError::TorrentNotFound(*id))?;
+        torrent.move_to(save_to.clone()).await?;
+        tokio::spawn(watch_move(
+            torrent.storage_state_watch.clone(),
+            sender,
+            Command::TorrentMoved {
+                id: *id,
+                save_to: save_to.clone(),
+            },
+        ));
+        Ok(())
+    }
+
+    async fn torrent_moved(&mut self, id: usize, save_to: PathBuf) -> Result<(), RsbtError> {
+        let torrent_header = if let Some(torrent) = self.torrents.iter_mut().find(|x| x.id == id) {
+            torrent.header.save_to = Some(save_to);
+            Ok(torrent.header.clone())
+        } else {
+            Err(RsbtError::TorrentNotFound(id))
+        }?;
+        self.add_to_current_torrents(torrent_header).await
+    }
+
+    /// Starts renaming torrent files, new names are saved when files are renamed.
+    async fn torrent_rename(
+        &mut self,
+        request: &CommandTorrentRename,
+        sender: Sender<Command>,
+    ) -> Result<(), RsbtError> {
+        let CommandTorrentRename { id, file_id, name } = request;
+        let torrent = self
+            .torrents
+            .iter()
+            .find(|x| x.id == *id)
+            .ok_or(RsbtError::TorrentNotFound(*id))?;
+        let header = torrent.rename(*file_id, name.clone()).await?;
+        tokio::spawn(watch_move(
+            torrent.storage_state_watch.clone(),
+            sender,
+            Command::TorrentRenamed {
+                id: *id,
+                folder: header.folder,
+                file_paths: header.file_paths,
+            },
+        ));
+        Ok(())
+    }
+
+    async fn torrent_renamed(
+        &mut self,
+        id: usize,
+        folder: Option<String>,
+        file_paths: Vec<PathBuf>,
+    ) -> Result<(), RsbtError> {
+        let torrent_header = if let Some(torrent) = self.torrents.iter_mut().find(|x| x.id == id) {
+            torrent.header.folder = folder;
+            torrent.header.file_paths = file_paths;
+            Ok(torrent.header.clone())
+        } else {
+            Err(RsbtError::TorrentNotFound(id))
+        }?;
+        self.add_to_current_torrents(torrent_header).await
+    }
+
+    /// Saves storage state of all torrents before exit.
+    async fn save_state(&self) {
+        for torrent in &self.torrents {
+            if let Err(err) = torrent.save_state().await {
+                error!("cannot save state of {}: {}", torrent.id, err);
+            }
+        }
+    }
+
+    fn bandwidth(&self) -> BandwidthView {
+        self.bandwidth.view()
+    }
+
+    /// Changes limits of current mode, normal or alternative.
+    fn bandwidth_limits(&mut self, request: &CommandBandwidthLimits) -> Result<(), RsbtError> {
+        if self.alt_speed {
+            self.alt_limits = request.limits;
+        } else {
+            self.limits = request.limits;
+        }
+        self.bandwidth.set_limits(request.limits);
+        Ok(())
+    }
+
+    fn status(&self) -> StatusView {
+        StatusView {
+            listen: self.properties.listen,
+            port: self.properties.port,
+            peer_id: String::from_utf8_lossy(&self.properties.peer_id).into_owned(),
+            torrents: self.torrents.len(),
+            external_ip: self.external_ip.get(),
+        }
+    }
+
+    /// Reloads filter lists from disk, blocking reads are moved off the command loop.
+    ///
+    /// Connected peers which are blocked now are disconnected.
+    async fn ip_filter_reload(&self) -> Result<IpFilterView, RsbtError> {
+        let ip_filter = self.ip_filter.clone();
+        tokio::task::spawn_blocking(move || ip_filter.reload()).await??;
+        for torrent in &self.torrents {
+            if let Err(err) = torrent.disconnect_blocked().await {
+                error!("cannot disconnect blocked peers of {}: {}", torrent.id, err);
+            }
+        }
+        Ok(self.ip_filter.view())
+    }
+
+    fn alt_speed_view(&self) -> AltSpeedView {
+        AltSpeedView {
+            enabled: self.alt_speed,
+            upload_limit: self.alt_limits.upload,
+            download_limit: self.alt_limits.download,
+            max_active_torrents: self.properties.alt_max_active_torrents,
+            schedule: self.properties.alt_speed_schedule.clone(),
+        }
+    }
+
+    async fn alt_speed_mode(&mut self, request: &CommandAltSpeedMode) -> Result<(), RsbtError> {
+        if self.alt_speed == request.enabled {
+            return Ok(());
+        }
+        debug!("alternative speed limits enabled: {}", request.enabled);
+        self.alt_speed = request.enabled;
+        self.bandwidth.set_limits(if self.alt_speed {
+            self.alt_limits
+        } else {
+            self.limits
+        });
+        self.apply_queue().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Settings;
+
+    #[tokio::test]
+    async fn memory_storage_is_not_persisted() {
+        let config_dir = std::env::temp_dir().join(format!("rsbt-app-{}", std::process::id()));
+        let mut properties = Properties::from((Settings::default(), config_dir.clone()));
+        properties.storage_backend = StorageBackend::Memory;
+        let mut app = App::new(properties);
+
+        app.init_storage().await.unwrap();
+        let torrent_header = TorrentProcessHeader {
+            file: "ferris.gif.torrent".into(),
+            state: TorrentProcessStatus::Enabled,
+            upload_limit: None,
+            download_limit: None,
+            file_priorities: vec![],
+            sequential: false,
+            first_last_pieces: false,
+            save_to: None,
+            folder: None,
+            file_paths: vec![],
+        };
+        app.add_to_current_torrents(torrent_header.clone())
+            .await
+            .unwrap();
+        app.remove_from_current_torrents(torrent_header)
+            .await
+            .unwrap();
+
+        assert!(!config_dir.exists());
+    }
+
+    #[tokio::test]
+    async fn renamed_files_are_restored() {
+        let config_dir =
+            std::env::temp_dir().join(format!("rsbt-app-restore-{}", std::process::id()));
+        let mut properties = Properties::from((Settings::default(), config_dir.clone()));
+        properties.storage_backend = StorageBackend::File;
+        let mut app = App::new(properties);
+
+        app.init_storage().await.unwrap();
+        let torrent_header = TorrentProcessHeader {
+            file: "ferris.gif.torrent".into(),
+            state: TorrentProcessStatus::Enabled,
+            upload_limit: None,
+            download_limit: None,
+            file_priorities: vec![],
+            sequential: false,
+            first_last_pieces: false,
+            save_to: Some(config_dir.join("moved")),
+            folder: Some("ferris".into()),
+            file_paths: vec![PathBuf::from("renamed.gif")],
+        };
+        app.add_to_current_torrents(torrent_header.clone())
+            .await
+            .unwrap();
+
+        let current_torrents = app.init_storage().await.unwrap();
+        let restored = &current_torrents.torrents[0];
+        assert_eq!(restored.save_to, torrent_header.save_to);
+        assert_eq!(restored.folder, torrent_header.folder);
+        assert_eq!(restored.file_paths, torrent_header.file_paths);
+
+        let (broker_sender, _) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        let info = &TorrentToken::sample(broker_sender).info;
+        assert_eq!(
+            restored.files(info)[0].path,
+            PathBuf::from("ferris").join("renamed.gif")
+        );
+
+        std::fs::remove_dir_all(&config_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn busy_port_falls_back_to_free_one() {
+        let busy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = busy.local_addr().unwrap().port();
+        let config_dir = std::env::temp_dir().join(format!("rsbt-app-port-{}", std::process::id()));
+        let mut properties = Properties::from((Settings::default(), config_dir));
+        properties.listen = busy.local_addr().unwrap().ip();
+        properties.port = port;
+        properties.port_max = port.saturating_add(16);
+        let mut app = App::new(properties);
+
+        // torrents are created with replaced properties, so they announce bound port
+        let listener = app.bind_listener().await.unwrap();
+        let bound = listener.local_addr().unwrap().port();
+        assert_ne!(bound, port);
+        assert!(bound <= port.saturating_add(16));
+        assert_eq!(app.properties.port, bound);
+    }
 }
@@ -0,0 +1,49 @@
+use crate::bandwidth::{RateMeter, TokenBucket};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Single transfer direction: limit applies to payload, overhead is only measured.
+#[derive(Debug)]
+pub(crate) struct BandwidthChannel {
+    bucket: Mutex<TokenBucket>,
+    payload: RateMeter,
+    overhead: RateMeter,
+}
+
+impl BandwidthChannel {
+    pub(crate) fn new(limit: Option<u64>) -> Self {
+        Self {
+            bucket: Mutex::new(TokenBucket::new(limit)),
+            payload: RateMeter::new(),
+            overhead: RateMeter::new(),
+        }
+    }
+
+    pub(crate) fn limit(&self) -> Option<u64> {
+        self.bucket.lock().unwrap().rate()
+    }
+
+    pub(crate) fn set_limit(&self, limit: Option<u64>) {
+        self.bucket.lock().unwrap().set_rate(limit);
+    }
+
+    /// Accounts transferred bytes, returns delay to stay within limit.
+    pub(crate) fn transfer(&self, payload: usize, overhead: usize) -> Option<Duration> {
+        self.payload.add(payload);
+        self.overhead.add(overhead);
+        if payload == 0 {
+            return None;
+        }
+        self.bucket.lock().unwrap().take(payload, Instant::now())
+    }
+
+    pub(crate) fn payload_rate(&self) -> u64 {
+        self.payload.rate()
+    }
+
+    pub(crate) fn overhead_rate(&self) -> u64 {
+        self.overhead.rate()
+    }
+}
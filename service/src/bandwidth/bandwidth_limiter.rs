@@ -0,0 +1,53 @@
+use crate::{
+    bandwidth::BandwidthChannel,
+    types::{
+        public::{BandwidthLimits, BandwidthView},
+        Properties,
+    },
+};
+
+/// Upload and download limits with measured rates.
+#[derive(Debug)]
+pub(crate) struct BandwidthLimiter {
+    pub(crate) upload: BandwidthChannel,
+    pub(crate) download: BandwidthChannel,
+}
+
+impl BandwidthLimiter {
+    pub(crate) fn new(limits: BandwidthLimits) -> Self {
+        Self {
+            upload: BandwidthChannel::new(limits.upload),
+            download: BandwidthChannel::new(limits.download),
+        }
+    }
+
+    pub(crate) fn from_properties(properties: &Properties) -> Self {
+        Self::new(BandwidthLimits {
+            upload: properties.upload_limit,
+            download: properties.download_limit,
+        })
+    }
+
+    pub(crate) fn limits(&self) -> BandwidthLimits {
+        BandwidthLimits {
+            upload: self.upload.limit(),
+            download: self.download.limit(),
+        }
+    }
+
+    pub(crate) fn set_limits(&self, limits: BandwidthLimits) {
+        self.upload.set_limit(limits.upload);
+        self.download.set_limit(limits.download);
+    }
+
+    pub(crate) fn view(&self) -> BandwidthView {
+        BandwidthView {
+            upload_limit: self.upload.limit(),
+            download_limit: self.download.limit(),
+            upload_rate: self.upload.payload_rate(),
+            download_rate: self.download.payload_rate(),
+            upload_overhead_rate: self.upload.overhead_rate(),
+            download_overhead_rate: self.download.overhead_rate(),
+        }
+    }
+}
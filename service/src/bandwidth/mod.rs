@@ -0,0 +1,11 @@
+mod bandwidth_channel;
+mod bandwidth_limiter;
+mod rate_meter;
+mod token_bucket;
+mod torrent_bandwidth;
+
+pub(crate) use bandwidth_channel::BandwidthChannel;
+pub(crate) use bandwidth_limiter::BandwidthLimiter;
+pub(crate) use rate_meter::RateMeter;
+pub(crate) use token_bucket::TokenBucket;
+pub(crate) use torrent_bandwidth::TorrentBandwidth;
@@ -0,0 +1,54 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const RATE_METER_WINDOW: Duration = Duration::from_secs(1);
+
+/// Measures transfer rate in bytes per second.
+#[derive(Debug)]
+pub(crate) struct RateMeter {
+    state: Mutex<RateMeterState>,
+}
+
+#[derive(Debug)]
+struct RateMeterState {
+    since: Instant,
+    bytes: u64,
+    rate: u64,
+}
+
+impl RateMeterState {
+    fn roll(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.since);
+        if elapsed >= RATE_METER_WINDOW {
+            self.rate = self.bytes * 1000 / elapsed.as_millis() as u64;
+            self.bytes = 0;
+            self.since = now;
+        }
+    }
+}
+
+impl RateMeter {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(RateMeterState {
+                since: Instant::now(),
+                bytes: 0,
+                rate: 0,
+            }),
+        }
+    }
+
+    pub(crate) fn add(&self, amount: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.roll(Instant::now());
+        state.bytes += amount as u64;
+    }
+
+    pub(crate) fn rate(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.roll(Instant::now());
+        state.rate
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+/// Token bucket with one second burst.
+///
+/// Tokens are allowed to go negative, so transfer bigger than bucket capacity
+/// is delayed proportionally instead of being stuck forever.
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    rate: Option<u64>,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(rate: Option<u64>) -> Self {
+        let rate = rate.filter(|x| *x > 0);
+        Self {
+            rate,
+            tokens: rate.unwrap_or_default() as f64,
+            updated: Instant::now(),
+        }
+    }
+
+    pub(crate) fn rate(&self) -> Option<u64> {
+        self.rate
+    }
+
+    /// Changes rate, zero or none means unlimited.
+    pub(crate) fn set_rate(&mut self, rate: Option<u64>) {
+        self.rate = rate.filter(|x| *x > 0);
+        self.tokens = self.tokens.min(self.rate.unwrap_or_default() as f64);
+    }
+
+    /// Takes tokens and returns delay required to pay off the debt.
+    pub(crate) fn take(&mut self, amount: usize, now: Instant) -> Option<Duration> {
+        let elapsed = now.saturating_duration_since(self.updated);
+        self.updated = now;
+
+        let rate = self.rate? as f64;
+
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * rate).min(rate);
+        self.tokens -= amount as f64;
+
+        if self.tokens < 0.0 {
+            Some(Duration::from_secs_f64(-self.tokens / rate))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Duration, Instant, TokenBucket};
+
+    #[test]
+    fn token_bucket_unlimited() {
+        let mut bucket = TokenBucket::new(None);
+        assert_eq!(bucket.take(1 << 20, Instant::now()), None);
+
+        bucket.set_rate(Some(0));
+        assert_eq!(bucket.rate(), None);
+        assert_eq!(bucket.take(1 << 20, Instant::now()), None);
+    }
+
+    #[test]
+    fn token_bucket_delay() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(Some(1000));
+        bucket.updated = now;
+
+        assert_eq!(bucket.take(1000, now), None);
+        assert_eq!(bucket.take(500, now), Some(Duration::from_millis(500)));
+
+        let later = now + Duration::from_millis(1500);
+        assert_eq!(bucket.take(500, later), None);
+        assert_eq!(bucket.take(1000, later), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn token_bucket_burst() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(Some(1000));
+        bucket.updated = now;
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(bucket.take(1000, later), None);
+        assert_eq!(bucket.take(1000, later), Some(Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{bandwidth::BandwidthLimiter, types::Message};
+use std::sync::Arc;
+use tokio::time::delay_for;
+
+/// Torrent limits combined with global ones.
+#[derive(Debug)]
+pub(crate) struct TorrentBandwidth {
+    pub(crate) torrent: BandwidthLimiter,
+    pub(crate) global: Arc<BandwidthLimiter>,
+}
+
+impl TorrentBandwidth {
+    /// Waits until message sent to peer fits into upload limits.
+    pub(crate) async fn upload(&self, message: &Message) {
+        let (payload, overhead) = message_sizes(message);
+        let delay = self
+            .torrent
+            .upload
+            .transfer(payload, overhead)
+            .max(self.global.upload.transfer(payload, overhead));
+        if let Some(delay) = delay {
+            delay_for(delay).await;
+        }
+    }
+
+    /// Waits until message received from peer fits into download limits.
+    pub(crate) async fn download(&self, message: &Message) {
+        let (payload, overhead) = message_sizes(message);
+        let delay = self
+            .torrent
+            .download
+            .transfer(payload, overhead)
+            .max(self.global.download.transfer(payload, overhead));
+        if let Some(delay) = delay {
+            delay_for(delay).await;
+        }
+    }
+}
+
+fn message_sizes(message: &Message) -> (usize, usize) {
+    let payload = message.payload_size();
+    (payload, message.wire_size() - payload)
+}
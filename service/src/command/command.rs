@@ -1,19 +1,24 @@
 use crate::{
     command::{
-        CommandAddTorrent, CommandDeleteTorrent, CommandTorrentAction, CommandTorrentAnnounce,
-        CommandTorrentDetail, CommandTorrentFileDownload, CommandTorrentFiles, CommandTorrentPeers,
-        CommandTorrentPieces,
+        CommandAddTorrent, CommandAltSpeedMode, CommandBandwidthLimits, CommandDeleteTorrent,
+        CommandTorrentAction, CommandTorrentAddPeer, CommandTorrentAnnounce, CommandTorrentBanPeer,
+        CommandTorrentBandwidthLimits, CommandTorrentDetail, CommandTorrentFileDownload,
+        CommandTorrentFilePriority, CommandTorrentFiles, CommandTorrentMove, CommandTorrentPeers,
+        CommandTorrentPieces, CommandTorrentRename, CommandTorrentUnbanPeer,
     },
     file_download::FileDownloadStream,
     process::{TorrentProcess, TorrentToken},
     request_response::RequestResponse,
     types::{
-        public::{AnnounceView, FileView, PeerView, TorrentDownloadView},
+        public::{
+            AltSpeedView, AnnounceView, BandwidthView, FileView, IpFilterView, PeerView,
+            StatusView, TorrentDownloadView,
+        },
         Handshake,
     },
-    RsbtError,
+    RsbtError, SHA1_SIZE,
 };
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 use tokio::sync::oneshot;
 
 #[derive(Debug)]
@@ -24,9 +29,17 @@ pub enum Command {
         handshake_request: Handshake,
         handshake_sender: oneshot::Sender<Option<Arc<TorrentToken>>>,
     },
+    /// Finds info hash of encrypted connection by `SHA1("req2", info_hash)`.
+    TorrentObfuscatedHash {
+        obfuscated_hash: [u8; SHA1_SIZE],
+        hash_sender: oneshot::Sender<Option<[u8; SHA1_SIZE]>>,
+    },
     TorrentList(RequestResponse<(), Result<Vec<TorrentDownloadView>, RsbtError>>),
     TorrentAction(RequestResponse<CommandTorrentAction, Result<(), RsbtError>>),
     TorrentPeers(RequestResponse<CommandTorrentPeers, Result<Vec<PeerView>, RsbtError>>),
+    TorrentAddPeer(RequestResponse<CommandTorrentAddPeer, Result<(), RsbtError>>),
+    TorrentBanPeer(RequestResponse<CommandTorrentBanPeer, Result<(), RsbtError>>),
+    TorrentUnbanPeer(RequestResponse<CommandTorrentUnbanPeer, Result<(), RsbtError>>),
     TorrentDetail(RequestResponse<CommandTorrentDetail, Result<TorrentDownloadView, RsbtError>>),
     TorrentAnnounces(RequestResponse<CommandTorrentAnnounce, Result<Vec<AnnounceView>, RsbtError>>),
     TorrentFiles(RequestResponse<CommandTorrentFiles, Result<Vec<FileView>, RsbtError>>),
@@ -37,4 +50,30 @@ pub enum Command {
     TorrentFileDownload(
         RequestResponse<CommandTorrentFileDownload, Result<FileDownloadStream, RsbtError>>,
     ),
+    TorrentBandwidthLimits(RequestResponse<CommandTorrentBandwidthLimits, Result<(), RsbtError>>),
+    TorrentFilePriority(RequestResponse<CommandTorrentFilePriority, Result<(), RsbtError>>),
+    /// Moves torrent data, response is sent once files are being moved.
+    TorrentMove(RequestResponse<CommandTorrentMove, Result<(), RsbtError>>),
+    /// Torrent data is moved to `save_to`, which is saved in torrents.toml.
+    TorrentMoved {
+        id: usize,
+        save_to: PathBuf,
+    },
+    /// Renames file or folder of torrent, response is sent once files are being renamed.
+    TorrentRename(RequestResponse<CommandTorrentRename, Result<(), RsbtError>>),
+    /// Torrent files are renamed, `folder` and `file_paths` are saved in torrents.toml.
+    TorrentRenamed {
+        id: usize,
+        folder: Option<String>,
+        file_paths: Vec<PathBuf>,
+    },
+    Bandwidth(RequestResponse<(), Result<BandwidthView, RsbtError>>),
+    BandwidthLimits(RequestResponse<CommandBandwidthLimits, Result<(), RsbtError>>),
+    AltSpeed(RequestResponse<(), Result<AltSpeedView, RsbtError>>),
+    AltSpeedMode(RequestResponse<CommandAltSpeedMode, Result<(), RsbtError>>),
+    IpFilter(RequestResponse<(), Result<IpFilterView, RsbtError>>),
+    IpFilterReload(RequestResponse<(), Result<IpFilterView, RsbtError>>),
+    Status(RequestResponse<(), Result<StatusView, RsbtError>>),
+    /// Stops processing loop, response is sent after port mapping is removed.
+    Shutdown(RequestResponse<(), Result<(), RsbtError>>),
 }
@@ -1,8 +1,26 @@
-use crate::process::TorrentProcessStatus;
+use std::path::PathBuf;
+
+use crate::{
+    process::TorrentProcessStatus,
+    types::public::{BandwidthLimits, FilePriority},
+};
 
 #[derive(Debug)]
 pub struct CommandAddTorrent {
     pub data: Vec<u8>,
     pub filename: String,
     pub state: TorrentProcessStatus,
+    pub limits: BandwidthLimits,
+    /// Priorities by file index, missing files have normal priority
+    pub file_priorities: Vec<FilePriority>,
+    /// Pieces are downloaded in order
+    pub sequential: bool,
+    /// First and last pieces of each file are downloaded first
+    pub first_last_pieces: bool,
+    /// Directory of torrent data, `save_to` setting if missing
+    pub save_to: Option<PathBuf>,
+    /// Folder of torrent files inside data directory, files are stored directly if missing
+    pub folder: Option<String>,
+    /// Renamed file paths by file index, missing files keep torrent paths
+    pub file_paths: Vec<PathBuf>,
 }
@@ -0,0 +1,4 @@
+#[derive(Debug)]
+pub struct CommandAltSpeedMode {
+    pub enabled: bool,
+}
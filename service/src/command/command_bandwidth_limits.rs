@@ -0,0 +1,6 @@
+use crate::types::public::BandwidthLimits;
+
+#[derive(Debug)]
+pub struct CommandBandwidthLimits {
+    pub limits: BandwidthLimits,
+}
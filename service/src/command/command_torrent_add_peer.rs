@@ -0,0 +1,7 @@
+use std::net::SocketAddr;
+
+#[derive(Debug)]
+pub struct CommandTorrentAddPeer {
+    pub id: usize,
+    pub addr: SocketAddr,
+}
@@ -0,0 +1,9 @@
+use std::net::IpAddr;
+
+#[derive(Debug)]
+pub struct CommandTorrentBanPeer {
+    pub id: usize,
+    pub ip: IpAddr,
+    /// Ban for all torrents, otherwise only for torrent with `id`
+    pub global: bool,
+}
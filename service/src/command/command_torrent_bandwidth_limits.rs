@@ -0,0 +1,7 @@
+use crate::types::public::BandwidthLimits;
+
+#[derive(Debug)]
+pub struct CommandTorrentBandwidthLimits {
+    pub id: usize,
+    pub limits: BandwidthLimits,
+}
@@ -0,0 +1,8 @@
+use crate::types::public::FilePriority;
+
+#[derive(Debug)]
+pub struct CommandTorrentFilePriority {
+    pub id: usize,
+    pub file_id: usize,
+    pub priority: FilePriority,
+}
@@ -0,0 +1,7 @@
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub struct CommandTorrentMove {
+    pub id: usize,
+    pub save_to: PathBuf,
+}
@@ -0,0 +1,7 @@
+#[derive(Debug)]
+pub struct CommandTorrentRename {
+    pub id: usize,
+    /// Renamed file, folder of all files if missing
+    pub file_id: Option<usize>,
+    pub name: String,
+}
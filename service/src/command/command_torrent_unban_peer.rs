@@ -0,0 +1,9 @@
+use std::net::IpAddr;
+
+#[derive(Debug)]
+pub struct CommandTorrentUnbanPeer {
+    pub id: usize,
+    pub ip: IpAddr,
+    /// Remove global ban, otherwise only ban for torrent with `id`
+    pub global: bool,
+}
@@ -1,21 +1,39 @@
 mod command;
 mod command_add_torrent;
+mod command_alt_speed_mode;
+mod command_bandwidth_limits;
 mod command_delete_torrent;
 mod command_torrent_action;
+mod command_torrent_add_peer;
 mod command_torrent_announce;
+mod command_torrent_ban_peer;
+mod command_torrent_bandwidth_limits;
 mod command_torrent_detail;
 mod command_torrent_file_download;
+mod command_torrent_file_priority;
 mod command_torrent_files;
+mod command_torrent_move;
 mod command_torrent_peers;
 mod command_torrent_pieces;
+mod command_torrent_rename;
+mod command_torrent_unban_peer;
 
 pub use command::Command;
 pub use command_add_torrent::CommandAddTorrent;
+pub use command_alt_speed_mode::CommandAltSpeedMode;
+pub use command_bandwidth_limits::CommandBandwidthLimits;
 pub use command_delete_torrent::CommandDeleteTorrent;
 pub use command_torrent_action::CommandTorrentAction;
+pub use command_torrent_add_peer::CommandTorrentAddPeer;
 pub use command_torrent_announce::CommandTorrentAnnounce;
+pub use command_torrent_ban_peer::CommandTorrentBanPeer;
+pub use command_torrent_bandwidth_limits::CommandTorrentBandwidthLimits;
 pub use command_torrent_detail::CommandTorrentDetail;
 pub use command_torrent_file_download::CommandTorrentFileDownload;
+pub use command_torrent_file_priority::CommandTorrentFilePriority;
 pub use command_torrent_files::CommandTorrentFiles;
+pub use command_torrent_move::CommandTorrentMove;
 pub use command_torrent_peers::CommandTorrentPeers;
 pub use command_torrent_pieces::CommandTorrentPieces;
+pub use command_torrent_rename::CommandTorrentRename;
+pub use command_torrent_unban_peer::CommandTorrentUnbanPeer;
@@ -33,7 +33,7 @@ pub enum RsbtError {
     #[fail(display = "send error {}", _0)]
     SendError(futures::channel::mpsc::SendError),
     #[fail(display = "cannot send to torrent process: {}", _0)]
-    SendToTorrentToken(tokio::sync::mpsc::error::SendError<Command>),
+    SendToTorrentToken(Box<tokio::sync::mpsc::error::SendError<Command>>),
     #[fail(display = "oneshot recv error {}", _0)]
     TokioMpscOneshotRecvError(tokio::sync::oneshot::error::RecvError),
     #[fail(display = "cannot parse uri {}", _0)]
@@ -42,6 +42,32 @@ pub enum RsbtError {
     Aborted,
     #[fail(display = "peer handshake failure")]
     PeerHandshakeFailure,
+    #[fail(display = "peer handshake timeout")]
+    PeerHandshakeTimeout,
+    #[fail(display = "peer encryption failure: {}", _0)]
+    PeerEncryptionFailure(String),
+    #[fail(
+        display = "peer bitfield length {} is invalid, expected {}",
+        length, expected
+    )]
+    PeerInvalidBitfieldLength { length: usize, expected: usize },
+    #[fail(display = "peer bitfield has spare bits set")]
+    PeerBitfieldSpareBits,
+    #[fail(display = "peer bitfield is not the first message")]
+    PeerBitfieldOutOfOrder,
+    #[fail(display = "peer have message with invalid piece {}", _0)]
+    PeerInvalidHave(u32),
+    #[fail(
+        display = "peer block {} {} [{}] is out of piece bounds or too large",
+        index, begin, length
+    )]
+    PeerInvalidRequest { index: u32, begin: u32, length: u32 },
+    #[fail(display = "peer {} is banned", _0)]
+    PeerBanned(std::net::IpAddr),
+    #[fail(display = "peer {} is blocked by ip filter", _0)]
+    PeerBlocked(std::net::IpAddr),
+    #[fail(display = "peer requested data without being interested")]
+    PeerRequestNotInterested,
     #[fail(display = "message codec {}", _0)]
     MessageCodec(MessageCodecError),
     #[fail(display = "udp tracker codec {}", _0)]
@@ -50,6 +76,8 @@ pub enum RsbtError {
     UdpTrackerTimeout,
     #[fail(display = "udp tracker implementation")]
     UdpTrackerImplementation,
+    #[fail(display = "invalid udp tracker address {}", _0)]
+    UdpTrackerAddress(String),
     #[fail(display = "cannot determine announce protocol")]
     AnnounceProtocolFailure,
     #[fail(display = "unknown announce protocol {}", _0)]
@@ -60,6 +88,8 @@ pub enum RsbtError {
     Storage(flat_storage::FlatStorageError),
     #[fail(display = "storage version {} is unsupported", _0)]
     StorageVersion(u8),
+    #[fail(display = "storage state checksum mismatch")]
+    StorageChecksum,
     #[fail(display = "failure {}", _0)]
     Failure(failure::Context<String>),
     #[fail(display = "toml deserialize {}", _0)]
@@ -68,10 +98,16 @@ pub enum RsbtError {
     TomlSerialize(toml::ser::Error),
     #[fail(display = "torrent with id {} not found", _0)]
     TorrentNotFound(usize),
+    #[fail(display = "super-seeding requires complete torrent")]
+    SuperSeedNotComplete,
     #[fail(display = "torrent file with id {} not found", _0)]
     TorrentFileNotFound(usize),
     #[fail(display = "torrent file range invalid")]
     TorrentFileRangeInvalid { file_size: usize },
+    #[fail(display = "invalid file name {:?}", _0)]
+    TorrentFileNameInvalid(String),
+    #[fail(display = "torrent data is being moved")]
+    TorrentDataMoving,
     #[fail(display = "torrent action not supported")]
     TorrentActionNotSupported,
     #[fail(display = "elapsed {}", _0)]
@@ -80,6 +116,20 @@ pub enum RsbtError {
     TorrentHttpAnnounceBadResponse(String),
     #[fail(display = "announce failure {}", _0)]
     TorrentHttpAnnounceFailure(hyper::Error),
+    #[fail(display = "no free port between {} and {}", port, port_max)]
+    NoFreePort { port: u16, port_max: u16 },
+    #[fail(display = "proxy authentication failure")]
+    ProxyAuthenticationFailure,
+    #[fail(display = "proxy connect failure: {}", _0)]
+    ProxyConnectFailure(String),
+    #[fail(display = "invalid response from proxy")]
+    ProxyInvalidResponse,
+    #[fail(display = "udp is not supported by http proxy")]
+    ProxyUdpUnsupported,
+    #[fail(display = "no gateway with port mapping support found")]
+    PortMappingGatewayNotFound,
+    #[fail(display = "port mapping failure: {}", _0)]
+    PortMappingFailure(String),
 }
 
 macro_rules! from_rsbt_error {
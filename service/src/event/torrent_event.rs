@@ -1,33 +1,46 @@
 use crate::{
     event::TorrentEventQueryPiece,
     file_download::FileDownloadStream,
+    mse::MseStream,
     request_response::RequestResponse,
     result::RsbtResult,
     types::{
-        public::{AnnounceView, FileView, PeerView, TorrentDownloadState},
-        Peer,
+        public::{AnnounceView, FilePriority, FileView, PeerView, TorrentDownloadState},
+        Handshake, Peer,
     },
 };
+use flat_storage::FlatStorageFile;
 use std::{
     fmt::{Display, Formatter},
+    net::{IpAddr, SocketAddr},
     ops::Range,
+    path::PathBuf,
 };
-use tokio::{net::TcpStream, sync::watch};
+use tokio::sync::watch;
 use uuid::Uuid;
 
 #[derive(Debug)]
 pub(crate) enum TorrentEvent {
     Announce(Vec<Peer>),
-    PeerConnected(Uuid, TcpStream),
-    PeerForwarded(TcpStream),
+    PeerConnected(Uuid, MseStream, Handshake),
+    PeerForwarded(MseStream, Handshake),
     PeerConnectFailed(Uuid),
+    PeerSelfConnection(Uuid),
     PeerDisconnect(Uuid),
     PeerPieces(Uuid, Vec<u8>),
     PeerPiece(Uuid, usize),
+    PeerChoke(Uuid),
     PeerUnchoke(Uuid),
     PeerInterested(Uuid),
+    PeerNotInterested(Uuid),
+    PeerClient(Uuid, String),
     PeerPieceDownloaded(Uuid, Vec<u8>),
     PeerPieceCanceled(Uuid),
+    PeerPieceHashFailed(Uuid, usize),
+    PeerPieceProgress(Uuid, usize),
+    CheckPeers,
+    /// Data verification or move is finished, pieces can be requested again.
+    StorageReady,
     PeerPieceRequest {
         peer_id: Uuid,
         index: u32,
@@ -39,6 +52,25 @@ pub(crate) enum TorrentEvent {
     Subscribe(RequestResponse<(), watch::Receiver<TorrentDownloadState>>),
     Delete(RequestResponse<bool, RsbtResult<()>>),
     PeersView(RequestResponse<(), RsbtResult<Vec<PeerView>>>),
+    AddPeer(RequestResponse<SocketAddr, RsbtResult<()>>),
+    /// Disconnects peers with address, banned for torrent only if flag is false.
+    BanPeer(RequestResponse<(IpAddr, bool), RsbtResult<()>>),
+    UnbanPeer(RequestResponse<IpAddr, RsbtResult<()>>),
+    /// Disconnects peers blocked by reloaded ip filter.
+    DisconnectBlocked(RequestResponse<(), RsbtResult<()>>),
+    /// Enables or disables super-seeding.
+    SuperSeed(RequestResponse<bool, RsbtResult<()>>),
+    FilePriority(RequestResponse<(usize, FilePriority), RsbtResult<()>>),
+    Sequential(RequestResponse<bool, RsbtResult<()>>),
+    FirstLastPieces(RequestResponse<bool, RsbtResult<()>>),
+    /// Moves torrent data to another directory.
+    Move(RequestResponse<PathBuf, RsbtResult<()>>),
+    /// Renames data files to paths of files on disk.
+    Rename(RequestResponse<Vec<FlatStorageFile>, RsbtResult<()>>),
+    /// Verifies stored data against piece hashes.
+    Recheck(RequestResponse<(), RsbtResult<()>>),
+    /// Saves storage state with pending changes.
+    SaveState(RequestResponse<(), RsbtResult<()>>),
     AnnounceView(RequestResponse<(), RsbtResult<Vec<AnnounceView>>>),
     FilesView(RequestResponse<(), RsbtResult<Vec<FileView>>>),
     FileDownload(RequestResponse<(usize, Option<Range<usize>>), RsbtResult<FileDownloadStream>>),
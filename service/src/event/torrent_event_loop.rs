@@ -1,5 +1,8 @@
 use crate::{
-    event::TorrentEvent, peer::PeerManager, process::TorrentToken, storage::TorrentStorage,
+    event::TorrentEvent,
+    peer::{BanList, ConnectionSlots, IpFilter, PeerManager},
+    process::TorrentToken,
+    storage::TorrentStorage,
     types::Properties,
 };
 use futures::StreamExt;
@@ -11,10 +14,20 @@ pub(crate) async fn torrent_event_loop(
     properties: Arc<Properties>,
     torrent_storage: TorrentStorage,
     torrent_process: Arc<TorrentToken>,
+    connection_slots: Arc<ConnectionSlots>,
+    ban_list: Arc<BanList>,
+    ip_filter: Arc<IpFilter>,
     mut broker_receiver: Receiver<TorrentEvent>,
 ) {
-    let mut peer_manager = PeerManager::new(properties, torrent_storage, torrent_process)
-        .expect("FIXME: need to turn this into non breaking failure");
+    let mut peer_manager = PeerManager::new(
+        properties,
+        torrent_storage,
+        torrent_process,
+        connection_slots,
+        ban_list,
+        ip_filter,
+    )
+    .expect("FIXME: need to turn this into non breaking failure");
     while let Some(event) = broker_receiver.next().await {
         debug!("received event: {}", event);
         match event {
@@ -25,19 +38,30 @@ pub(crate) async fn torrent_event_loop(
                 if let Some(_peer_state) = peer_manager.peer_remove_by_id(peer_id) {
                     debug!("[{}] removed peer due to disconnect", peer_id);
                 }
+                peer_manager.connect_candidates();
             }
             TorrentEvent::PeerConnectFailed(peer_id) => {
-                if let Some(_peer_state) = peer_manager.peer_remove_by_id(peer_id) {
+                if let Some(_peer_state) = peer_manager.peer_connect_failed(peer_id) {
                     debug!("[{}] removed peer due to connection failure", peer_id);
                 }
+                peer_manager.connect_candidates();
+            }
+            TorrentEvent::PeerSelfConnection(peer_id) => {
+                if let Some(_peer_state) = peer_manager.peer_self_connection(peer_id) {
+                    debug!("[{}] removed peer due to connection to self", peer_id);
+                }
+                peer_manager.connect_candidates();
             }
-            TorrentEvent::PeerForwarded(stream) => {
-                if let Err(err) = peer_manager.peer_forwarded(stream).await {
+            TorrentEvent::PeerForwarded(stream, handshake) => {
+                if let Err(err) = peer_manager.peer_forwarded(stream, handshake).await {
                     error!("cannot forward peer: {}", err);
                 }
             }
-            TorrentEvent::PeerConnected(peer_id, stream) => {
-                if let Err(err) = peer_manager.peer_connected(peer_id, stream).await {
+            TorrentEvent::PeerConnected(peer_id, stream, handshake) => {
+                if let Err(err) = peer_manager
+                    .peer_connected(peer_id, stream, handshake)
+                    .await
+                {
                     error!("[{}] cannot process peer connected: {}", peer_id, err);
                 }
             }
@@ -51,6 +75,11 @@ pub(crate) async fn torrent_event_loop(
                     error!("[{}] cannot process peer pieces: {}", peer_id, err);
                 }
             }
+            TorrentEvent::PeerChoke(peer_id) => {
+                if let Err(err) = peer_manager.peer_choke(peer_id).await {
+                    error!("[{}] cannot process peer choke: {}", peer_id, err);
+                }
+            }
             TorrentEvent::PeerUnchoke(peer_id) => {
                 if let Err(err) = peer_manager.peer_unchoke(peer_id).await {
                     error!("[{}] cannot process peer unchoke: {}", peer_id, err);
@@ -61,11 +90,40 @@ pub(crate) async fn torrent_event_loop(
                     error!("[{}] cannot process peer interested: {}", peer_id, err);
                 }
             }
+            TorrentEvent::PeerNotInterested(peer_id) => {
+                if let Err(err) = peer_manager.peer_not_interested(peer_id).await {
+                    error!("[{}] cannot process peer not interested: {}", peer_id, err);
+                }
+            }
+            TorrentEvent::PeerClient(peer_id, client) => {
+                peer_manager.peer_client(peer_id, client);
+            }
             TorrentEvent::PeerPieceCanceled(peer_id) => {
                 if let Err(err) = peer_manager.peer_piece_canceled(peer_id).await {
                     error!("[{}] cannot process peer piece canceled: {}", peer_id, err);
                 }
             }
+            TorrentEvent::PeerPieceHashFailed(peer_id, piece) => {
+                if let Err(err) = peer_manager.peer_piece_hash_failed(peer_id, piece).await {
+                    error!(
+                        "[{}] cannot process peer piece hash failed: {}",
+                        peer_id, err
+                    );
+                }
+            }
+            TorrentEvent::PeerPieceProgress(peer_id, length) => {
+                peer_manager.peer_piece_progress(peer_id, length);
+            }
+            TorrentEvent::CheckPeers => {
+                if let Err(err) = peer_manager.check_peers().await {
+                    error!("cannot check peers: {}", err);
+                }
+            }
+            TorrentEvent::StorageReady => {
+                if let Err(err) = peer_manager.storage_ready().await {
+                    error!("cannot request pieces after check or move: {}", err);
+                }
+            }
             TorrentEvent::PeerPieceDownloaded(peer_id, piece) => {
                 peer_manager.peer_piece_downloaded(peer_id, piece).await;
             }
@@ -98,6 +156,42 @@ pub(crate) async fn torrent_event_loop(
             TorrentEvent::PeersView(request_response) => {
                 peer_manager.peers_view(request_response).await;
             }
+            TorrentEvent::AddPeer(request_response) => {
+                peer_manager.add_peer(request_response).await;
+            }
+            TorrentEvent::BanPeer(request_response) => {
+                peer_manager.ban_peer_request(request_response).await;
+            }
+            TorrentEvent::UnbanPeer(request_response) => {
+                peer_manager.unban_peer(request_response).await;
+            }
+            TorrentEvent::DisconnectBlocked(request_response) => {
+                peer_manager.disconnect_blocked(request_response).await;
+            }
+            TorrentEvent::SuperSeed(request_response) => {
+                peer_manager.super_seed(request_response).await;
+            }
+            TorrentEvent::FilePriority(request_response) => {
+                peer_manager.file_priority(request_response).await;
+            }
+            TorrentEvent::Sequential(request_response) => {
+                peer_manager.sequential(request_response);
+            }
+            TorrentEvent::FirstLastPieces(request_response) => {
+                peer_manager.first_last_pieces(request_response);
+            }
+            TorrentEvent::Move(request_response) => {
+                peer_manager.move_storage(request_response).await;
+            }
+            TorrentEvent::Rename(request_response) => {
+                peer_manager.rename_storage(request_response).await;
+            }
+            TorrentEvent::Recheck(request_response) => {
+                peer_manager.recheck(request_response).await;
+            }
+            TorrentEvent::SaveState(request_response) => {
+                peer_manager.save_state(request_response).await;
+            }
             TorrentEvent::AnnounceView(request_response) => {
                 peer_manager.announce_view(request_response).await;
             }
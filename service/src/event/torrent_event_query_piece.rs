@@ -1,5 +1,5 @@
 use std::{
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, Weak},
     task::Waker,
 };
 
@@ -7,4 +7,8 @@ use std::{
 pub(crate) struct TorrentEventQueryPiece {
     pub(crate) piece: usize,
     pub(crate) waker: Arc<Mutex<Option<Waker>>>,
+    /// Last piece of requested range, pieces up to it are read ahead.
+    pub(crate) last_piece: usize,
+    /// Handle of stream, it is gone when stream is dropped.
+    pub(crate) stream: Weak<()>,
 }
@@ -5,4 +5,5 @@ pub enum TorrentStatisticMessage {
     Subscribe(RequestResponse<(), watch::Receiver<TorrentDownloadState>>),
     Downloaded(u64),
     Uploaded(u64),
+    HashFailed,
 }
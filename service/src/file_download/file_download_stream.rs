@@ -27,6 +27,10 @@ pub struct FileDownloadStream {
     pub(crate) torrent_process: Arc<TorrentToken>,
     pub(crate) state: FileDownloadState,
     pub(crate) waker: Arc<Mutex<Option<Waker>>>,
+    /// Last piece of requested range.
+    pub(crate) last_piece: usize,
+    /// Pieces are prioritized while stream is alive.
+    pub(crate) alive: Arc<()>,
 }
 
 impl Stream for FileDownloadStream {
@@ -52,6 +56,8 @@ impl Stream for FileDownloadStream {
                             RequestResponse::new(TorrentEventQueryPiece {
                                 piece: that.piece,
                                 waker: that.waker.clone(),
+                                last_piece: that.last_piece,
+                                stream: Arc::downgrade(&that.alive),
                             });
 
                         let torrent_process = that.torrent_process.clone();
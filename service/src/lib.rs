@@ -1,16 +1,21 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
+mod alt_speed;
 mod announce;
 mod app;
+mod bandwidth;
 mod command;
 mod errors;
 mod event;
 mod event_loop;
 mod file_download;
+mod mse;
 mod parser;
 mod peer;
 mod piece;
+mod port_mapping;
 mod process;
+mod proxy;
 mod request_response;
 mod result;
 mod spawn_and_log_error;
@@ -21,39 +26,89 @@ mod types;
 pub use app::App as RsbtApp;
 pub use command::Command as RsbtCommand;
 pub use command::CommandAddTorrent as RsbtCommandAddTorrent;
+pub use command::CommandAltSpeedMode as RsbtCommandAltSpeedMode;
+pub use command::CommandBandwidthLimits as RsbtCommandBandwidthLimits;
 pub use command::CommandDeleteTorrent as RsbtCommandDeleteTorrent;
 pub use command::CommandTorrentAction as RsbtCommandTorrentAction;
+pub use command::CommandTorrentAddPeer as RsbtCommandTorrentAddPeer;
 pub use command::CommandTorrentAnnounce as RsbtCommandTorrentAnnounce;
+pub use command::CommandTorrentBanPeer as RsbtCommandTorrentBanPeer;
+pub use command::CommandTorrentBandwidthLimits as RsbtCommandTorrentBandwidthLimits;
 pub use command::CommandTorrentDetail as RsbtCommandTorrentDetail;
 pub use command::CommandTorrentFileDownload as RsbtCommandTorrentFileDownload;
+pub use command::CommandTorrentFilePriority as RsbtCommandTorrentFilePriority;
 pub use command::CommandTorrentFiles as RsbtCommandTorrentFiles;
+pub use command::CommandTorrentMove as RsbtCommandTorrentMove;
 pub use command::CommandTorrentPeers as RsbtCommandTorrentPeers;
 pub use command::CommandTorrentPieces as RsbtCommandTorrentPieces;
+pub use command::CommandTorrentRename as RsbtCommandTorrentRename;
+pub use command::CommandTorrentUnbanPeer as RsbtCommandTorrentUnbanPeer;
 pub use errors::RsbtError;
 pub use process::TorrentProcess as RsbtTorrentProcess;
 pub use process::TorrentProcessStatus as RsbtTorrentProcessStatus;
 pub use request_response::RequestResponse as RsbtRequestResponse;
 pub(crate) use spawn_and_log_error::spawn_and_log_error;
+pub use types::public::AltSpeedPeriod as RsbtAltSpeedPeriod;
+pub use types::public::AltSpeedView as RsbtAltSpeedView;
+pub use types::public::BandwidthLimits as RsbtBandwidthLimits;
+pub use types::public::BandwidthView as RsbtBandwidthView;
+pub use types::public::FilePriority as RsbtFilePriority;
+pub use types::public::IpFilterView as RsbtIpFilterView;
+pub use types::public::StatusView as RsbtStatusView;
 pub use types::public::TorrentAction as RsbtTorrentAction;
 pub use types::public::TorrentDownloadView as RsbtTorrentDownloadView;
 pub use types::public::TorrentStatisticsEvent as RsbtTorrentStatisticsEvent;
 pub use types::Config as RsbtConfig;
 pub use types::Properties as RsbtProperties;
 pub use types::Settings as RsbtSettings;
+pub use types::StorageBackend as RsbtStorageBackend;
 pub use types::Torrent as RsbtTorrent;
 
 pub(crate) const SHA1_SIZE: usize = 20;
 
 pub(crate) const BLOCK_SIZE: usize = 1 << 14;
 
-pub(crate) const PEER_ID: [u8; 20] = *b"-rs0001-zzzzxxxxyyyy";
+/// Peer is banned after this count of pieces with wrong checksum.
+pub(crate) const PEER_MAX_HASH_FAILS: usize = 3;
+
+/// Connection failures are remembered for at most this count of addresses.
+pub(crate) const PEER_MAX_CONNECT_FAILS: usize = 1024;
+
+pub(crate) const PEER_MAX_CONNECTIONS: usize = 200;
+
+pub(crate) const PEER_MAX_CONNECTIONS_PER_TORRENT: usize = 50;
+
+pub(crate) const PEER_MAX_HALF_OPEN: usize = 8;
+
+/// Peer is disconnected after this time without any message.
+pub(crate) const PEER_IDLE_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Piece is reassigned if peer did not send a block for this time.
+pub(crate) const PEER_SNUB_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Maximum length of message from peer, larger messages are rejected before parsing.
+pub(crate) const PEER_MAX_MESSAGE_SIZE: usize = 1 << 20;
+
+/// Maximum length of requested block.
+pub(crate) const PEER_MAX_REQUEST_LENGTH: usize = 1 << 17;
+
+/// Connection to peer or proxy is dropped if it is not established in this time.
+pub(crate) const PEER_CONNECT_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Peer is disconnected if handshake is not received in this time.
+pub(crate) const PEER_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
-//FIXME: pub(crate) const PEER_MAX_CONNECTIONS: usize = 50;
 pub const TORRENTS_TOML: &str = "torrents.toml";
 
 pub const DEFAULT_CHANNEL_BUFFER: usize = 256;
 
-//FIXME: pub(crate) const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(110);
+/// Open files per torrent kept by file storage.
+pub(crate) const STORAGE_MAX_OPEN_FILES: usize = 64;
+
+/// Pieces after position of file download stream, which are downloaded first.
+pub(crate) const STREAM_READ_AHEAD: usize = 8;
+
+pub(crate) const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(110);
 
 pub(crate) fn count_parts(total: usize, part_size: usize) -> usize {
     total / part_size + if total % part_size != 0 { 1 } else { 0 }
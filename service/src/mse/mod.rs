@@ -0,0 +1,7 @@
+mod mse_handshake;
+mod mse_stream;
+mod rc4;
+
+pub(crate) use mse_handshake::{mse_accept, mse_initiate, obfuscated_info_hash};
+pub(crate) use mse_stream::MseStream;
+pub(crate) use rc4::Rc4;
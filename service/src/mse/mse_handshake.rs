@@ -0,0 +1,349 @@
+use crate::{
+    mse::{MseStream, Rc4},
+    types::EncryptionMode,
+    RsbtError, SHA1_SIZE,
+};
+use bytes::{BufMut, BytesMut};
+use num_bigint::BigUint;
+use rand::{random, thread_rng, Rng};
+use sha1::{Digest, Sha1};
+use std::{convert::TryInto, future::Future};
+use tokio::{net::TcpStream, prelude::*};
+
+/// 768-bit safe prime of MSE key exchange, generator is 2.
+const DH_PRIME: [u8; DH_KEY_SIZE] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xc9, 0x0f, 0xda, 0xa2, 0x21, 0x68, 0xc2, 0x34,
+    0xc4, 0xc6, 0x62, 0x8b, 0x80, 0xdc, 0x1c, 0xd1, 0x29, 0x02, 0x4e, 0x08, 0x8a, 0x67, 0xcc, 0x74,
+    0x02, 0x0b, 0xbe, 0xa6, 0x3b, 0x13, 0x9b, 0x22, 0x51, 0x4a, 0x08, 0x79, 0x8e, 0x34, 0x04, 0xdd,
+    0xef, 0x95, 0x19, 0xb3, 0xcd, 0x3a, 0x43, 0x1b, 0x30, 0x2b, 0x0a, 0x6d, 0xf2, 0x5f, 0x14, 0x37,
+    0x4f, 0xe1, 0x35, 0x6d, 0x6d, 0x51, 0xc2, 0x45, 0xe4, 0x85, 0xb5, 0x76, 0x62, 0x5e, 0x7e, 0xc6,
+    0xf4, 0x4c, 0x42, 0xe9, 0xa6, 0x3a, 0x36, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x05, 0x63,
+];
+const DH_GENERATOR: u32 = 2;
+const DH_KEY_SIZE: usize = 96;
+const DH_PRIVATE_KEY_SIZE: usize = 20;
+
+/// Verification constant, sent encrypted to find end of padding.
+const VC: [u8; 8] = [0; 8];
+const CRYPTO_PLAINTEXT: u32 = 0x01;
+const CRYPTO_RC4: u32 = 0x02;
+const MAX_PAD_SIZE: usize = 512;
+const RC4_DISCARD: usize = 1024;
+
+/// Performs MSE handshake as connection initiator.
+///
+/// Info hash is the shared secret, so the torrent must be known to peer.
+pub(crate) async fn mse_initiate(
+    mut stream: TcpStream,
+    info_hash: &[u8; SHA1_SIZE],
+    mode: EncryptionMode,
+) -> Result<MseStream, RsbtError> {
+    let (private_key, public_key) = dh_keys();
+    stream
+        .write_all(&[public_key, random_pad()].concat())
+        .await?;
+
+    let mut remote_public_key = [0u8; DH_KEY_SIZE];
+    stream.read_exact(&mut remote_public_key).await?;
+    let secret = dh_secret(&private_key, &remote_public_key)?;
+
+    let mut encrypt = rc4(b"keyA", &secret, info_hash);
+    let mut decrypt = rc4(b"keyB", &secret, info_hash);
+
+    let crypto_provide = match mode {
+        EncryptionMode::Forced => CRYPTO_RC4,
+        _ => CRYPTO_RC4 | CRYPTO_PLAINTEXT,
+    };
+    let pad = random_pad();
+    let mut encrypted = BytesMut::new();
+    encrypted.put_slice(&VC);
+    encrypted.put_u32(crypto_provide);
+    encrypted.put_u16(pad.len() as u16);
+    encrypted.put_slice(&pad);
+    // initial payload is not used, handshake is sent after negotiation
+    encrypted.put_u16(0);
+    encrypt.apply(&mut encrypted);
+
+    let mut request = BytesMut::new();
+    request.put_slice(&hash(&[b"req1", &secret]));
+    request.put_slice(&xor(
+        &obfuscated_info_hash(info_hash),
+        &hash(&[b"req3", &secret]),
+    ));
+    request.put_slice(&encrypted);
+    stream.write_all(&request).await?;
+
+    let mut vc = VC;
+    decrypt.apply(&mut vc);
+    read_sync(&mut stream, &vc).await?;
+
+    let mut reply = [0u8; 6];
+    stream.read_exact(&mut reply).await?;
+    decrypt.apply(&mut reply);
+    let crypto_select = u32::from_be_bytes(reply[..4].try_into()?);
+    let pad_size = u16::from_be_bytes(reply[4..].try_into()?) as usize;
+    if pad_size > MAX_PAD_SIZE {
+        return Err(RsbtError::PeerEncryptionFailure(
+            "padding is too long".into(),
+        ));
+    }
+    let mut pad = vec![0u8; pad_size];
+    stream.read_exact(&mut pad).await?;
+    decrypt.apply(&mut pad);
+
+    match crypto_select {
+        CRYPTO_RC4 => Ok(MseStream::new(
+            stream,
+            Some(decrypt),
+            Some(encrypt),
+            BytesMut::new(),
+        )),
+        CRYPTO_PLAINTEXT if mode != EncryptionMode::Forced => Ok(MseStream::plaintext(stream)),
+        _ => Err(RsbtError::PeerEncryptionFailure(format!(
+            "unexpected crypto method {}",
+            crypto_select
+        ))),
+    }
+}
+
+/// Performs MSE handshake for incoming connection, which started with `prefix`.
+///
+/// Torrent is found by `find_info_hash` from its obfuscated hash.
+pub(crate) async fn mse_accept<F, FF>(
+    mut stream: TcpStream,
+    prefix: &[u8],
+    mode: EncryptionMode,
+    find_info_hash: F,
+) -> Result<MseStream, RsbtError>
+where
+    F: FnOnce([u8; SHA1_SIZE]) -> FF,
+    FF: Future<Output = Result<Option<[u8; SHA1_SIZE]>, RsbtError>>,
+{
+    let mut remote_public_key = [0u8; DH_KEY_SIZE];
+    remote_public_key[..prefix.len()].copy_from_slice(prefix);
+    stream
+        .read_exact(&mut remote_public_key[prefix.len()..])
+        .await?;
+
+    let (private_key, public_key) = dh_keys();
+    stream
+        .write_all(&[public_key, random_pad()].concat())
+        .await?;
+    let secret = dh_secret(&private_key, &remote_public_key)?;
+
+    read_sync(&mut stream, &hash(&[b"req1", &secret])).await?;
+
+    let mut obfuscated_hash = [0u8; SHA1_SIZE];
+    stream.read_exact(&mut obfuscated_hash).await?;
+    let obfuscated_hash = xor(&obfuscated_hash, &hash(&[b"req3", &secret]));
+    let info_hash = find_info_hash(obfuscated_hash)
+        .await?
+        .ok_or_else(|| RsbtError::PeerEncryptionFailure("torrent not found".into()))?;
+
+    let mut decrypt = rc4(b"keyA", &secret, &info_hash);
+    let mut encrypt = rc4(b"keyB", &secret, &info_hash);
+
+    let mut request = [0u8; 14];
+    stream.read_exact(&mut request).await?;
+    decrypt.apply(&mut request);
+    if request[..8] != VC {
+        return Err(RsbtError::PeerEncryptionFailure(
+            "verification constant mismatch".into(),
+        ));
+    }
+    let crypto_provide = u32::from_be_bytes(request[8..12].try_into()?);
+    let pad_size = u16::from_be_bytes(request[12..].try_into()?) as usize;
+    if pad_size > MAX_PAD_SIZE {
+        return Err(RsbtError::PeerEncryptionFailure(
+            "padding is too long".into(),
+        ));
+    }
+
+    // padding is followed by size of initial payload
+    let mut pad = vec![0u8; pad_size + 2];
+    stream.read_exact(&mut pad).await?;
+    decrypt.apply(&mut pad);
+    let initial_payload_size = u16::from_be_bytes(pad[pad_size..].try_into()?) as usize;
+
+    // initial payload is always encrypted, the method is not selected yet
+    let mut initial_payload = vec![0u8; initial_payload_size];
+    stream.read_exact(&mut initial_payload).await?;
+    decrypt.apply(&mut initial_payload);
+
+    let crypto_select = if crypto_provide & CRYPTO_RC4 != 0 {
+        CRYPTO_RC4
+    } else if crypto_provide & CRYPTO_PLAINTEXT != 0 && mode != EncryptionMode::Forced {
+        CRYPTO_PLAINTEXT
+    } else {
+        return Err(RsbtError::PeerEncryptionFailure(format!(
+            "no supported crypto method in {}",
+            crypto_provide
+        )));
+    };
+
+    let pad = random_pad();
+    let mut reply = BytesMut::new();
+    reply.put_slice(&VC);
+    reply.put_u32(crypto_select);
+    reply.put_u16(pad.len() as u16);
+    reply.put_slice(&pad);
+    encrypt.apply(&mut reply);
+    stream.write_all(&reply).await?;
+
+    Ok(if crypto_select == CRYPTO_RC4 {
+        MseStream::new(
+            stream,
+            Some(decrypt),
+            Some(encrypt),
+            initial_payload.as_slice().into(),
+        )
+    } else {
+        MseStream::new(stream, None, None, initial_payload.as_slice().into())
+    })
+}
+
+/// Torrent identifier sent by initiator, info hash itself is not revealed.
+pub(crate) fn obfuscated_info_hash(info_hash: &[u8; SHA1_SIZE]) -> [u8; SHA1_SIZE] {
+    hash(&[b"req2", info_hash])
+}
+
+fn dh_keys() -> (BigUint, Vec<u8>) {
+    let private_key = BigUint::from_bytes_be(&random::<[u8; DH_PRIVATE_KEY_SIZE]>());
+    let public_key = BigUint::from(DH_GENERATOR).modpow(&private_key, &dh_prime());
+    (private_key, to_key_bytes(&public_key))
+}
+
+fn dh_secret(
+    private_key: &BigUint,
+    remote_public_key: &[u8; DH_KEY_SIZE],
+) -> Result<Vec<u8>, RsbtError> {
+    let prime = dh_prime();
+    let remote_public_key = BigUint::from_bytes_be(remote_public_key);
+    if remote_public_key <= BigUint::from(1u32) || remote_public_key >= prime {
+        return Err(RsbtError::PeerEncryptionFailure(
+            "invalid public key".into(),
+        ));
+    }
+    Ok(to_key_bytes(&remote_public_key.modpow(private_key, &prime)))
+}
+
+fn dh_prime() -> BigUint {
+    BigUint::from_bytes_be(&DH_PRIME)
+}
+
+/// Big-endian bytes padded to key size.
+fn to_key_bytes(value: &BigUint) -> Vec<u8> {
+    let bytes = value.to_bytes_be();
+    let mut key = vec![0u8; DH_KEY_SIZE - bytes.len()];
+    key.extend_from_slice(&bytes);
+    key
+}
+
+fn hash(parts: &[&[u8]]) -> [u8; SHA1_SIZE] {
+    let mut hasher = Sha1::new();
+    for part in parts {
+        hasher.input(part);
+    }
+    hasher.result().into()
+}
+
+fn xor(a: &[u8; SHA1_SIZE], b: &[u8; SHA1_SIZE]) -> [u8; SHA1_SIZE] {
+    let mut result = *a;
+    for (x, y) in result.iter_mut().zip(b.iter()) {
+        *x ^= y;
+    }
+    result
+}
+
+fn rc4(name: &[u8], secret: &[u8], info_hash: &[u8; SHA1_SIZE]) -> Rc4 {
+    let mut rc4 = Rc4::new(&hash(&[name, secret, info_hash]));
+    rc4.discard(RC4_DISCARD);
+    rc4
+}
+
+fn random_pad() -> Vec<u8> {
+    let mut rng = thread_rng();
+    let size = rng.gen_range(0, MAX_PAD_SIZE + 1);
+    (0..size).map(|_| rng.gen()).collect()
+}
+
+/// Reads until pattern, which must appear after padding of at most `MAX_PAD_SIZE`.
+async fn read_sync(stream: &mut TcpStream, pattern: &[u8]) -> Result<(), RsbtError> {
+    let mut received = Vec::with_capacity(MAX_PAD_SIZE + pattern.len());
+    while !received.ends_with(pattern) {
+        if received.len() == MAX_PAD_SIZE + pattern.len() {
+            return Err(RsbtError::PeerEncryptionFailure(
+                "synchronization pattern not found".into(),
+            ));
+        }
+        received.push(stream.read_u8().await?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    async fn mse_pair(
+        initiator_mode: EncryptionMode,
+        receiver_mode: EncryptionMode,
+    ) -> (Result<MseStream, RsbtError>, Result<MseStream, RsbtError>) {
+        let info_hash = [7u8; SHA1_SIZE];
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let receiver = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut prefix = [0u8; 20];
+            stream.read_exact(&mut prefix).await.unwrap();
+            mse_accept(
+                stream,
+                &prefix,
+                receiver_mode,
+                |obfuscated_hash| async move {
+                    Ok(Some(info_hash).filter(|x| obfuscated_info_hash(x) == obfuscated_hash))
+                },
+            )
+            .await
+        });
+
+        let initiator = mse_initiate(
+            TcpStream::connect(addr).await.unwrap(),
+            &info_hash,
+            initiator_mode,
+        )
+        .await;
+
+        (initiator, receiver.await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn mse_encrypted_exchange() {
+        let (initiator, receiver) = mse_pair(EncryptionMode::Enabled, EncryptionMode::Forced).await;
+        let (mut initiator, mut receiver) = (initiator.unwrap(), receiver.unwrap());
+        assert!(initiator.is_encrypted());
+        assert!(receiver.is_encrypted());
+
+        initiator.write_all(b"ping").await.unwrap();
+        let mut data = [0u8; 4];
+        receiver.read_exact(&mut data).await.unwrap();
+        assert_eq!(&data, b"ping");
+
+        receiver.write_all(b"pong").await.unwrap();
+        initiator.read_exact(&mut data).await.unwrap();
+        assert_eq!(&data, b"pong");
+    }
+
+    #[test]
+    fn dh_shared_secret() {
+        let (private_a, public_a) = dh_keys();
+        let (private_b, public_b) = dh_keys();
+        assert_eq!(public_a.len(), DH_KEY_SIZE);
+        assert_eq!(
+            dh_secret(&private_a, &public_b[..].try_into().unwrap()).unwrap(),
+            dh_secret(&private_b, &public_a[..].try_into().unwrap()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use crate::mse::Rc4;
+use bytes::{Buf, BytesMut};
+use std::{
+    fmt, io,
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+
+/// Peer connection, RC4-encrypted after MSE handshake or plaintext.
+pub(crate) struct MseStream {
+    stream: TcpStream,
+    read_cipher: Option<Rc4>,
+    write_cipher: Option<Rc4>,
+    /// Decrypted data received during handshake.
+    pending: BytesMut,
+    /// Encrypted data not yet written to socket.
+    write_buffer: BytesMut,
+}
+
+impl fmt::Debug for MseStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MseStream")
+            .field("stream", &self.stream)
+            .field("encrypted", &self.is_encrypted())
+            .finish()
+    }
+}
+
+impl MseStream {
+    pub(crate) fn plaintext(stream: TcpStream) -> Self {
+        Self::new(stream, None, None, BytesMut::new())
+    }
+
+    pub(crate) fn new(
+        stream: TcpStream,
+        read_cipher: Option<Rc4>,
+        write_cipher: Option<Rc4>,
+        pending: BytesMut,
+    ) -> Self {
+        Self {
+            stream,
+            read_cipher,
+            write_cipher,
+            pending,
+            write_buffer: BytesMut::new(),
+        }
+    }
+
+    pub(crate) fn is_encrypted(&self) -> bool {
+        self.write_cipher.is_some()
+    }
+
+    pub(crate) fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    fn poll_write_buffer(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.write_buffer.is_empty() {
+            match Pin::new(&mut self.stream).poll_write(cx, &self.write_buffer)? {
+                Poll::Ready(0) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(written) => self.write_buffer.advance(written),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for MseStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        if !this.pending.is_empty() {
+            let size = this.pending.len().min(buf.len());
+            buf[..size].copy_from_slice(&this.pending[..size]);
+            this.pending.advance(size);
+            return Poll::Ready(Ok(size));
+        }
+
+        let size = futures::ready!(Pin::new(&mut this.stream).poll_read(cx, buf))?;
+        if let Some(cipher) = this.read_cipher.as_mut() {
+            cipher.apply(&mut buf[..size]);
+        }
+        Poll::Ready(Ok(size))
+    }
+}
+
+impl AsyncWrite for MseStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        if this.write_cipher.is_none() {
+            return Pin::new(&mut this.stream).poll_write(cx, buf);
+        }
+
+        // data is encrypted once, so it is accepted only when previous data is written
+        futures::ready!(this.poll_write_buffer(cx))?;
+
+        this.write_buffer.extend_from_slice(buf);
+        if let Some(cipher) = this.write_cipher.as_mut() {
+            cipher.apply(&mut this.write_buffer);
+        }
+
+        if let Poll::Ready(Err(err)) = this.poll_write_buffer(cx) {
+            return Poll::Ready(Err(err));
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        futures::ready!(this.poll_write_buffer(cx))?;
+        Pin::new(&mut this.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        futures::ready!(this.poll_write_buffer(cx))?;
+        Pin::new(&mut this.stream).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,52 @@
+/// RC4 stream cipher, MSE requires no other cipher.
+pub(crate) struct Rc4 {
+    state: [u8; 256],
+    i: u8,
+    j: u8,
+}
+
+impl Rc4 {
+    pub(crate) fn new(key: &[u8]) -> Self {
+        let mut state = [0u8; 256];
+        for (i, x) in state.iter_mut().enumerate() {
+            *x = i as u8;
+        }
+
+        let mut j = 0u8;
+        for i in 0..256 {
+            j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+            state.swap(i, j as usize);
+        }
+
+        Self { state, i: 0, j: 0 }
+    }
+
+    /// Encrypts or decrypts data in place.
+    pub(crate) fn apply(&mut self, data: &mut [u8]) {
+        for x in data.iter_mut() {
+            self.i = self.i.wrapping_add(1);
+            self.j = self.j.wrapping_add(self.state[self.i as usize]);
+            self.state.swap(self.i as usize, self.j as usize);
+            let k = self.state
+                [self.state[self.i as usize].wrapping_add(self.state[self.j as usize]) as usize];
+            *x ^= k;
+        }
+    }
+
+    /// Skips keystream, MSE discards first 1024 bytes.
+    pub(crate) fn discard(&mut self, count: usize) {
+        self.apply(&mut vec![0u8; count]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rc4_test_vector() {
+        let mut data = *b"Plaintext";
+        Rc4::new(b"Key").apply(&mut data);
+        assert_eq!(data, [0xbb, 0xf3, 0x16, 0xe8, 0xd9, 0x40, 0xaf, 0x0a, 0xd3]);
+    }
+}
@@ -14,7 +14,7 @@ named!(
                     2 => value!(Message::Interested) |
                     3 => value!(Message::NotInterested)
                 ) >> (m)) |
-                _ => do_parse!(id: be_u8 >> m: switch!(value!(id),
+                _ => do_parse!(id: be_u8 >> m: map_opt!(switch!(value!(id),
                     4 => cond!(len == 5, map!(be_u32, |x| Message::Have { piece_index: x})) |
                     5 => map!(take!(len - 1), |x| Some(Message::Bitfield(x.into()))) |
                     6 => cond!(len == 13, do_parse!(index: be_u32 >> begin: be_u32 >> length: be_u32 >> (Message::Request {
@@ -26,8 +26,11 @@ named!(
                     8 => cond!(len == 13, do_parse!(index: be_u32 >> begin: be_u32 >> length: be_u32 >> (Message::Cancel {
                         index, begin, length
                     }))) |
-                    9 => cond!(len == 3, map!(be_u16, |x| Message::Port(x)))
-                ) >> (m.unwrap()))
+                    9 => cond!(len == 3, map!(be_u16, |x| Message::Port(x))) |
+                    20 => cond!(len >= 2, do_parse!(extended_id: be_u8 >> payload: take!(len - 2) >> (Message::Extended {
+                        id: extended_id, payload: payload.into()
+                    })))
+                ), |x| x) >> (m))
             )
             >> (m)
     )
@@ -74,6 +77,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn message_extended() {
+        parse(
+            &[0, 0, 0, 5, 20, 0, b'd', b'e', b'e'],
+            Message::Extended {
+                id: 0,
+                payload: b"dee".to_vec(),
+            },
+        );
+    }
+
+    #[test]
+    fn message_malformed_length() {
+        assert!(parser_message(&[0, 0, 0, 6, 4, 0, 0, 0, 10, 0]).is_err());
+    }
+
     #[test]
     fn message_bitfield() {
         parse(&[0, 0, 0, 4, 5, 1, 2, 3], Message::Bitfield(vec![1, 2, 3]));
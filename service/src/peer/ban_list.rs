@@ -0,0 +1,21 @@
+use std::{collections::HashSet, net::IpAddr, sync::RwLock};
+
+/// Addresses banned for all torrents.
+#[derive(Debug, Default)]
+pub(crate) struct BanList {
+    banned: RwLock<HashSet<IpAddr>>,
+}
+
+impl BanList {
+    pub(crate) fn contains(&self, ip: &IpAddr) -> bool {
+        self.banned.read().unwrap().contains(ip)
+    }
+
+    pub(crate) fn ban(&self, ip: IpAddr) {
+        self.banned.write().unwrap().insert(ip);
+    }
+
+    pub(crate) fn unban(&self, ip: &IpAddr) {
+        self.banned.write().unwrap().remove(ip);
+    }
+}
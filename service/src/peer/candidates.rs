@@ -0,0 +1,121 @@
+use crate::types::Peer;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::{IpAddr, SocketAddr},
+};
+
+/// Announced peers waiting for free connection slot.
+///
+/// Peers are kept in order of arrival, queued addresses are looked up in set.
+#[derive(Debug, Default)]
+pub(crate) struct Candidates {
+    peers: VecDeque<Peer>,
+    addrs: HashSet<SocketAddr>,
+}
+
+impl Candidates {
+    /// Queues peer, returns `false` if its address is already queued.
+    pub(crate) fn push(&mut self, peer: Peer) -> bool {
+        if !self.addrs.insert(SocketAddr::new(peer.ip, peer.port)) {
+            return false;
+        }
+        self.peers.push_back(peer);
+        true
+    }
+
+    pub(crate) fn contains(&self, addr: &SocketAddr) -> bool {
+        self.addrs.contains(addr)
+    }
+
+    /// Moves queued peer to front, so it is connected first.
+    pub(crate) fn prioritize(&mut self, addr: SocketAddr) {
+        if !self.addrs.contains(&addr) {
+            return;
+        }
+        if let Some(position) = self
+            .peers
+            .iter()
+            .position(|x| SocketAddr::new(x.ip, x.port) == addr)
+        {
+            if let Some(peer) = self.peers.remove(position) {
+                self.peers.push_front(peer);
+            }
+        }
+    }
+
+    /// Takes peer with least connection failures, earliest queued of equal ones.
+    pub(crate) fn take(&mut self, connect_fails: &HashMap<SocketAddr, usize>) -> Option<Peer> {
+        let position = self
+            .peers
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, x)| {
+                connect_fails
+                    .get(&SocketAddr::new(x.ip, x.port))
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .map(|(position, _)| position)?;
+        let peer = self.peers.remove(position)?;
+        self.addrs.remove(&SocketAddr::new(peer.ip, peer.port));
+        Some(peer)
+    }
+
+    /// Drops queued peers with address.
+    pub(crate) fn remove_ip(&mut self, ip: IpAddr) {
+        self.peers.retain(|x| x.ip != ip);
+        self.addrs.retain(|x| x.ip() != ip);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.peers.clear();
+        self.addrs.clear();
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn peer(last: u8, port: u16) -> Peer {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, last)), port).into()
+    }
+
+    #[test]
+    fn check_candidates() {
+        let mut candidates = Candidates::default();
+        assert!(candidates.push(peer(1, 6881)));
+        assert!(candidates.push(peer(2, 6881)));
+        assert!(candidates.push(peer(2, 6882)));
+        assert!(candidates.push(peer(3, 6881)));
+        assert!(!candidates.push(peer(1, 6881)));
+        assert_eq!(candidates.len(), 4);
+        assert!(candidates.contains(&SocketAddr::new(peer(2, 6882).ip, 6882)));
+
+        let mut connect_fails = HashMap::new();
+        connect_fails.insert(SocketAddr::new(peer(1, 6881).ip, 6881), 1);
+        assert_eq!(candidates.take(&connect_fails), Some(peer(2, 6881)));
+
+        candidates.prioritize(SocketAddr::new(peer(3, 6881).ip, 6881));
+        assert_eq!(candidates.take(&connect_fails), Some(peer(3, 6881)));
+
+        candidates.remove_ip(peer(2, 0).ip);
+        assert_eq!(candidates.take(&connect_fails), Some(peer(1, 6881)));
+        assert!(candidates.is_empty());
+        assert_eq!(candidates.take(&connect_fails), None);
+
+        assert!(candidates.push(peer(1, 6881)));
+        candidates.clear();
+        assert!(candidates.is_empty());
+        assert!(candidates.push(peer(1, 6881)));
+    }
+}
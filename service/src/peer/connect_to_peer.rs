@@ -1,27 +1,61 @@
 use crate::{
     event::TorrentEvent,
+    mse::{mse_initiate, MseStream},
     process::TorrentToken,
-    types::{Handshake, Peer},
-    RsbtError,
+    proxy::proxy_connect,
+    types::{EncryptionMode, Handshake, Peer, ProxyConfig},
+    RsbtError, PEER_CONNECT_TIMEOUT, PEER_HANDSHAKE_TIMEOUT,
 };
-use log::error;
+use log::{debug, error};
 use std::{convert::TryInto, net::SocketAddr, sync::Arc};
-use tokio::{net::TcpStream, prelude::*};
+use tokio::{net::TcpStream, prelude::*, time::timeout};
 use uuid::Uuid;
 
 pub(crate) async fn connect_to_peer(
     torrent_process: Arc<TorrentToken>,
     peer_id: Uuid,
     peer: Peer,
+    proxy: Option<ProxyConfig>,
+    encryption: EncryptionMode,
 ) -> Result<(), RsbtError> {
-    let socket_addr = SocketAddr::new(peer.ip, peer.port);
-    let mut stream = TcpStream::connect(socket_addr).await?;
+    let mut stream = match encryption {
+        EncryptionMode::Disabled => MseStream::plaintext(open_stream(&peer, &proxy).await?),
+        _ => {
+            let encrypted = timeout(
+                PEER_HANDSHAKE_TIMEOUT,
+                mse_initiate(
+                    open_stream(&peer, &proxy).await?,
+                    &torrent_process.hash_id,
+                    encryption,
+                ),
+            )
+            .await
+            .map_err(|_| RsbtError::PeerHandshakeTimeout)
+            .and_then(|x| x);
+            match encrypted {
+                Ok(stream) => stream,
+                Err(err) if encryption == EncryptionMode::Enabled => {
+                    debug!(
+                        "[{}] peer {:?}: encrypted handshake failed: {}, retry plaintext",
+                        peer_id, peer, err
+                    );
+                    MseStream::plaintext(open_stream(&peer, &proxy).await?)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    };
 
     stream.write_all(&torrent_process.handshake).await?;
 
     let mut handshake_reply = vec![0u8; 68];
 
-    stream.read_exact(&mut handshake_reply).await?;
+    timeout(
+        PEER_HANDSHAKE_TIMEOUT,
+        stream.read_exact(&mut handshake_reply),
+    )
+    .await
+    .map_err(|_| RsbtError::PeerHandshakeTimeout)??;
 
     let handshake_reply: Handshake = handshake_reply.try_into()?;
 
@@ -35,11 +69,41 @@ pub(crate) async fn connect_to_peer(
         return Ok(());
     }
 
+    if handshake_reply.peer_id == torrent_process.peer_id {
+        debug!(
+            "[{}] peer {:?}: connection to self. Disconnect.",
+            peer_id, peer
+        );
+        torrent_process
+            .broker_sender
+            .clone()
+            .send(TorrentEvent::PeerSelfConnection(peer_id))
+            .await?;
+        return Ok(());
+    }
+
     torrent_process
         .broker_sender
         .clone()
-        .send(TorrentEvent::PeerConnected(peer_id, stream))
+        .send(TorrentEvent::PeerConnected(
+            peer_id,
+            stream,
+            handshake_reply,
+        ))
         .await?;
 
     Ok(())
 }
+
+async fn open_stream(peer: &Peer, proxy: &Option<ProxyConfig>) -> Result<TcpStream, RsbtError> {
+    Ok(match proxy {
+        Some(proxy) => proxy_connect(proxy, &peer.ip.to_string(), peer.port).await?,
+        None => {
+            timeout(
+                PEER_CONNECT_TIMEOUT,
+                TcpStream::connect(SocketAddr::new(peer.ip, peer.port)),
+            )
+            .await??
+        }
+    })
+}
@@ -0,0 +1,126 @@
+use crate::types::Properties;
+use std::sync::{Arc, Mutex};
+
+/// Connection counters shared between all torrents.
+///
+/// Connecting (half-open) sockets are counted in total connections too.
+#[derive(Debug)]
+pub(crate) struct ConnectionSlots {
+    max_connections: usize,
+    max_half_open: usize,
+    counters: Mutex<ConnectionCounters>,
+}
+
+#[derive(Debug, Default)]
+struct ConnectionCounters {
+    connecting: usize,
+    connected: usize,
+}
+
+/// Reserved connection slot, released on drop.
+#[derive(Debug)]
+pub(crate) struct ConnectionSlot {
+    slots: Arc<ConnectionSlots>,
+    connecting: bool,
+}
+
+impl ConnectionSlots {
+    pub(crate) fn new(max_connections: usize, max_half_open: usize) -> Self {
+        Self {
+            max_connections,
+            max_half_open,
+            counters: Mutex::new(Default::default()),
+        }
+    }
+
+    pub(crate) fn from_properties(properties: &Properties) -> Self {
+        Self::new(properties.max_connections, properties.max_half_open)
+    }
+
+    /// Reserves slot for outgoing connection.
+    pub(crate) fn connecting(self: &Arc<Self>) -> Option<ConnectionSlot> {
+        let mut counters = self.counters.lock().unwrap();
+        if counters.connecting >= self.max_half_open
+            || counters.connecting + counters.connected >= self.max_connections
+        {
+            return None;
+        }
+        counters.connecting += 1;
+        Some(ConnectionSlot {
+            slots: self.clone(),
+            connecting: true,
+        })
+    }
+
+    /// Reserves slot for established (incoming) connection.
+    pub(crate) fn connected(self: &Arc<Self>) -> Option<ConnectionSlot> {
+        let mut counters = self.counters.lock().unwrap();
+        if counters.connecting + counters.connected >= self.max_connections {
+            return None;
+        }
+        counters.connected += 1;
+        Some(ConnectionSlot {
+            slots: self.clone(),
+            connecting: false,
+        })
+    }
+}
+
+impl ConnectionSlot {
+    /// Outgoing connection established, half-open slot becomes connected slot.
+    pub(crate) fn established(&mut self) {
+        if self.connecting {
+            let mut counters = self.slots.counters.lock().unwrap();
+            counters.connecting -= 1;
+            counters.connected += 1;
+            self.connecting = false;
+        }
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        let mut counters = self.slots.counters.lock().unwrap();
+        if self.connecting {
+            counters.connecting -= 1;
+        } else {
+            counters.connected -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Arc, ConnectionSlots};
+
+    #[test]
+    fn connection_slots_half_open() {
+        let slots = Arc::new(ConnectionSlots::new(3, 2));
+
+        let mut first = slots.connecting().expect("first half-open slot");
+        let second = slots.connecting().expect("second half-open slot");
+        assert!(slots.connecting().is_none());
+
+        first.established();
+        let third = slots.connecting().expect("half-open slot freed");
+        assert!(slots.connected().is_none());
+
+        drop(second);
+        drop(third);
+        assert!(slots.connecting().is_some());
+        assert!(slots.connected().is_some());
+    }
+
+    #[test]
+    fn connection_slots_total() {
+        let slots = Arc::new(ConnectionSlots::new(2, 2));
+
+        let first = slots.connected().expect("first slot");
+        let _second = slots.connecting().expect("second slot");
+        assert!(slots.connecting().is_none());
+        assert!(slots.connected().is_none());
+
+        drop(first);
+        assert!(slots.connected().is_some());
+    }
+}
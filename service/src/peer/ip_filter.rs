@@ -0,0 +1,110 @@
+use crate::{
+    peer::{parse_ip_filter, IpRanges, ParsedIpFilter},
+    types::{public::IpFilterView, Properties},
+    RsbtError,
+};
+use flate2::read::GzDecoder;
+use log::{debug, warn};
+use std::{
+    io::Read,
+    net::IpAddr,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        RwLock,
+    },
+};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Address ranges blocked for all torrents, loaded from filter lists.
+#[derive(Debug, Default)]
+pub(crate) struct IpFilter {
+    paths: Vec<PathBuf>,
+    ranges: RwLock<IpRanges>,
+    invalid_lines: AtomicUsize,
+    blocked: AtomicU64,
+}
+
+impl IpFilter {
+    pub(crate) fn from_properties(properties: &Properties) -> Self {
+        Self {
+            paths: properties.ip_filter.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Loads all filter lists, current ranges are kept if any list cannot be read.
+    pub(crate) fn reload(&self) -> Result<(), RsbtError> {
+        let mut parsed = ParsedIpFilter::default();
+        for path in &self.paths {
+            let file = parse_ip_filter(&decode_filter_list(std::fs::read(path)?)?);
+            if file.invalid_lines > 0 {
+                warn!(
+                    "ip filter {}: {} invalid lines skipped",
+                    path.display(),
+                    file.invalid_lines
+                );
+            }
+            parsed.ranges.extend(file.ranges);
+            parsed.invalid_lines += file.invalid_lines;
+        }
+
+        let ranges = IpRanges::new(parsed.ranges);
+        debug!("ip filter loaded: {} ranges", ranges.len());
+
+        *self.ranges.write().unwrap() = ranges;
+        self.invalid_lines
+            .store(parsed.invalid_lines, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// Checks address before connection, blocked connections are counted.
+    pub(crate) fn is_blocked(&self, ip: &IpAddr) -> bool {
+        let blocked = self.ranges.read().unwrap().contains(ip);
+        if blocked {
+            self.blocked.fetch_add(1, Ordering::Relaxed);
+        }
+        blocked
+    }
+
+    pub(crate) fn view(&self) -> IpFilterView {
+        IpFilterView {
+            paths: self.paths.clone(),
+            ranges: self.ranges.read().unwrap().len(),
+            invalid_lines: self.invalid_lines.load(Ordering::Relaxed),
+            blocked: self.blocked.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Decodes filter list, gzip-compressed lists are detected by content.
+fn decode_filter_list(data: Vec<u8>) -> Result<String, RsbtError> {
+    if data.starts_with(GZIP_MAGIC) {
+        let mut decompressed = vec![];
+        GzDecoder::new(&data[..]).read_to_end(&mut decompressed)?;
+        return Ok(String::from_utf8_lossy(&decompressed).into_owned());
+    }
+
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    #[test]
+    fn decode_gzip_filter_list() {
+        let list = "Some organization:1.2.3.0-1.2.3.255\n";
+
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(list.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(decode_filter_list(compressed).unwrap(), list);
+        assert_eq!(decode_filter_list(list.into()).unwrap(), list);
+    }
+}
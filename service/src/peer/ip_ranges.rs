@@ -0,0 +1,85 @@
+use std::net::IpAddr;
+
+/// Sorted non-overlapping address ranges.
+///
+/// IPv4 addresses are stored as IPv4-mapped IPv6, so both families share one list.
+#[derive(Debug, Default)]
+pub(crate) struct IpRanges {
+    ranges: Vec<(u128, u128)>,
+}
+
+impl IpRanges {
+    /// Sorts ranges and merges overlapping or adjacent ones.
+    pub(crate) fn new(mut ranges: Vec<(u128, u128)>) -> Self {
+        ranges.sort_unstable();
+
+        let mut merged: Vec<(u128, u128)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1.saturating_add(1) => {
+                    last.1 = last.1.max(end);
+                }
+                _ => merged.push((start, end)),
+            }
+        }
+
+        Self { ranges: merged }
+    }
+
+    pub(crate) fn contains(&self, ip: &IpAddr) -> bool {
+        let ip = ip_to_u128(ip);
+        self.ranges
+            .binary_search_by(|&(start, end)| {
+                if end < ip {
+                    std::cmp::Ordering::Less
+                } else if start > ip {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.ranges.len()
+    }
+}
+
+pub(crate) fn ip_to_u128(ip: &IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(ip) => u128::from(ip.to_ipv6_mapped()),
+        IpAddr::V6(ip) => u128::from(*ip),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: &str, end: &str) -> (u128, u128) {
+        (
+            ip_to_u128(&start.parse().unwrap()),
+            ip_to_u128(&end.parse().unwrap()),
+        )
+    }
+
+    #[test]
+    fn merge_and_lookup() {
+        let ranges = IpRanges::new(vec![
+            range("10.0.0.0", "10.0.0.255"),
+            range("1.2.3.4", "1.2.3.4"),
+            range("10.0.1.0", "10.0.1.255"),
+            range("10.0.0.128", "10.0.0.200"),
+            range("2001:db8::", "2001:db8::ffff"),
+        ]);
+
+        assert_eq!(ranges.len(), 3);
+        assert!(ranges.contains(&"1.2.3.4".parse().unwrap()));
+        assert!(!ranges.contains(&"1.2.3.5".parse().unwrap()));
+        assert!(ranges.contains(&"10.0.1.17".parse().unwrap()));
+        assert!(ranges.contains(&"::ffff:10.0.0.1".parse().unwrap()));
+        assert!(ranges.contains(&"2001:db8::1".parse().unwrap()));
+        assert!(!ranges.contains(&"2001:db8::1:0".parse().unwrap()));
+    }
+}
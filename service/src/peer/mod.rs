@@ -1,17 +1,39 @@
+mod ban_list;
+mod candidates;
 mod connect_to_peer;
+mod connection_slots;
+mod ip_filter;
+mod ip_ranges;
+mod parse_ip_filter;
 mod peer_loop;
 mod peer_loop_message;
 mod peer_manager;
 mod peer_message;
 mod peer_state;
+mod piece_availability;
+mod piece_order;
 mod request_message;
+mod streaming_pieces;
+mod super_seed;
 mod torrent_peer_state;
+mod validate_message;
 
+pub(crate) use ban_list::BanList;
+pub(crate) use candidates::Candidates;
 pub(crate) use connect_to_peer::connect_to_peer;
+pub(crate) use connection_slots::{ConnectionSlot, ConnectionSlots};
+pub(crate) use ip_filter::IpFilter;
+pub(crate) use ip_ranges::{ip_to_u128, IpRanges};
+pub(crate) use parse_ip_filter::{parse_ip_filter, ParsedIpFilter};
 pub(crate) use peer_loop::peer_loop;
 pub(crate) use peer_loop_message::PeerLoopMessage;
 pub(crate) use peer_manager::PeerManager;
 pub(crate) use peer_message::PeerMessage;
 pub(crate) use peer_state::PeerState;
+pub(crate) use piece_availability::PieceAvailability;
+pub(crate) use piece_order::PieceOrder;
 pub(crate) use request_message::request_message;
+pub(crate) use streaming_pieces::StreamingPieces;
+pub(crate) use super_seed::SuperSeed;
 pub(crate) use torrent_peer_state::TorrentPeerState;
+pub(crate) use validate_message::validate_message;
@@ -0,0 +1,192 @@
+use crate::peer::ip_to_u128;
+use std::net::{IpAddr, Ipv4Addr};
+
+/// eMule access level, ranges with greater level are allowed.
+const EMULE_MAX_BLOCKED_LEVEL: u32 = 127;
+
+/// Parsed blocked ranges and count of lines which cannot be parsed.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ParsedIpFilter {
+    pub(crate) ranges: Vec<(u128, u128)>,
+    pub(crate) invalid_lines: usize,
+}
+
+/// Parses filter list, line format is detected for every line.
+///
+/// Supported formats are P2P plaintext `name:start-end`, eMule `ipfilter.dat`
+/// `start - end , level , name`, CIDR `address/prefix`, bare ranges and single addresses.
+/// Empty lines and comments starting with `#` or `//` are skipped.
+pub(crate) fn parse_ip_filter(data: &str) -> ParsedIpFilter {
+    let mut parsed = ParsedIpFilter::default();
+
+    for line in data.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+
+        match parse_line(line) {
+            Some(Some(range)) => parsed.ranges.push(range),
+            Some(None) => (),
+            None => parsed.invalid_lines += 1,
+        }
+    }
+
+    parsed
+}
+
+/// Returns `Some(None)` for valid lines which do not block anything.
+fn parse_line(line: &str) -> Option<Option<(u128, u128)>> {
+    if let Some(range) = parse_emule(line) {
+        return Some(range);
+    }
+
+    if let Some(range) = parse_cidr(line).or_else(|| parse_range(line)) {
+        return Some(Some(range));
+    }
+
+    parse_p2p(line).map(Some)
+}
+
+/// Name may contain colons and IPv6 range too, first split with valid range wins.
+fn parse_p2p(line: &str) -> Option<(u128, u128)> {
+    line.match_indices(':')
+        .find_map(|(position, _)| parse_range(&line[position + 1..]))
+}
+
+fn parse_emule(line: &str) -> Option<Option<(u128, u128)>> {
+    let mut parts = line.splitn(3, ',');
+    let range = parts.next()?;
+    let level = parts.next()?.trim().parse::<u32>().ok()?;
+    let range = parse_range(range)?;
+
+    Some(if level <= EMULE_MAX_BLOCKED_LEVEL {
+        Some(range)
+    } else {
+        None
+    })
+}
+
+fn parse_cidr(line: &str) -> Option<(u128, u128)> {
+    let mut parts = line.splitn(2, '/');
+    let ip = parse_ip(parts.next()?)?;
+    let prefix = parts.next()?.trim().parse::<u32>().ok()?;
+
+    let prefix = match ip {
+        IpAddr::V4(_) if prefix <= 32 => prefix + 96,
+        IpAddr::V6(_) if prefix <= 128 => prefix,
+        _ => return None,
+    };
+
+    let host_mask = u128::MAX.checked_shr(prefix).unwrap_or_default();
+    let start = ip_to_u128(&ip) & !host_mask;
+
+    Some((start, start | host_mask))
+}
+
+fn parse_range(range: &str) -> Option<(u128, u128)> {
+    let mut parts = range.splitn(2, '-');
+    let start = parse_ip(parts.next()?)?;
+    let end = parts.next().map(parse_ip).unwrap_or(Some(start))?;
+
+    if start.is_ipv4() != end.is_ipv4() {
+        return None;
+    }
+
+    let (start, end) = (ip_to_u128(&start), ip_to_u128(&end));
+    if start > end {
+        return None;
+    }
+
+    Some((start, end))
+}
+
+/// Parses address, IPv4 octets with leading zeros (`001.002.003.004`) are accepted.
+fn parse_ip(ip: &str) -> Option<IpAddr> {
+    let ip = ip.trim();
+    if let Ok(ip) = ip.parse() {
+        return Some(ip);
+    }
+
+    let octets = ip
+        .split('.')
+        .map(|x| x.parse::<u8>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if octets.len() != 4 {
+        return None;
+    }
+
+    Some(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: &str, end: &str) -> (u128, u128) {
+        (
+            ip_to_u128(&start.parse().unwrap()),
+            ip_to_u128(&end.parse().unwrap()),
+        )
+    }
+
+    #[test]
+    fn parse_p2p() {
+        let parsed = parse_ip_filter(
+            "# comment\n\
+             Some organization:1.2.3.0-1.2.3.255\n\
+             Name: with colon, and comma:5.6.7.8-5.6.7.9\n\
+             IPv6 organization:2001:db8::-2001:db8::ffff\n\
+             \n\
+             broken line\n",
+        );
+        assert_eq!(
+            parsed,
+            ParsedIpFilter {
+                ranges: vec![
+                    range("1.2.3.0", "1.2.3.255"),
+                    range("5.6.7.8", "5.6.7.9"),
+                    range("2001:db8::", "2001:db8::ffff"),
+                ],
+                invalid_lines: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_emule() {
+        let parsed = parse_ip_filter(
+            "001.009.096.105 - 001.009.096.105 , 000 , Some organization\r\n\
+             002.000.000.000 - 002.000.000.255 , 200 , Allowed\r\n\
+             // comment\r\n",
+        );
+        assert_eq!(
+            parsed,
+            ParsedIpFilter {
+                ranges: vec![range("1.9.96.105", "1.9.96.105")],
+                invalid_lines: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_cidr_and_addresses() {
+        let parsed = parse_ip_filter(
+            "10.0.0.0/8\n\
+             192.168.1.1\n\
+             2001:db8::/32\n\
+             10.0.0.0/33\n\
+             ::1-1.2.3.4\n",
+        );
+        assert_eq!(
+            parsed,
+            ParsedIpFilter {
+                ranges: vec![
+                    range("10.0.0.0", "10.255.255.255"),
+                    range("192.168.1.1", "192.168.1.1"),
+                    range("2001:db8::", "2001:db8:ffff:ffff:ffff:ffff:ffff:ffff"),
+                ],
+                invalid_lines: 2,
+            }
+        );
+    }
+}
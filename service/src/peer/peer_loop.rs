@@ -1,17 +1,21 @@
 use crate::{
     event::{TorrentEvent, TorrentStatisticMessage},
     event_loop::EventLoopSender,
+    mse::MseStream,
     peer::{request_message, PeerLoopMessage, PeerMessage},
     process::TorrentToken,
-    types::{Message, MessageCodec},
-    RsbtError,
+    types::{Message, MessageCodec, EXTENDED_HANDSHAKE_ID},
+    RsbtError, KEEP_ALIVE_INTERVAL,
 };
 use futures::{future::try_join, prelude::*, StreamExt};
 use log::{debug, error};
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::{
-    net::TcpStream,
     sync::mpsc::{Receiver, Sender},
+    time::timeout,
 };
 use tokio_util::codec::Framed;
 use uuid::Uuid;
@@ -21,8 +25,9 @@ pub(crate) async fn peer_loop(
     peer_id: Uuid,
     mut sender: Sender<PeerMessage>,
     mut receiver: Receiver<PeerMessage>,
-    stream: TcpStream,
+    stream: MseStream,
     statistic_sender: EventLoopSender<TorrentStatisticMessage, TorrentEvent>,
+    idle_timeout: Duration,
 ) -> Result<(), RsbtError> {
     let (wtransport, mut rtransport) = Framed::new(stream, MessageCodec).split();
 
@@ -30,12 +35,15 @@ pub(crate) async fn peer_loop(
 
     let command_loop_broker_sender = broker_sender.clone();
 
+    let receive_torrent_process = torrent_process.clone();
+
     let command_loop = async move {
         let mut processor = PeerLoopMessage {
             peer_id,
             command_loop_broker_sender,
             torrent_process: torrent_process.clone(),
             chocked: true,
+            choking: true,
             interested: false,
             message_count: 0,
             downloading: None,
@@ -44,21 +52,47 @@ pub(crate) async fn peer_loop(
             wtransport,
             request: None,
             statistic_sender,
+            last_sent: Instant::now(),
         };
 
-        while let Some(message) = receiver.next().await {
+        loop {
+            let keep_alive_in = KEEP_ALIVE_INTERVAL
+                .checked_sub(processor.last_sent.elapsed())
+                .unwrap_or_default();
+            let message = match timeout(keep_alive_in, receiver.next()).await {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(_) => {
+                    debug!("[{}] send keep alive to peer", peer_id);
+                    processor.send(Message::KeepAlive).await?;
+                    continue;
+                }
+            };
             debug!("[{}] sending to connected peer: {}", peer_id, message);
             match message {
                 PeerMessage::Bitfield(pieces) => {
-                    processor.wtransport.send(Message::Bitfield(pieces)).await?;
+                    processor.send(Message::Bitfield(pieces)).await?;
                 }
-                PeerMessage::Have(piece) => {
-                    let piece_index = piece as u32;
+                PeerMessage::ExtendedHandshake(payload) => {
                     processor
-                        .wtransport
-                        .send(Message::Have { piece_index })
+                        .send(Message::Extended {
+                            id: EXTENDED_HANDSHAKE_ID,
+                            payload,
+                        })
                         .await?;
                 }
+                PeerMessage::Choke => {
+                    processor.choking = true;
+                    processor.send(Message::Choke).await?;
+                }
+                PeerMessage::Unchoke => {
+                    processor.choking = false;
+                    processor.send(Message::Unchoke).await?;
+                }
+                PeerMessage::Have(piece) => {
+                    let piece_index = piece as u32;
+                    processor.send(Message::Have { piece_index }).await?;
+                }
                 PeerMessage::Piece {
                     index,
                     begin,
@@ -70,7 +104,6 @@ pub(crate) async fn peer_loop(
                         peer_id, index, begin, block_len
                     );
                     processor
-                        .wtransport
                         .send(Message::Piece {
                             index,
                             begin,
@@ -87,17 +120,16 @@ pub(crate) async fn peer_loop(
                 }
                 PeerMessage::Cancel => {
                     debug!("[{}] cancel download", peer_id);
-                    if let Some((index, begin, length)) = processor.request {
+                    if let Some((index, begin, length)) = processor.request.take() {
                         processor
-                            .wtransport
                             .send(Message::Cancel {
                                 index,
                                 begin,
                                 length,
                             })
                             .await?;
-                        processor.request = None;
-                        processor.downloading = None;
+                    }
+                    if processor.downloading.take().is_some() {
                         processor.torrent_piece = None;
                         processor
                             .command_loop_broker_sender
@@ -113,13 +145,12 @@ pub(crate) async fn peer_loop(
 
                     if processor.chocked {
                         debug!("[{}] send interested message", peer_id);
-                        processor.wtransport.send(Message::Interested).await?;
+                        processor.send(Message::Interested).await?;
                     } else if let Some(ref torrent_peer_piece) = processor.torrent_piece {
                         let (index, begin, length) =
                             request_message(torrent_peer_piece, piece, processor.piece_length);
                         processor.request = Some((index, begin, length));
                         processor
-                            .wtransport
                             .send(Message::Request {
                                 index,
                                 begin,
@@ -145,7 +176,20 @@ pub(crate) async fn peer_loop(
     };
 
     let receive_loop = async move {
-        while let Some(Ok(message)) = rtransport.next().await {
+        loop {
+            let message = match timeout(idle_timeout, rtransport.next()).await {
+                Ok(Some(Ok(message))) => message,
+                Ok(Some(Err(err))) => {
+                    error!("[{}] invalid message from peer: {}", peer_id, err);
+                    break;
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    debug!("[{}] peer is idle for {:?}", peer_id, idle_timeout);
+                    break;
+                }
+            };
+            receive_torrent_process.bandwidth.download(&message).await;
             sender.send(PeerMessage::Message(message)).await?;
         }
 
@@ -173,3 +217,103 @@ pub(crate) async fn peer_loop(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_CHANNEL_BUFFER;
+    use tokio::{
+        net::{TcpListener, TcpStream},
+        sync::mpsc,
+    };
+
+    #[tokio::test]
+    async fn idle_peer_is_disconnected() {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stream, remote) = try_join(TcpStream::connect(addr), listener.accept())
+            .await
+            .unwrap();
+
+        let (broker_sender, mut broker_receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        let (statistic_sender, _statistic_receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        let (feedback_sender, _feedback_receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        let (sender, receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        let peer_id = Uuid::new_v4();
+        let peer = tokio::spawn(peer_loop(
+            TorrentToken::sample(broker_sender),
+            peer_id,
+            sender,
+            receiver,
+            MseStream::plaintext(stream),
+            EventLoopSender::new(statistic_sender, feedback_sender),
+            Duration::from_millis(50),
+        ));
+
+        // remote peer is connected but silent
+        let event = timeout(Duration::from_secs(5), broker_receiver.recv())
+            .await
+            .unwrap();
+        assert!(matches!(event, Some(TorrentEvent::PeerDisconnect(id)) if id == peer_id));
+        peer.await.unwrap().unwrap();
+        drop(remote);
+    }
+
+    #[tokio::test]
+    async fn requests_are_dropped_while_choking() {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stream, (remote, _)) = try_join(TcpStream::connect(addr), listener.accept())
+            .await
+            .unwrap();
+        let mut remote = Framed::new(remote, MessageCodec);
+
+        let (broker_sender, mut broker_receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        let (statistic_sender, _statistic_receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        let (feedback_sender, _feedback_receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        let (mut sender, receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        let peer_id = Uuid::new_v4();
+        let peer = tokio::spawn(peer_loop(
+            TorrentToken::sample(broker_sender),
+            peer_id,
+            sender.clone(),
+            receiver,
+            MseStream::plaintext(stream),
+            EventLoopSender::new(statistic_sender, feedback_sender),
+            Duration::from_secs(60),
+        ));
+
+        let request = |index| Message::Request {
+            index,
+            begin: 0,
+            length: 16,
+        };
+        // have follows request, so its event shows that request was processed
+        remote.send(Message::Interested).await.unwrap();
+        remote.send(request(0)).await.unwrap();
+        remote.send(Message::Have { piece_index: 0 }).await.unwrap();
+        let event = timeout(Duration::from_secs(5), broker_receiver.recv())
+            .await
+            .unwrap();
+        assert!(matches!(event, Some(TorrentEvent::PeerInterested(id)) if id == peer_id));
+        let event = timeout(Duration::from_secs(5), broker_receiver.recv())
+            .await
+            .unwrap();
+        assert!(matches!(event, Some(TorrentEvent::PeerPiece(id, 0)) if id == peer_id));
+
+        sender.send(PeerMessage::Unchoke).await.unwrap();
+        assert!(matches!(remote.next().await, Some(Ok(Message::Unchoke))));
+        remote.send(request(1)).await.unwrap();
+        let event = timeout(Duration::from_secs(5), broker_receiver.recv())
+            .await
+            .unwrap();
+        assert!(matches!(
+            event,
+            Some(TorrentEvent::PeerPieceRequest { peer_id: id, index: 1, .. }) if id == peer_id
+        ));
+
+        drop(remote);
+        drop(sender);
+        let _ = peer.await.unwrap();
+    }
+}
@@ -1,16 +1,21 @@
 use crate::{
     event::{TorrentEvent, TorrentStatisticMessage},
     event_loop::EventLoopSender,
-    peer::request_message,
+    mse::MseStream,
+    peer::{request_message, validate_message},
     process::TorrentToken,
-    types::{info::PieceChecksum, Message, MessageCodec},
+    types::{info::PieceChecksum, ExtendedHandshake, Message, MessageCodec, EXTENDED_HANDSHAKE_ID},
     RsbtError,
 };
 use futures::{prelude::*, stream::SplitSink};
 use log::{debug, error};
 use sha1::{Digest, Sha1};
-use std::{convert::TryInto, sync::Arc};
-use tokio::{net::TcpStream, sync::mpsc::Sender};
+use std::{
+    convert::{TryFrom, TryInto},
+    sync::Arc,
+    time::Instant,
+};
+use tokio::sync::mpsc::Sender;
 use tokio_util::codec::Framed;
 use uuid::Uuid;
 
@@ -18,26 +23,33 @@ pub(crate) struct PeerLoopMessage {
     pub(crate) torrent_process: Arc<TorrentToken>,
     pub(crate) message_count: usize,
     pub(crate) chocked: bool,
+    /// We are choking peer, its requests are dropped.
+    pub(crate) choking: bool,
     pub(crate) interested: bool,
     pub(crate) peer_id: Uuid,
     pub(crate) command_loop_broker_sender: Sender<TorrentEvent>,
     pub(crate) downloading: Option<usize>,
     pub(crate) torrent_piece: Option<Vec<u8>>,
     pub(crate) piece_length: usize,
-    pub(crate) wtransport: SplitSink<Framed<TcpStream, MessageCodec>, Message>,
+    pub(crate) wtransport: SplitSink<Framed<MseStream, MessageCodec>, Message>,
     pub(crate) request: Option<(u32, u32, u32)>,
     pub(crate) statistic_sender: EventLoopSender<TorrentStatisticMessage, TorrentEvent>,
+    pub(crate) last_sent: Instant,
 }
 
 impl PeerLoopMessage {
+    /// Sends message to peer within upload limits.
+    pub(crate) async fn send(&mut self, message: Message) -> Result<(), RsbtError> {
+        self.torrent_process.bandwidth.upload(&message).await;
+        self.wtransport.send(message).await?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
     pub(crate) async fn bitfield(&mut self, pieces: Vec<u8>) -> Result<bool, RsbtError> {
         let peer_id = self.peer_id;
         if self.message_count != 1 {
-            error!(
-                "[{}] wrong message sequence for peer: bitfield message must be first message",
-                peer_id
-            );
-            return Ok(true);
+            return Err(RsbtError::PeerBitfieldOutOfOrder);
         }
         self.command_loop_broker_sender
             .send(TorrentEvent::PeerPieces(peer_id, pieces))
@@ -56,6 +68,18 @@ impl PeerLoopMessage {
         Ok(false)
     }
 
+    pub(crate) async fn choke(&mut self) -> Result<bool, RsbtError> {
+        self.chocked = true;
+
+        debug!("[{}] chocked", self.peer_id);
+
+        self.command_loop_broker_sender
+            .send(TorrentEvent::PeerChoke(self.peer_id))
+            .await?;
+
+        Ok(false)
+    }
+
     pub(crate) async fn unchoke(&mut self) -> Result<bool, RsbtError> {
         self.chocked = false;
 
@@ -77,13 +101,12 @@ impl PeerLoopMessage {
                 let (index, begin, length) =
                     request_message(torrent_peer_piece, piece, self.piece_length);
                 self.request = Some((index, begin, length));
-                self.wtransport
-                    .send(Message::Request {
-                        index,
-                        begin,
-                        length,
-                    })
-                    .await?;
+                self.send(Message::Request {
+                    index,
+                    begin,
+                    length,
+                })
+                .await?;
             }
         }
 
@@ -122,21 +145,25 @@ impl PeerLoopMessage {
                     return Ok(false);
                 }
 
+                let block_length = block.len();
                 torrent_peer_piece.extend(block);
 
+                self.command_loop_broker_sender
+                    .send(TorrentEvent::PeerPieceProgress(peer_id, block_length))
+                    .await?;
+
                 use std::cmp::Ordering;
                 match self.piece_length.cmp(&torrent_peer_piece.len()) {
                     Ordering::Greater => {
                         let (index, begin, length) =
                             request_message(torrent_peer_piece, piece, self.piece_length);
                         self.request = Some((index, begin, length));
-                        self.wtransport
-                            .send(Message::Request {
-                                index,
-                                begin,
-                                length,
-                            })
-                            .await?;
+                        self.send(Message::Request {
+                            index,
+                            begin,
+                            length,
+                        })
+                        .await?;
                     }
                     Ordering::Equal => {
                         let control_piece = &self.torrent_process.info.pieces[piece];
@@ -144,7 +171,25 @@ impl PeerLoopMessage {
                         let sha1: PieceChecksum =
                             Sha1::digest(torrent_peer_piece.as_slice())[..].try_into()?;
                         if sha1 != *control_piece {
-                            error!("[{}] piece sha1 failure", peer_id);
+                            error!("[{}] piece {} sha1 failure", peer_id, piece);
+
+                            self.downloading = None;
+                            self.torrent_piece = None;
+                            self.request = None;
+
+                            if let Err(err) = self
+                                .statistic_sender
+                                .send(TorrentStatisticMessage::HashFailed)
+                                .await
+                            {
+                                error!("cannot send hash failed statistics: {}", err);
+                            }
+
+                            self.command_loop_broker_sender
+                                .send(TorrentEvent::PeerPieceHashFailed(peer_id, piece))
+                                .await?;
+
+                            return Ok(false);
                         }
 
                         self.downloading = None;
@@ -182,8 +227,16 @@ impl PeerLoopMessage {
         let peer_id = self.peer_id;
 
         if !self.interested {
-            error!("[{}] peer requested data without unchoke", peer_id);
-            return Ok(true);
+            return Err(RsbtError::PeerRequestNotInterested);
+        }
+
+        // requests may cross our choke on the wire, so they are dropped without disconnect
+        if self.choking {
+            debug!(
+                "[{}] drop request {} {} [{}] while choking",
+                peer_id, index, begin, length
+            );
+            return Ok(false);
         }
 
         self.command_loop_broker_sender
@@ -207,16 +260,55 @@ impl PeerLoopMessage {
         Ok(false)
     }
 
+    pub(crate) async fn not_interested(&mut self) -> Result<bool, RsbtError> {
+        self.interested = false;
+        self.command_loop_broker_sender
+            .send(TorrentEvent::PeerNotInterested(self.peer_id))
+            .await?;
+
+        Ok(false)
+    }
+
+    /// Only extended handshake is processed, it may contain client name and version.
+    pub(crate) async fn extended(&mut self, id: u8, payload: Vec<u8>) -> Result<bool, RsbtError> {
+        let peer_id = self.peer_id;
+
+        if id != EXTENDED_HANDSHAKE_ID {
+            debug!("[{}] unsupported extended message {}", peer_id, id);
+            return Ok(false);
+        }
+
+        match ExtendedHandshake::try_from(payload) {
+            Ok(ExtendedHandshake {
+                client: Some(client),
+                ..
+            }) => {
+                self.command_loop_broker_sender
+                    .send(TorrentEvent::PeerClient(peer_id, client))
+                    .await?;
+            }
+            Ok(_) => (),
+            Err(err) => debug!("[{}] cannot parse extended handshake: {}", peer_id, err),
+        }
+
+        Ok(false)
+    }
+
+    /// Keep alive from peer only resets idle timeout, our keep alives are sent by timer.
     pub(crate) async fn keep_alive(&mut self) -> Result<bool, RsbtError> {
-        debug!("[{}] send keep alive to peer", self.peer_id);
-        self.wtransport.send(Message::KeepAlive).await?;
+        debug!("[{}] keep alive from peer", self.peer_id);
         Ok(false)
     }
 
     /// Message from peer to us
+    ///
+    /// Protocol violations are returned as errors, so peer is disconnected.
     pub(crate) async fn peer_loop_message(&mut self, message: Message) -> Result<bool, RsbtError> {
         let peer_id = self.peer_id;
-        self.message_count += 1;
+        validate_message(&self.torrent_process.info, &message)?;
+        if !matches!(message, Message::KeepAlive | Message::Extended { .. }) {
+            self.message_count += 1;
+        }
         match message {
             Message::Bitfield(pieces) => {
                 return self.bitfield(pieces).await;
@@ -224,12 +316,21 @@ impl PeerLoopMessage {
             Message::Have { piece_index } => {
                 return self.have(piece_index as usize).await;
             }
+            Message::Choke => {
+                return self.choke().await;
+            }
             Message::Unchoke => {
                 return self.unchoke().await;
             }
             Message::Interested => {
                 return self.interested().await;
             }
+            Message::NotInterested => {
+                return self.not_interested().await;
+            }
+            Message::Extended { id, payload } => {
+                return self.extended(id, payload).await;
+            }
             Message::Piece {
                 index,
                 begin,
@@ -1,32 +1,47 @@
 use crate::{
     announce::{AnnounceManager, AnnounceManagerMessage},
     event::{TorrentDownloadMode, TorrentEvent, TorrentEventQueryPiece, TorrentStatisticMessage},
-    event_loop::EventLoop,
+    event_loop::{EventLoop, EventLoopCommand},
     file_download::FileDownloadStream,
-    peer::{connect_to_peer, peer_loop, PeerMessage, PeerState, TorrentPeerState},
+    mse::MseStream,
+    peer::{
+        connect_to_peer, peer_loop, BanList, Candidates, ConnectionSlots, IpFilter, PeerMessage,
+        PeerState, PieceAvailability, PieceOrder, StreamingPieces, SuperSeed, TorrentPeerState,
+    },
     piece::{collect_pieces_and_update, match_pieces},
     process::TorrentToken,
     request_response::RequestResponse,
     result::RsbtResult,
     spawn_and_log_error,
     statistics::StatisticsManager,
-    storage::TorrentStorage,
+    storage::{TorrentStorage, TorrentStorageState},
     types::{
-        public::{AnnounceView, FileView, PeerView, TorrentDownloadState},
-        Peer, Properties,
+        peer_client,
+        public::{AnnounceView, FilePriority, FileView, PeerView, TorrentDownloadState},
+        EncryptionMode, ExtendedHandshake, Handshake, Peer, Properties, ProxyConfig,
     },
-    DEFAULT_CHANNEL_BUFFER,
+    RsbtError, DEFAULT_CHANNEL_BUFFER, PEER_MAX_CONNECT_FAILS, PEER_MAX_HASH_FAILS,
 };
-use flat_storage::{bit_by_index, index_in_bitarray};
-use log::{debug, error};
+use flat_storage::{bit_by_index, index_in_bitarray, FlatStorageFile};
+use log::{debug, error, warn};
 use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
-use std::{collections::HashMap, ops::Range, sync::Arc, time::Instant};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    ops::Range,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::{
-    net::TcpStream,
     sync::{mpsc, watch},
+    time::delay_for,
 };
 use uuid::Uuid;
 
+const CHECK_PEERS_INTERVAL: Duration = Duration::from_secs(10);
+
 pub(crate) struct PeerManager {
     announce_manager: EventLoop<AnnounceManagerMessage, AnnounceManager, TorrentEvent>,
     statistics_manager: EventLoop<TorrentStatisticMessage, StatisticsManager, TorrentEvent>,
@@ -37,6 +52,32 @@ pub(crate) struct PeerManager {
     active: bool,
     awaiting_for_piece:
         HashMap<usize, Vec<RequestResponse<TorrentEventQueryPiece, RsbtResult<Vec<u8>>>>>,
+    hash_fails: HashMap<IpAddr, usize>,
+    banned: HashSet<IpAddr>,
+    ban_list: Arc<BanList>,
+    ip_filter: Arc<IpFilter>,
+    connection_slots: Arc<ConnectionSlots>,
+    max_connections: usize,
+    candidates: Candidates,
+    connect_fails: HashMap<SocketAddr, usize>,
+    self_addrs: HashSet<SocketAddr>,
+    idle_timeout: Duration,
+    snub_timeout: Duration,
+    check_peers: Option<EventLoopCommand>,
+    /// Reports end of data verification, pieces are not requested while checking.
+    storage_ready: EventLoopCommand,
+    /// Proxy for outgoing peer connections.
+    proxy: Option<ProxyConfig>,
+    encryption: EncryptionMode,
+    /// Our extended handshake payload, sent to peers supporting extension protocol.
+    extended_handshake: Vec<u8>,
+    /// Super-seeding state, real bitfield is withheld while it is set.
+    super_seed: Option<SuperSeed>,
+    availability: PieceAvailability,
+    piece_order: PieceOrder,
+    /// Pieces awaited and read ahead by file download streams, they are downloaded first.
+    streaming_pieces: StreamingPieces,
+    stream_read_ahead: usize,
 }
 
 impl PeerManager {
@@ -44,7 +85,23 @@ impl PeerManager {
         properties: Arc<Properties>,
         torrent_storage: TorrentStorage,
         torrent_process: Arc<TorrentToken>,
+        connection_slots: Arc<ConnectionSlots>,
+        ban_list: Arc<BanList>,
+        ip_filter: Arc<IpFilter>,
     ) -> RsbtResult<Self> {
+        let max_connections = properties.max_connections_per_torrent;
+        let availability = PieceAvailability::new(torrent_process.info.pieces.len());
+
+        let extended_handshake = ExtendedHandshake {
+            client: if properties.anonymous {
+                None
+            } else {
+                Some(format!("rsbt {}", env!("CARGO_PKG_VERSION")))
+            },
+            port: Some(properties.port),
+        }
+        .encode();
+
         let announce_manager = EventLoop::spawn(
             AnnounceManager::new(properties.clone(), torrent_process.clone()),
             torrent_process.broker_sender.clone(),
@@ -55,6 +112,11 @@ impl PeerManager {
             torrent_process.broker_sender.clone(),
         )?;
 
+        let storage_ready = Self::watch_storage_ready(
+            torrent_storage.receiver.clone(),
+            torrent_process.broker_sender.clone(),
+        );
+
         let peer_manager = PeerManager {
             announce_manager,
             statistics_manager,
@@ -64,11 +126,60 @@ impl PeerManager {
             mode: TorrentDownloadMode::Normal,
             active: false,
             awaiting_for_piece: HashMap::new(),
+            hash_fails: HashMap::new(),
+            banned: HashSet::new(),
+            ban_list,
+            ip_filter,
+            connection_slots,
+            max_connections,
+            candidates: Candidates::default(),
+            connect_fails: HashMap::new(),
+            self_addrs: HashSet::new(),
+            idle_timeout: properties.peer_idle_timeout,
+            snub_timeout: properties.peer_snub_timeout,
+            check_peers: None,
+            storage_ready,
+            proxy: properties.proxy.clone().filter(|x| x.peers),
+            encryption: properties.encryption,
+            extended_handshake,
+            super_seed: None,
+            availability,
+            piece_order: PieceOrder::default(),
+            streaming_pieces: StreamingPieces::default(),
+            stream_read_ahead: properties.stream_read_ahead,
         };
 
         Ok(peer_manager)
     }
 
+    /// Sends `StorageReady` when storage state changes from checked or moved data to ready.
+    fn watch_storage_ready(
+        mut receiver: watch::Receiver<TorrentStorageState>,
+        mut broker_sender: mpsc::Sender<TorrentEvent>,
+    ) -> EventLoopCommand {
+        let busy = |state: &TorrentStorageState| state.checking.is_some() || state.moving.is_some();
+        // state at spawn time, check may end before task is polled
+        let mut was_busy = busy(&receiver.borrow());
+        EventLoopCommand::spawn(async move {
+            while let Some(state) = receiver.recv().await {
+                let is_busy = busy(&state);
+                if was_busy && !is_busy {
+                    if let Err(err) = broker_sender.send(TorrentEvent::StorageReady).await {
+                        error!("cannot send storage ready: {}", err);
+                        break;
+                    }
+                }
+                was_busy = is_busy;
+            }
+        })
+    }
+
+    /// Peers which sent pieces while data was verified or moved get pieces to download.
+    pub(crate) async fn storage_ready(&mut self) -> RsbtResult<()> {
+        debug!("storage ready, requesting pieces");
+        self.request_pieces().await
+    }
+
     pub(crate) async fn peers_announced(&mut self, peers: Vec<Peer>) {
         for peer in peers {
             debug!("peer announced: {:?}", peer);
@@ -76,23 +187,46 @@ impl PeerManager {
                 error!("cannot process peer announced {:?}: {}", peer, err);
             }
         }
+
+        self.connect_candidates();
     }
 
+    /// Announced peer is queued as candidate, candidates are connected with `connect_candidates`.
     pub(crate) async fn peer_announced(&mut self, peer: Peer) -> RsbtResult<()> {
-        let torrent_process = self.torrent_process.clone();
-        let mut peer_states_iter = self.peer_states.iter_mut();
-        let peer_err = peer.clone();
-        if let Some((peer_id, existing_peer)) = peer_states_iter.find(|x| x.1.peer == peer) {
-            let peer_id = *peer_id;
+        if self.is_banned(&peer.ip) {
+            debug!("skip banned peer {:?}", peer);
+            return Ok(());
+        }
+
+        if self
+            .self_addrs
+            .contains(&SocketAddr::new(peer.ip, peer.port))
+        {
+            debug!("skip own address {:?}", peer);
+            return Ok(());
+        }
+
+        let can_connect = self.connections_count() < self.max_connections;
+
+        if let Some((&peer_id, existing_peer)) =
+            self.peer_states.iter_mut().find(|x| x.1.peer == peer)
+        {
             match existing_peer.state {
                 TorrentPeerState::Idle => {
-                    let handler = spawn_and_log_error(
-                        connect_to_peer(torrent_process, peer_id, peer),
-                        move || {
-                            format!("connect to existing peer {} {:?} failed", peer_id, peer_err)
-                        },
-                    );
-                    existing_peer.state = TorrentPeerState::Connecting(handler);
+                    if self.ip_filter.is_blocked(&existing_peer.peer.ip) {
+                        debug!("[{}] skip peer blocked by ip filter", peer_id);
+                    } else if !can_connect {
+                        debug!("[{}] skip peer: torrent connection limit reached", peer_id);
+                    } else if let Some(slot) = self.connection_slots.connecting() {
+                        existing_peer.state = Self::connect_peer(
+                            self.torrent_process.clone(),
+                            peer_id,
+                            existing_peer.peer.clone(),
+                            self.proxy.clone(),
+                            self.encryption,
+                        );
+                        existing_peer.slot = Some(slot);
+                    }
                 }
                 TorrentPeerState::Connected { .. } => {
                     existing_peer.announce_count += 1;
@@ -100,41 +234,148 @@ impl PeerManager {
                 _ => (),
             }
         } else {
+            self.candidates.push(peer);
+        }
+
+        Ok(())
+    }
+
+    /// Connects queued candidates while there are free connection slots.
+    ///
+    /// Peers without previous connection failures are preferred.
+    pub(crate) fn connect_candidates(&mut self) {
+        while !self.candidates.is_empty() && self.connections_count() < self.max_connections {
+            let slot = if let Some(slot) = self.connection_slots.connecting() {
+                slot
+            } else {
+                debug!(
+                    "no free connection slots, {} candidates left",
+                    self.candidates.len()
+                );
+                break;
+            };
+
+            let peer = if let Some(peer) = self.candidates.take(&self.connect_fails) {
+                peer
+            } else {
+                break;
+            };
+
+            if self.ip_filter.is_blocked(&peer.ip) {
+                debug!("skip peer {:?} blocked by ip filter", peer);
+                continue;
+            }
+
             let peer_id = Uuid::new_v4();
-            let torrent_process_on_failure = torrent_process.clone();
+            let hash_fails = self.peer_hash_fails(&peer);
             self.peer_states.insert(
                 peer_id,
                 PeerState {
                     peer: peer.clone(),
-                    state: TorrentPeerState::Connecting(tokio::spawn(async move {
-                        if let Err(err) = connect_to_peer(torrent_process, peer_id, peer).await {
-                            error!(
-                                "[{}] connect to new peer {:?} failed: {}",
-                                peer_id, peer_err, err
-                            );
-                            if let Err(err) = torrent_process_on_failure
-                                .broker_sender
-                                .clone()
-                                .send(TorrentEvent::PeerConnectFailed(peer_id))
-                                .await
-                            {
-                                error!("[{}] cannot send peer connect failed: {}", peer_id, err);
-                            }
-                        }
-                    })),
+                    state: Self::connect_peer(
+                        self.torrent_process.clone(),
+                        peer_id,
+                        peer,
+                        self.proxy.clone(),
+                        self.encryption,
+                    ),
                     announce_count: 0,
+                    hash_fails,
+                    slot: Some(slot),
                 },
             );
-        };
+        }
+    }
 
-        Ok(())
+    fn connect_peer(
+        torrent_process: Arc<TorrentToken>,
+        peer_id: Uuid,
+        peer: Peer,
+        proxy: Option<ProxyConfig>,
+        encryption: EncryptionMode,
+    ) -> TorrentPeerState {
+        let peer_err = peer.clone();
+        let torrent_process_on_failure = torrent_process.clone();
+        TorrentPeerState::Connecting(tokio::spawn(async move {
+            if let Err(err) =
+                connect_to_peer(torrent_process, peer_id, peer, proxy, encryption).await
+            {
+                error!(
+                    "[{}] connect to peer {:?} failed: {}",
+                    peer_id, peer_err, err
+                );
+                if let Err(err) = torrent_process_on_failure
+                    .broker_sender
+                    .clone()
+                    .send(TorrentEvent::PeerConnectFailed(peer_id))
+                    .await
+                {
+                    error!("[{}] cannot send peer connect failed: {}", peer_id, err);
+                }
+            }
+        }))
+    }
+
+    /// Count of connected and connecting peers.
+    fn connections_count(&self) -> usize {
+        self.peer_states
+            .values()
+            .filter(|x| !matches!(x.state, TorrentPeerState::Idle))
+            .count()
+    }
+
+    fn peer_hash_fails(&self, peer: &Peer) -> usize {
+        self.hash_fails.get(&peer.ip).cloned().unwrap_or_default()
     }
 
     pub(crate) fn peer_remove_by_id(&mut self, id: Uuid) -> Option<PeerState> {
-        self.peer_states.remove(&id)
+        if let Some(super_seed) = self.super_seed.as_mut() {
+            super_seed.remove(&id);
+        }
+        let peer_state = self.peer_states.remove(&id);
+        if let Some(PeerState {
+            state: TorrentPeerState::Connected { pieces, .. },
+            ..
+        }) = &peer_state
+        {
+            self.availability.remove_pieces(pieces);
+        }
+        peer_state
+    }
+
+    /// Failures are counted by address, on overflow only queued candidates keep their counts.
+    pub(crate) fn peer_connect_failed(&mut self, id: Uuid) -> Option<PeerState> {
+        let peer_state = self.peer_remove_by_id(id);
+        if let Some(peer_state) = &peer_state {
+            let peer = &peer_state.peer;
+            let addr = SocketAddr::new(peer.ip, peer.port);
+            if self.connect_fails.len() >= PEER_MAX_CONNECT_FAILS
+                && !self.connect_fails.contains_key(&addr)
+            {
+                let candidates = &self.candidates;
+                self.connect_fails
+                    .retain(|addr, _| candidates.contains(addr));
+            }
+            *self.connect_fails.entry(addr).or_default() += 1;
+        }
+        peer_state
+    }
+
+    /// Peer address is our own listener, it is never connected again.
+    pub(crate) fn peer_self_connection(&mut self, id: Uuid) -> Option<PeerState> {
+        let peer_state = self.peer_remove_by_id(id);
+        if let Some(peer_state) = &peer_state {
+            let peer = &peer_state.peer;
+            self.self_addrs.insert(SocketAddr::new(peer.ip, peer.port));
+        }
+        peer_state
     }
 
-    pub(crate) async fn peer_forwarded(&mut self, stream: TcpStream) -> RsbtResult<()> {
+    pub(crate) async fn peer_forwarded(
+        &mut self,
+        stream: MseStream,
+        handshake: Handshake,
+    ) -> RsbtResult<()> {
         let peer_id = Uuid::new_v4();
         debug!("[{}] peer connection forwarded", peer_id);
 
@@ -142,32 +383,51 @@ impl PeerManager {
 
         let peer: Peer = peer_addr.into();
 
+        if self.is_banned(&peer.ip) {
+            debug!("[{}] drop connection from banned peer {:?}", peer_id, peer);
+            return Ok(());
+        }
+
+        if self.connections_count() >= self.max_connections {
+            debug!(
+                "[{}] drop connection from peer {:?}: torrent connection limit reached",
+                peer_id, peer
+            );
+            return Ok(());
+        }
+
+        let slot = if let Some(slot) = self.connection_slots.connected() {
+            slot
+        } else {
+            debug!(
+                "[{}] drop connection from peer {:?}: connection limit reached",
+                peer_id, peer
+            );
+            return Ok(());
+        };
+
+        let hash_fails = self.peer_hash_fails(&peer);
+
         let (mut sender, receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
 
         self.peer_states.insert(
             peer_id,
             PeerState {
                 peer: peer.clone(),
-                state: TorrentPeerState::Connected {
-                    chocked: true,
-                    interested: false,
-                    downloading_piece: None,
-                    downloading_since: None,
-                    downloaded: 0,
-                    uploaded: 0,
-                    pieces: vec![],
-                    sender: sender.clone(),
-                },
+                state: TorrentPeerState::connected(
+                    sender.clone(),
+                    peer_client(&handshake.peer_id),
+                    true,
+                    stream.is_encrypted(),
+                ),
                 announce_count: 0,
+                hash_fails,
+                slot: Some(slot),
             },
         );
 
-        {
-            let downloaded = self.torrent_storage.receiver.borrow().downloaded.clone();
-            if !downloaded.is_empty() {
-                sender.send(PeerMessage::Bitfield(downloaded)).await?;
-            }
-        }
+        self.greet_peer(&mut sender, &handshake).await?;
+        self.super_seed_offer(peer_id).await?;
 
         let _ = spawn_and_log_error(
             peer_loop(
@@ -177,6 +437,7 @@ impl PeerManager {
                 receiver,
                 stream,
                 self.statistics_manager.loop_sender().clone(),
+                self.idle_timeout,
             ),
             move || format!("[{}] peer loop failed", peer_id),
         );
@@ -187,13 +448,29 @@ impl PeerManager {
     pub(crate) async fn peer_connected(
         &mut self,
         peer_id: Uuid,
-        stream: TcpStream,
+        stream: MseStream,
+        handshake: Handshake,
     ) -> RsbtResult<()> {
         debug!("[{}] peer connected to {:?}", peer_id, stream.peer_addr());
         debug!("[{}] peer connection initiated", peer_id);
 
+        if let Some(ip) = self.peer_states.get(&peer_id).map(|x| x.peer.ip) {
+            if self.is_banned(&ip) {
+                debug!("[{}] drop connection to banned peer {}", peer_id, ip);
+                self.peer_remove_by_id(peer_id);
+                return Ok(());
+            }
+        }
+
         if let Some(existing_peer) = self.peer_states.get_mut(&peer_id) {
-            let (sender, receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+            let (mut sender, receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+            let encrypted = stream.is_encrypted();
+
+            // failures are forgotten, address is preferred again when reconnected
+            self.connect_fails.remove(&SocketAddr::new(
+                existing_peer.peer.ip,
+                existing_peer.peer.port,
+            ));
 
             let _ = spawn_and_log_error(
                 peer_loop(
@@ -203,32 +480,96 @@ impl PeerManager {
                     receiver,
                     stream,
                     self.statistics_manager.loop_sender().clone(),
+                    self.idle_timeout,
                 ),
                 move || format!("[{}] existing peer loop failed", peer_id),
             );
 
-            existing_peer.state = TorrentPeerState::Connected {
-                chocked: true,
-                interested: false,
-                downloading_piece: None,
-                downloading_since: None,
-                downloaded: 0,
-                uploaded: 0,
-                pieces: vec![],
-                sender,
-            };
+            if let Some(slot) = existing_peer.slot.as_mut() {
+                slot.established();
+            }
+
+            existing_peer.state = TorrentPeerState::connected(
+                sender.clone(),
+                peer_client(&handshake.peer_id),
+                false,
+                encrypted,
+            );
+
+            self.greet_peer(&mut sender, &handshake).await?;
+            self.super_seed_offer(peer_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends our pieces and extended handshake to newly connected peer.
+    async fn greet_peer(
+        &self,
+        sender: &mut mpsc::Sender<PeerMessage>,
+        handshake: &Handshake,
+    ) -> RsbtResult<()> {
+        let downloaded = self.torrent_storage.receiver.borrow().downloaded.clone();
+        if !downloaded.is_empty() && self.super_seed.is_none() {
+            sender.send(PeerMessage::Bitfield(downloaded)).await?;
+        }
+
+        if handshake.supports_extensions() {
+            sender
+                .send(PeerMessage::ExtendedHandshake(
+                    self.extended_handshake.clone(),
+                ))
+                .await?;
         }
 
         Ok(())
     }
 
+    /// Client name from extended handshake, it is more precise than guess from peer id.
+    pub(crate) fn peer_client(&mut self, peer_id: Uuid, name: String) {
+        if let Some(TorrentPeerState::Connected { ref mut client, .. }) =
+            self.peer_states.get_mut(&peer_id).map(|x| &mut x.state)
+        {
+            *client = Some(name);
+        }
+    }
+
     pub(crate) async fn select_new_peer(
         &mut self,
         new_pieces: &[usize],
         peer_id: Uuid,
     ) -> RsbtResult<()> {
-        for &new_piece in new_pieces {
-            if let TorrentDownloadMode::Normal = self.mode {
+        let new_pieces = {
+            let state = self.torrent_storage.receiver.borrow();
+            if state.checking.is_some() || state.moving.is_some() {
+                return Ok(());
+            }
+            let priority = |x: usize| state.piece_priorities.get(x).cloned().unwrap_or_default();
+            let streaming_pieces = &self.streaming_pieces;
+            // streamed pieces are downloaded even from skipped files
+            let mut new_pieces: Vec<usize> = new_pieces
+                .iter()
+                .cloned()
+                .filter(|&x| {
+                    priority(x) != FilePriority::Skip || streaming_pieces.rank(x).is_some()
+                })
+                .collect();
+            let piece_order = &self.piece_order;
+            new_pieces.sort_by_key(|&x| {
+                (
+                    streaming_pieces.rank(x).unwrap_or(usize::MAX),
+                    Reverse(priority(x)),
+                    piece_order.key(x),
+                )
+            });
+            new_pieces
+        };
+        let awaited = self.streaming_pieces.awaited();
+
+        for new_piece in new_pieces {
+            // awaited pieces are downloaded from several peers, the fastest one wins
+            if let (TorrentDownloadMode::Normal, false) = (&self.mode, awaited.contains(&new_piece))
+            {
                 let any_peer_downloading = self.peer_states.values().any(|x| match x.state {
                     TorrentPeerState::Connected {
                         downloading_piece, ..
@@ -244,6 +585,7 @@ impl PeerManager {
                 if let TorrentPeerState::Connected {
                     ref mut downloading_piece,
                     ref mut downloading_since,
+                    ref mut am_interested,
                     ref mut sender,
                     ..
                 } = existing_peer.state
@@ -251,6 +593,7 @@ impl PeerManager {
                     if downloading_piece.is_none() {
                         *downloading_piece = Some(new_piece);
                         *downloading_since = Some(Instant::now());
+                        *am_interested = true;
                         sender.send(PeerMessage::Download(new_piece)).await?;
                     }
                 }
@@ -265,10 +608,17 @@ impl PeerManager {
         debug!("[{}] peer piece: {}", peer_id, peer_piece);
 
         let new_pieces = if let Some(existing_peer) = self.peer_states.get_mut(&peer_id) {
-            match existing_peer.state {
-                TorrentPeerState::Connected { .. } => {
+            match &mut existing_peer.state {
+                TorrentPeerState::Connected { pieces, .. } => {
                     let mut downloadable = vec![];
                     let (index, bit) = index_in_bitarray(peer_piece);
+                    if pieces.len() <= index {
+                        pieces.resize(index + 1, 0);
+                    }
+                    if pieces[index] & bit == 0 {
+                        self.availability.add(peer_piece);
+                    }
+                    pieces[index] |= bit;
                     match_pieces(
                         &mut downloadable,
                         &self.torrent_storage.receiver.borrow().downloaded,
@@ -291,6 +641,8 @@ impl PeerManager {
 
         self.select_new_peer(&new_pieces, peer_id).await?;
 
+        self.super_seed_propagated(peer_id).await?;
+
         Ok(())
     }
 
@@ -303,11 +655,16 @@ impl PeerManager {
 
         let new_pieces = if let Some(existing_peer) = self.peer_states.get_mut(&peer_id) {
             match &mut existing_peer.state {
-                TorrentPeerState::Connected { pieces, .. } => collect_pieces_and_update(
-                    pieces,
-                    &peer_pieces,
-                    &self.torrent_storage.receiver.borrow().downloaded,
-                ),
+                TorrentPeerState::Connected { pieces, .. } => {
+                    self.availability.remove_pieces(pieces);
+                    let new_pieces = collect_pieces_and_update(
+                        pieces,
+                        &peer_pieces,
+                        &self.torrent_storage.receiver.borrow().downloaded,
+                    );
+                    self.availability.add_pieces(pieces);
+                    new_pieces
+                }
                 TorrentPeerState::Idle | TorrentPeerState::Connecting(_) => {
                     error!(
                         "[{}] cannot process peer pieces: wrong state: {:?}",
@@ -322,6 +679,118 @@ impl PeerManager {
 
         self.select_new_peer(&new_pieces, peer_id).await?;
 
+        // piece offered before bitfield was received may be already at peer
+        let offered_piece_known = match (self.super_seed.as_ref(), self.peer_states.get(&peer_id)) {
+            (
+                Some(super_seed),
+                Some(PeerState {
+                    state: TorrentPeerState::Connected { pieces, .. },
+                    ..
+                }),
+            ) => super_seed
+                .offered(&peer_id)
+                .map(|x| bit_by_index(x, pieces).is_some())
+                .unwrap_or(true),
+            _ => false,
+        };
+        if offered_piece_known {
+            self.super_seed_offer(peer_id).await?;
+        }
+
+        self.super_seed_propagated(peer_id).await?;
+
+        Ok(())
+    }
+
+    /// Offers next piece to peer with `Have` while super-seeding, peer is unchoked to request it.
+    async fn super_seed_offer(&mut self, peer_id: Uuid) -> RsbtResult<()> {
+        if let (Some(super_seed), Some(peer_state)) =
+            (self.super_seed.as_mut(), self.peer_states.get_mut(&peer_id))
+        {
+            if let TorrentPeerState::Connected {
+                sender,
+                pieces,
+                am_chocking,
+                ..
+            } = &mut peer_state.state
+            {
+                if let Some(piece) = super_seed.offer(peer_id, pieces, self.availability.counts()) {
+                    debug!("[{}] super-seeding: offer piece {}", peer_id, piece);
+                    sender.send(PeerMessage::Have(piece)).await?;
+                    if *am_chocking {
+                        sender.send(PeerMessage::Unchoke).await?;
+                        *am_chocking = false;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Offers new pieces to peers whose offered piece has propagated to `peer_id`.
+    async fn super_seed_propagated(&mut self, peer_id: Uuid) -> RsbtResult<()> {
+        let peers = match (self.super_seed.as_ref(), self.peer_states.get(&peer_id)) {
+            (
+                Some(super_seed),
+                Some(PeerState {
+                    state: TorrentPeerState::Connected { pieces, .. },
+                    ..
+                }),
+            ) => super_seed.propagated(peer_id, pieces),
+            _ => return Ok(()),
+        };
+
+        for peer in peers {
+            debug!("[{}] super-seeding: offered piece propagated", peer);
+            self.super_seed_offer(peer).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Switches super-seeding, on disable peers receive `Have` for all pieces they miss.
+    pub(crate) async fn super_seed(
+        &mut self,
+        request_response: RequestResponse<bool, RsbtResult<()>>,
+    ) {
+        let result = self.set_super_seed(*request_response.request()).await;
+        if let Err(err) = request_response.response(result) {
+            error!("cannot send response for super-seed: {}", err);
+        }
+    }
+
+    async fn set_super_seed(&mut self, enabled: bool) -> RsbtResult<()> {
+        if enabled == self.super_seed.is_some() {
+            return Ok(());
+        }
+
+        if enabled {
+            if self.torrent_storage.receiver.borrow().pieces_left > 0 {
+                return Err(RsbtError::SuperSeedNotComplete);
+            }
+            debug!("super-seeding enabled");
+            self.super_seed = Some(SuperSeed::default());
+            let peers: Vec<Uuid> = self.peer_states.keys().cloned().collect();
+            for peer_id in peers {
+                self.super_seed_offer(peer_id).await?;
+            }
+        } else {
+            debug!("super-seeding disabled");
+            self.super_seed = None;
+            let pieces_count = self.torrent_process.info.pieces.len();
+            for (peer_id, peer_state) in self.peer_states.iter_mut() {
+                if let TorrentPeerState::Connected { sender, pieces, .. } = &mut peer_state.state {
+                    for piece in (0..pieces_count).filter(|&x| bit_by_index(x, pieces).is_none()) {
+                        if let Err(err) = sender.send(PeerMessage::Have(piece)).await {
+                            error!("[{}] cannot send Have: {}", peer_id, err);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -338,52 +807,264 @@ impl PeerManager {
         Ok(())
     }
 
+    pub(crate) async fn peer_choke(&mut self, peer_id: Uuid) -> RsbtResult<()> {
+        debug!("[{}] peer choke", peer_id);
+
+        if let Some(TorrentPeerState::Connected {
+            ref mut chocked, ..
+        }) = self.peer_states.get_mut(&peer_id).map(|x| &mut x.state)
+        {
+            *chocked = true;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn peer_not_interested(&mut self, peer_id: Uuid) -> RsbtResult<()> {
+        debug!("[{}] peer not interested", peer_id);
+
+        if let Some(TorrentPeerState::Connected {
+            ref mut interested,
+            ref mut am_chocking,
+            ref mut sender,
+            ..
+        }) = self.peer_states.get_mut(&peer_id).map(|x| &mut x.state)
+        {
+            *interested = false;
+            if !*am_chocking {
+                sender.send(PeerMessage::Choke).await?;
+                *am_chocking = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every interested peer is unchoked, uploads are limited by bandwidth only.
     pub(crate) async fn peer_interested(&mut self, peer_id: Uuid) -> RsbtResult<()> {
         debug!("[{}] peer interested", peer_id);
 
         if let Some(TorrentPeerState::Connected {
-            ref mut interested, ..
+            ref mut interested,
+            ref mut am_chocking,
+            ref mut sender,
+            ..
         }) = self.peer_states.get_mut(&peer_id).map(|x| &mut x.state)
         {
             *interested = true;
+            if *am_chocking {
+                sender.send(PeerMessage::Unchoke).await?;
+                *am_chocking = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn peer_piece_canceled(&mut self, peer_id: Uuid) -> RsbtResult<()> {
+        debug!("[{}] canceled piece for peer", peer_id);
+
+        let new_pieces = if let Some(existing_peer) = self.peer_states.get_mut(&peer_id) {
+            if let TorrentPeerState::Connected {
+                ref pieces,
+                ref mut downloading_piece,
+                ref mut downloading_since,
+                ..
+            } = existing_peer.state
+            {
+                *downloading_piece = None;
+                *downloading_since = None;
+                let mut downloadable = vec![];
+                for (i, &a) in pieces.iter().enumerate() {
+                    match_pieces(
+                        &mut downloadable,
+                        &self.torrent_storage.receiver.borrow().downloaded,
+                        i,
+                        a,
+                    );
+                }
+                downloadable
+            } else {
+                return Ok(());
+            }
+        } else {
+            return Ok(());
+        };
+
+        self.select_new_peer(&new_pieces, peer_id).await?;
+
+        Ok(())
+    }
+
+    /// Downloaded piece did not match checksum, piece is returned back to queue.
+    pub(crate) async fn peer_piece_hash_failed(
+        &mut self,
+        peer_id: Uuid,
+        index: usize,
+    ) -> RsbtResult<()> {
+        debug!("[{}] peer piece {} hash failed", peer_id, index);
+
+        let peer = if let Some(existing_peer) = self.peer_states.get_mut(&peer_id) {
+            if let TorrentPeerState::Connected {
+                ref mut downloading_piece,
+                ref mut downloading_since,
+                ..
+            } = existing_peer.state
+            {
+                *downloading_piece = None;
+                *downloading_since = None;
+            }
+            existing_peer.peer.clone()
+        } else {
+            return Ok(());
+        };
+
+        let hash_fails = {
+            let hash_fails = self.hash_fails.entry(peer.ip).or_default();
+            *hash_fails += 1;
+            *hash_fails
+        };
+
+        for peer_state in self
+            .peer_states
+            .values_mut()
+            .filter(|x| x.peer.ip == peer.ip)
+        {
+            peer_state.hash_fails = hash_fails;
+        }
+
+        if hash_fails >= PEER_MAX_HASH_FAILS {
+            warn!(
+                "[{}] peer {:?} banned after {} hash failures",
+                peer_id, peer, hash_fails
+            );
+            self.ban_peer(peer.ip).await;
+        }
+
+        self.requeue_piece(index, peer_id).await?;
+
+        if !self.is_banned(&peer.ip) {
+            self.peer_piece_canceled(peer_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Peer received next block of downloading piece.
+    pub(crate) fn peer_piece_progress(&mut self, peer_id: Uuid, length: usize) {
+        if let Some(TorrentPeerState::Connected {
+            ref downloading_piece,
+            ref mut downloading_since,
+            ref mut downloaded,
+            ref download_rate,
+            ..
+        }) = self.peer_states.get_mut(&peer_id).map(|x| &mut x.state)
+        {
+            *downloaded += length;
+            download_rate.add(length);
+            if downloading_piece.is_some() {
+                *downloading_since = Some(Instant::now());
+            }
+        }
+    }
+
+    /// Cancels pieces of snubbed peers and offers them to other peers.
+    ///
+    /// Candidates are connected too, slots may be freed by other torrents meanwhile.
+    pub(crate) async fn check_peers(&mut self) -> RsbtResult<()> {
+        self.connect_candidates();
+        self.close_streams();
+        if !self.streaming_pieces.awaited().is_empty() {
+            self.request_streaming_pieces().await?;
+        }
+
+        let snub_timeout = self.snub_timeout;
+
+        let snubbed: Vec<(Uuid, usize)> = self
+            .peer_states
+            .iter()
+            .filter_map(|(&peer_id, peer_state)| match peer_state.state {
+                TorrentPeerState::Connected {
+                    downloading_piece: Some(piece),
+                    downloading_since: Some(since),
+                    ..
+                } if since.elapsed() >= snub_timeout => Some((peer_id, piece)),
+                _ => None,
+            })
+            .collect();
+
+        for (peer_id, piece) in snubbed {
+            warn!(
+                "[{}] peer snubbed: no blocks of piece {} for {:?}",
+                peer_id, piece, snub_timeout
+            );
+            if let Some(TorrentPeerState::Connected {
+                ref mut downloading_piece,
+                ref mut downloading_since,
+                ref mut sender,
+                ..
+            }) = self.peer_states.get_mut(&peer_id).map(|x| &mut x.state)
+            {
+                *downloading_piece = None;
+                *downloading_since = None;
+                if let Err(err) = sender.send(PeerMessage::Cancel).await {
+                    error!("[{}] cannot send cancel to snubbed peer: {}", peer_id, err);
+                }
+            }
+            self.requeue_piece(piece, peer_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Offers piece to connected peers which have it, except `skip_peer_id`.
+    async fn requeue_piece(&mut self, index: usize, skip_peer_id: Uuid) -> RsbtResult<()> {
+        let candidates: Vec<Uuid> = self
+            .peer_states
+            .iter()
+            .filter(|(&key, _)| key != skip_peer_id)
+            .filter_map(|(key, peer_state)| match &peer_state.state {
+                TorrentPeerState::Connected {
+                    pieces,
+                    downloading_piece: None,
+                    ..
+                } if bit_by_index(index, pieces).is_some() => Some(*key),
+                _ => None,
+            })
+            .collect();
+
+        for peer_id in candidates {
+            self.select_new_peer(&[index], peer_id).await?;
         }
 
         Ok(())
     }
 
-    pub(crate) async fn peer_piece_canceled(&mut self, peer_id: Uuid) -> RsbtResult<()> {
-        debug!("[{}] canceled piece for peer", peer_id);
+    /// Address is banned for this torrent or for all torrents.
+    fn is_banned(&self, ip: &IpAddr) -> bool {
+        self.banned.contains(ip) || self.ban_list.contains(ip)
+    }
 
-        let new_pieces = if let Some(existing_peer) = self.peer_states.get_mut(&peer_id) {
-            if let TorrentPeerState::Connected {
-                ref pieces,
-                ref mut downloading_piece,
-                ref mut downloading_since,
-                ..
-            } = existing_peer.state
-            {
-                *downloading_piece = None;
-                *downloading_since = None;
-                let mut downloadable = vec![];
-                for (i, &a) in pieces.iter().enumerate() {
-                    match_pieces(
-                        &mut downloadable,
-                        &self.torrent_storage.receiver.borrow().downloaded,
-                        i,
-                        a,
+    /// Disconnects all peers with address and prevents new connections.
+    async fn ban_peer(&mut self, ip: IpAddr) {
+        self.banned.insert(ip);
+        self.disconnect_ip(ip).await;
+    }
+
+    /// Disconnects all peers with address, queued candidates are dropped.
+    async fn disconnect_ip(&mut self, ip: IpAddr) {
+        self.candidates.remove_ip(ip);
+
+        for (peer_id, peer_state) in self.peer_states.iter_mut().filter(|(_, x)| x.peer.ip == ip) {
+            if let TorrentPeerState::Connected { ref mut sender, .. } = peer_state.state {
+                if let Err(err) = sender.send(PeerMessage::Disconnect).await {
+                    error!(
+                        "[{}] cannot send disconnect message to banned peer: {}",
+                        peer_id, err
                     );
                 }
-                downloadable
-            } else {
-                return Ok(());
             }
-        } else {
-            return Ok(());
-        };
-
-        self.select_new_peer(&new_pieces, peer_id).await?;
-
-        Ok(())
+        }
     }
 
     pub(crate) async fn peer_piece_downloaded(&mut self, peer_id: Uuid, piece: Vec<u8>) {
@@ -428,18 +1109,20 @@ impl PeerManager {
                 if let (Some(index), Some(_since)) =
                     (downloading_piece.take(), downloading_since.take())
                 {
-                    self.torrent_storage.save(index, piece.to_vec()).await?;
-
-                    let mut downloadable = vec![];
-                    for (i, &a) in pieces.iter().enumerate() {
-                        match_pieces(
-                            &mut downloadable,
-                            &self.torrent_storage.receiver.borrow().downloaded,
-                            i,
-                            a,
-                        );
+                    if let Err(err) = self.torrent_storage.save(index, piece.to_vec()).await {
+                        (index, Err(err))
+                    } else {
+                        let mut downloadable = vec![];
+                        for (i, &a) in pieces.iter().enumerate() {
+                            match_pieces(
+                                &mut downloadable,
+                                &self.torrent_storage.receiver.borrow().downloaded,
+                                i,
+                                a,
+                            );
+                        }
+                        (index, Ok(downloadable))
                     }
-                    (index, downloadable)
                 } else {
                     return Ok(());
                 }
@@ -450,6 +1133,15 @@ impl PeerManager {
             return Ok(());
         };
 
+        // piece is not stored, so other peers download it again
+        let new_pieces = match new_pieces {
+            Ok(new_pieces) => new_pieces,
+            Err(err) => {
+                self.requeue_piece(index, peer_id).await?;
+                return Err(err);
+            }
+        };
+
         for (peer_id, peer_state) in self
             .peer_states
             .iter_mut()
@@ -462,6 +1154,18 @@ impl PeerManager {
                 ..
             } = peer_state.state
             {
+                // duplicate downloads of awaited piece are canceled, those peers have the piece
+                let peer_downloads_same_piece = *downloading_piece == Some(index);
+                if peer_downloads_same_piece {
+                    debug!("[{}] cancel duplicate download {}", peer_id, index);
+                    if let Err(err) = sender.send(PeerMessage::Cancel).await {
+                        error!(
+                            "[{}] cannot send Cancel to {:?}: {}",
+                            peer_id, peer_state.peer, err
+                        );
+                    };
+                }
+
                 let peer_already_have_piece = bit_by_index(index, pieces).is_some();
                 if peer_already_have_piece {
                     continue;
@@ -473,16 +1177,6 @@ impl PeerManager {
                         peer_id, peer_state.peer, err
                     );
                 };
-
-                let peer_downloads_same_piece = *downloading_piece == Some(index);
-                if peer_downloads_same_piece {
-                    if let Err(err) = sender.send(PeerMessage::Cancel).await {
-                        error!(
-                            "[{}] cannot send Have to {:?}: {}",
-                            peer_id, peer_state.peer, err
-                        );
-                    };
-                }
             }
         }
 
@@ -515,13 +1209,22 @@ impl PeerManager {
         if let Some(TorrentPeerState::Connected {
             ref mut sender,
             ref mut uploaded,
+            ref upload_rate,
             ..
         }) = self.peer_states.get_mut(&peer_id).map(|x| &mut x.state)
         {
             if let Some(piece) = self.torrent_storage.load(index as usize).await? {
+                let block = piece
+                    .as_ref()
+                    .get(begin as usize..(begin as usize + length as usize))
+                    .ok_or(RsbtError::PeerInvalidRequest {
+                        index,
+                        begin,
+                        length,
+                    })?
+                    .to_vec();
                 *uploaded += length as usize;
-                let block =
-                    piece.as_ref()[begin as usize..(begin as usize + length as usize)].to_vec();
+                upload_rate.add(length as usize);
                 sender
                     .send(PeerMessage::Piece {
                         index,
@@ -560,16 +1263,33 @@ impl PeerManager {
     pub(crate) async fn start(&mut self) -> RsbtResult<()> {
         self.announce_manager.start().await?;
 
+        let mut broker_sender = self.torrent_process.broker_sender.clone();
+        self.check_peers = Some(EventLoopCommand::spawn(async move {
+            loop {
+                delay_for(CHECK_PEERS_INTERVAL).await;
+                if let Err(err) = broker_sender.send(TorrentEvent::CheckPeers).await {
+                    error!("cannot send check peers: {}", err);
+                    break;
+                }
+            }
+        }));
+
         Ok(())
     }
 
     pub(crate) async fn stop(&mut self) -> RsbtResult<()> {
         self.announce_manager.stop().await?;
 
+        if let Some(check_peers) = self.check_peers.take() {
+            check_peers.abort();
+        }
+
         Ok(())
     }
 
     pub(crate) async fn quit(&mut self) -> RsbtResult<()> {
+        self.storage_ready.abort();
+
         if let Some(_announce_manager) = self.announce_manager.quit().await? {
             debug!("successfully exited announce manager");
         }
@@ -621,7 +1341,12 @@ impl PeerManager {
                 _ => (),
             }
         }
-        self.peer_states = HashMap::new();
+        // pieces of removed peers are not available anymore, super-seed offers are dropped
+        let peer_ids: Vec<Uuid> = self.peer_states.keys().copied().collect();
+        for peer_id in peer_ids {
+            self.peer_remove_by_id(peer_id);
+        }
+        self.candidates.clear();
 
         let result = self.stop().await;
 
@@ -644,6 +1369,130 @@ impl PeerManager {
         }
     }
 
+    /// Changes file priority and offers pieces of wanted files to idle peers.
+    pub(crate) async fn file_priority(
+        &mut self,
+        request_response: RequestResponse<(usize, FilePriority), RsbtResult<()>>,
+    ) {
+        let (file_id, priority) = *request_response.request();
+        let mut result = self
+            .torrent_storage
+            .set_file_priority(file_id, priority)
+            .await;
+        if result.is_ok() {
+            result = self.request_pieces().await;
+        }
+
+        if let Err(err) = request_response.response(result) {
+            error!("cannot send response for file priority: {}", err);
+        }
+    }
+
+    /// Switches sequential download.
+    pub(crate) fn sequential(&mut self, request_response: RequestResponse<bool, RsbtResult<()>>) {
+        self.piece_order.sequential = *request_response.request();
+        debug!("sequential download: {}", self.piece_order.sequential);
+        if let Err(err) = request_response.response(Ok(())) {
+            error!("cannot send response for sequential: {}", err);
+        }
+    }
+
+    /// Switches downloading of first and last pieces of each file first.
+    pub(crate) fn first_last_pieces(
+        &mut self,
+        request_response: RequestResponse<bool, RsbtResult<()>>,
+    ) {
+        let info = &self.torrent_process.info;
+        self.piece_order.set_first_last_pieces(
+            *request_response.request(),
+            info.piece_length,
+            &info.files,
+        );
+        debug!(
+            "first and last pieces first: {}",
+            self.piece_order.first_last_pieces()
+        );
+        if let Err(err) = request_response.response(Ok(())) {
+            error!("cannot send response for first and last pieces: {}", err);
+        }
+    }
+
+    /// Starts moving torrent data, pieces are not requested until files are moved.
+    pub(crate) async fn move_storage(
+        &mut self,
+        request_response: RequestResponse<PathBuf, RsbtResult<()>>,
+    ) {
+        let save_to = request_response.request().clone();
+        let result = self.torrent_storage.move_to(save_to).await;
+        if let Err(err) = request_response.response(result) {
+            error!("cannot send response for move: {}", err);
+        }
+    }
+
+    /// Renames data files, pieces are neither loaded nor saved until files are renamed.
+    pub(crate) async fn rename_storage(
+        &mut self,
+        request_response: RequestResponse<Vec<FlatStorageFile>, RsbtResult<()>>,
+    ) {
+        let files = request_response.request().clone();
+        let result = self.torrent_storage.rename(files).await;
+        if let Err(err) = request_response.response(result) {
+            error!("cannot send response for rename: {}", err);
+        }
+    }
+
+    /// Offers downloadable pieces to connected peers, which download nothing.
+    /// Fastest peers are offered first.
+    async fn request_pieces(&mut self) -> RsbtResult<()> {
+        let downloaded = self.torrent_storage.receiver.borrow().downloaded.clone();
+        let mut candidates: Vec<(Uuid, Vec<usize>, u64)> = self
+            .peer_states
+            .iter()
+            .filter_map(|(&peer_id, peer_state)| match &peer_state.state {
+                TorrentPeerState::Connected {
+                    pieces,
+                    downloading_piece: None,
+                    download_rate,
+                    ..
+                } => {
+                    let mut downloadable = vec![];
+                    for (i, &a) in pieces.iter().enumerate() {
+                        match_pieces(&mut downloadable, &downloaded, i, a);
+                    }
+                    Some((peer_id, downloadable, download_rate.rate()))
+                }
+                _ => None,
+            })
+            .collect();
+        candidates.sort_by_key(|x| Reverse(x.2));
+
+        for (peer_id, new_pieces, _) in candidates {
+            self.select_new_peer(&new_pieces, peer_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Starts data verification, progress is reported by storage state.
+    pub(crate) async fn recheck(&mut self, request_response: RequestResponse<(), RsbtResult<()>>) {
+        let result = self.torrent_storage.recheck().await;
+
+        if let Err(err) = request_response.response(result) {
+            error!("cannot send response for recheck: {}", err);
+        }
+    }
+
+    pub(crate) async fn save_state(
+        &mut self,
+        request_response: RequestResponse<(), RsbtResult<()>>,
+    ) {
+        let result = self.torrent_storage.save_state().await;
+
+        if let Err(err) = request_response.response(result) {
+            error!("cannot send response for save state: {}", err);
+        }
+    }
+
     pub(crate) async fn delete(&mut self, request_response: RequestResponse<bool, RsbtResult<()>>) {
         let delete_result = self
             .torrent_storage
@@ -659,13 +1508,103 @@ impl PeerManager {
         &mut self,
         request_response: RequestResponse<(), RsbtResult<Vec<PeerView>>>,
     ) {
-        let peers_view = self.peer_states.values().map(PeerView::from).collect();
+        let pieces_count = self.torrent_process.info.pieces.len();
+        let peers_view = self
+            .peer_states
+            .values()
+            .map(|x| PeerView::from((x, pieces_count)))
+            .collect();
 
         if let Err(err) = request_response.response(Ok(peers_view)) {
             error!("cannot send response for delete torrent: {}", err);
         }
     }
 
+    pub(crate) async fn add_peer(
+        &mut self,
+        request_response: RequestResponse<SocketAddr, RsbtResult<()>>,
+    ) {
+        let result = self.connect_added_peer(*request_response.request()).await;
+
+        if let Err(err) = request_response.response(result) {
+            error!("cannot send response for add peer: {}", err);
+        }
+    }
+
+    /// Manually added peer is connected before announced candidates.
+    async fn connect_added_peer(&mut self, addr: SocketAddr) -> RsbtResult<()> {
+        if self.is_banned(&addr.ip()) {
+            return Err(RsbtError::PeerBanned(addr.ip()));
+        }
+
+        if self.ip_filter.is_blocked(&addr.ip()) {
+            return Err(RsbtError::PeerBlocked(addr.ip()));
+        }
+
+        let peer: Peer = addr.into();
+
+        self.connect_fails.remove(&addr);
+        self.self_addrs.remove(&addr);
+
+        self.peer_announced(peer).await?;
+
+        self.candidates.prioritize(addr);
+
+        self.connect_candidates();
+
+        Ok(())
+    }
+
+    pub(crate) async fn ban_peer_request(
+        &mut self,
+        request_response: RequestResponse<(IpAddr, bool), RsbtResult<()>>,
+    ) {
+        let (ip, global) = *request_response.request();
+        if global {
+            self.disconnect_ip(ip).await;
+        } else {
+            self.ban_peer(ip).await;
+        }
+
+        if let Err(err) = request_response.response(Ok(())) {
+            error!("cannot send response for ban peer: {}", err);
+        }
+    }
+
+    pub(crate) async fn unban_peer(
+        &mut self,
+        request_response: RequestResponse<IpAddr, RsbtResult<()>>,
+    ) {
+        let ip = request_response.request();
+        self.banned.remove(ip);
+        self.hash_fails.remove(ip);
+
+        if let Err(err) = request_response.response(Ok(())) {
+            error!("cannot send response for unban peer: {}", err);
+        }
+    }
+
+    pub(crate) async fn disconnect_blocked(
+        &mut self,
+        request_response: RequestResponse<(), RsbtResult<()>>,
+    ) {
+        let blocked: HashSet<IpAddr> = self
+            .peer_states
+            .values()
+            .filter(|x| matches!(x.state, TorrentPeerState::Connected { .. }))
+            .map(|x| x.peer.ip)
+            .filter(|x| self.ip_filter.is_blocked(x))
+            .collect();
+        for ip in blocked {
+            debug!("disconnect peer {} blocked by ip filter", ip);
+            self.disconnect_ip(ip).await;
+        }
+
+        if let Err(err) = request_response.response(Ok(())) {
+            error!("cannot send response for disconnect blocked: {}", err);
+        }
+    }
+
     pub(crate) async fn announce_view(
         &mut self,
         request_response: RequestResponse<(), RsbtResult<Vec<AnnounceView>>>,
@@ -712,6 +1651,15 @@ impl PeerManager {
         let request = request_response.request();
         let piece_index = request.piece;
         debug!("query piece event: search for piece index {}", piece_index);
+        self.close_streams();
+        self.streaming_pieces.update(
+            &request.stream,
+            piece_index..(piece_index + 1 + self.stream_read_ahead).min(request.last_piece + 1),
+        );
+        if let Err(err) = self.request_streaming_pieces().await {
+            error!("cannot request streaming pieces: {}", err);
+        }
+
         let piece_bit = {
             let state = self.torrent_storage.receiver.borrow();
             let downloaded = state.downloaded.as_slice();
@@ -756,4 +1704,444 @@ impl PeerManager {
             .or_insert_with(|| vec![]);
         awaiters.push(request_response);
     }
+
+    /// Forgets pieces wanted by dropped file download streams.
+    fn close_streams(&mut self) {
+        self.streaming_pieces.retain_open();
+        self.awaiting_for_piece.retain(|_, awaiters| {
+            awaiters.retain(|x| x.request().stream.strong_count() > 0);
+            !awaiters.is_empty()
+        });
+    }
+
+    /// Offers streamed pieces to idle peers, if nobody downloads awaited piece
+    /// the fastest peer which has it drops its current piece.
+    async fn request_streaming_pieces(&mut self) -> RsbtResult<()> {
+        self.request_pieces().await?;
+
+        let downloaded = self.torrent_storage.receiver.borrow().downloaded.clone();
+        let mut preempted = HashSet::new();
+        for piece in self.streaming_pieces.awaited() {
+            if bit_by_index(piece, &downloaded).is_some() {
+                continue;
+            }
+            let any_peer_downloading = self.peer_states.values().any(|x| match x.state {
+                TorrentPeerState::Connected {
+                    downloading_piece, ..
+                } => downloading_piece == Some(piece),
+                _ => false,
+            });
+            if any_peer_downloading {
+                continue;
+            }
+
+            let streaming_pieces = &self.streaming_pieces;
+            let fastest = self
+                .peer_states
+                .iter_mut()
+                .filter(|(peer_id, _)| !preempted.contains(*peer_id))
+                .filter_map(|(&peer_id, peer_state)| match &mut peer_state.state {
+                    TorrentPeerState::Connected {
+                        chocked: false,
+                        downloading_piece: Some(downloading_piece),
+                        pieces,
+                        download_rate,
+                        sender,
+                        ..
+                    } if bit_by_index(piece, pieces).is_some()
+                        && streaming_pieces.rank(*downloading_piece).is_none() =>
+                    {
+                        Some((peer_id, download_rate.rate(), sender))
+                    }
+                    _ => None,
+                })
+                .max_by_key(|x| x.1);
+            if let Some((peer_id, _, sender)) = fastest {
+                debug!("[{}] cancel current piece for awaited {}", peer_id, piece);
+                sender.send(PeerMessage::Cancel).await?;
+                preempted.insert(peer_id);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Settings, StorageBackend};
+    use std::convert::TryInto;
+    use tokio::time::timeout;
+
+    /// Peer manager of sample torrent with in-memory storage, returned receiver gets torrent events.
+    async fn sample_peer_manager() -> (PeerManager, mpsc::Receiver<TorrentEvent>) {
+        sample_peer_manager_with(|_| ()).await
+    }
+
+    /// Peer manager of sample torrent, properties are changed by `f`.
+    async fn sample_peer_manager_with(
+        f: impl FnOnce(&mut Properties),
+    ) -> (PeerManager, mpsc::Receiver<TorrentEvent>) {
+        let config_dir = std::env::temp_dir().join(format!("rsbt-peer-manager-{}", Uuid::new_v4()));
+        let mut properties = Properties::from((Settings::default(), config_dir.clone()));
+        properties.storage_backend = StorageBackend::Memory;
+        f(&mut properties);
+        let properties = Arc::new(properties);
+
+        let (broker_sender, broker_receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        let torrent_process = TorrentToken::sample(broker_sender);
+        let files = torrent_process
+            .info
+            .files
+            .iter()
+            .map(|x| FlatStorageFile {
+                path: x.path.clone(),
+                length: x.length,
+            })
+            .collect();
+        let torrent_storage = TorrentStorage::new(
+            properties.clone(),
+            "ferris.gif",
+            torrent_process.clone(),
+            vec![],
+            config_dir,
+            files,
+        )
+        .await
+        .unwrap();
+
+        let peer_manager = PeerManager::new(
+            properties.clone(),
+            torrent_storage,
+            torrent_process,
+            Arc::new(ConnectionSlots::from_properties(&properties)),
+            Default::default(),
+            Arc::new(IpFilter::from_properties(&properties)),
+        )
+        .unwrap();
+
+        (peer_manager, broker_receiver)
+    }
+
+    /// Adds connected peer, messages sent to peer are returned by receiver.
+    fn connect_peer(
+        peer_manager: &mut PeerManager,
+        port: u16,
+    ) -> (Uuid, mpsc::Receiver<PeerMessage>) {
+        connect_peer_at(peer_manager, SocketAddr::from(([127, 0, 0, 1], port)))
+    }
+
+    fn connect_peer_at(
+        peer_manager: &mut PeerManager,
+        addr: SocketAddr,
+    ) -> (Uuid, mpsc::Receiver<PeerMessage>) {
+        let peer_id = Uuid::new_v4();
+        let (sender, receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        peer_manager.peer_states.insert(
+            peer_id,
+            PeerState {
+                peer: addr.into(),
+                state: TorrentPeerState::connected(sender, None, false, false),
+                announce_count: 0,
+                hash_fails: 0,
+                slot: None,
+            },
+        );
+        (peer_id, receiver)
+    }
+
+    /// Peer has all pieces and downloads `piece`.
+    fn start_download(peer_manager: &mut PeerManager, peer_id: Uuid, piece: Option<usize>) {
+        if let TorrentPeerState::Connected {
+            pieces,
+            downloading_piece,
+            downloading_since,
+            ..
+        } = &mut peer_manager.peer_states.get_mut(&peer_id).unwrap().state
+        {
+            *pieces = vec![0b1100_0000];
+            *downloading_piece = piece;
+            *downloading_since = piece.map(|_| Instant::now());
+        }
+    }
+
+    #[tokio::test]
+    async fn super_seed_offers_next_piece_after_propagation() {
+        let (mut peer_manager, _broker_receiver) = sample_peer_manager().await;
+        peer_manager.super_seed = Some(SuperSeed::default());
+        let (first, mut first_receiver) = connect_peer(&mut peer_manager, 6881);
+        let (second, mut second_receiver) = connect_peer(&mut peer_manager, 6882);
+        let (third, _third_receiver) = connect_peer(&mut peer_manager, 6883);
+
+        peer_manager.super_seed_offer(first).await.unwrap();
+        peer_manager.super_seed_offer(second).await.unwrap();
+        assert!(matches!(
+            first_receiver.try_recv(),
+            Ok(PeerMessage::Have(0))
+        ));
+        assert!(matches!(
+            first_receiver.try_recv(),
+            Ok(PeerMessage::Unchoke)
+        ));
+        assert!(matches!(
+            second_receiver.try_recv(),
+            Ok(PeerMessage::Have(1))
+        ));
+        assert!(matches!(
+            second_receiver.try_recv(),
+            Ok(PeerMessage::Unchoke)
+        ));
+
+        // piece offered to first peer is seen at third one, so first peer gets next piece
+        peer_manager.peer_piece(third, 0).await.unwrap();
+        assert_eq!(peer_manager.availability.counts(), &[1, 0]);
+        assert!(matches!(
+            first_receiver.try_recv(),
+            Ok(PeerMessage::Have(1))
+        ));
+        assert!(first_receiver.try_recv().is_err());
+        assert!(second_receiver.try_recv().is_err());
+
+        peer_manager.peer_remove_by_id(third);
+        assert_eq!(peer_manager.availability.counts(), &[0, 0]);
+    }
+
+    #[tokio::test]
+    async fn pieces_requested_after_check() {
+        let (mut peer_manager, mut broker_receiver) = sample_peer_manager().await;
+        let mut state = peer_manager.torrent_storage.receiver.borrow().clone();
+        state.checking = Some(0);
+        let (watch_sender, receiver) = watch::channel(state.clone());
+        peer_manager.torrent_storage.receiver = receiver.clone();
+        let storage_ready = PeerManager::watch_storage_ready(
+            receiver,
+            peer_manager.torrent_process.broker_sender.clone(),
+        );
+
+        // bitfield arrives while data is verified, nothing is requested yet
+        let (peer_id, mut peer_receiver) = connect_peer(&mut peer_manager, 6881);
+        peer_manager
+            .peer_pieces(peer_id, vec![0b1100_0000])
+            .await
+            .unwrap();
+        assert!(peer_receiver.try_recv().is_err());
+
+        state.checking = None;
+        watch_sender.broadcast(state).unwrap();
+        let event = timeout(Duration::from_secs(5), broker_receiver.recv())
+            .await
+            .unwrap();
+        assert!(matches!(event, Some(TorrentEvent::StorageReady)));
+
+        peer_manager.storage_ready().await.unwrap();
+        assert!(matches!(
+            peer_receiver.try_recv(),
+            Ok(PeerMessage::Download(0))
+        ));
+        storage_ready.abort();
+    }
+
+    #[tokio::test]
+    async fn streamed_piece_of_skipped_file_is_downloaded() {
+        let (mut peer_manager, _broker_receiver) = sample_peer_manager().await;
+        let mut state = peer_manager.torrent_storage.receiver.borrow().clone();
+        state.piece_priorities = Arc::new(vec![FilePriority::Skip; 2]);
+        let (_watch_sender, receiver) = watch::channel(state);
+        peer_manager.torrent_storage.receiver = receiver;
+        let stream = Arc::new(());
+        peer_manager
+            .streaming_pieces
+            .update(&Arc::downgrade(&stream), 1..2);
+
+        let (peer_id, mut peer_receiver) = connect_peer(&mut peer_manager, 6881);
+        peer_manager
+            .peer_pieces(peer_id, vec![0b1100_0000])
+            .await
+            .unwrap();
+        assert!(matches!(
+            peer_receiver.try_recv(),
+            Ok(PeerMessage::Download(1))
+        ));
+        assert!(peer_receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn duplicate_awaited_download_is_canceled() {
+        let (mut peer_manager, _broker_receiver) = sample_peer_manager().await;
+        let (first, _first_receiver) = connect_peer(&mut peer_manager, 6881);
+        let (second, mut second_receiver) = connect_peer(&mut peer_manager, 6882);
+        start_download(&mut peer_manager, first, Some(1));
+        start_download(&mut peer_manager, second, Some(1));
+
+        let piece = vec![0; peer_manager.torrent_process.info.last_piece_length];
+        peer_manager.peer_piece_downloaded(first, piece).await;
+        assert!(matches!(
+            second_receiver.try_recv(),
+            Ok(PeerMessage::Cancel)
+        ));
+        assert!(second_receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn hash_failed_piece_is_requeued_and_peer_banned() {
+        let (mut peer_manager, _broker_receiver) = sample_peer_manager().await;
+        let (bad, mut bad_receiver) = connect_peer(&mut peer_manager, 6881);
+        let (good, mut good_receiver) =
+            connect_peer_at(&mut peer_manager, SocketAddr::from(([127, 0, 0, 2], 6881)));
+        start_download(&mut peer_manager, bad, Some(0));
+        start_download(&mut peer_manager, good, None);
+
+        // failed piece goes to idle peer, failing peer gets another piece
+        peer_manager.peer_piece_hash_failed(bad, 0).await.unwrap();
+        assert!(matches!(
+            good_receiver.try_recv(),
+            Ok(PeerMessage::Download(0))
+        ));
+        assert!(matches!(
+            bad_receiver.try_recv(),
+            Ok(PeerMessage::Download(1))
+        ));
+
+        for _ in 1..PEER_MAX_HASH_FAILS {
+            peer_manager.peer_piece_hash_failed(bad, 1).await.unwrap();
+        }
+        let bad_ip = SocketAddr::from(([127, 0, 0, 1], 6881)).ip();
+        assert!(peer_manager.is_banned(&bad_ip));
+        assert_eq!(
+            peer_manager.peer_states[&bad].hash_fails,
+            PEER_MAX_HASH_FAILS
+        );
+        assert!(matches!(
+            bad_receiver.try_recv(),
+            Ok(PeerMessage::Download(1))
+        ));
+        assert!(matches!(
+            bad_receiver.try_recv(),
+            Ok(PeerMessage::Disconnect)
+        ));
+        assert!(bad_receiver.try_recv().is_err());
+        assert!(!peer_manager.is_banned(&SocketAddr::from(([127, 0, 0, 2], 6881)).ip()));
+    }
+
+    #[tokio::test]
+    async fn connect_fails_are_pruned() {
+        let (mut peer_manager, _broker_receiver) = sample_peer_manager().await;
+        let queued = SocketAddr::from(([10, 0, 0, 1], 6881));
+        peer_manager.candidates.push(queued.into());
+        peer_manager.connect_fails.insert(queued, 2);
+        for port in 0..PEER_MAX_CONNECT_FAILS as u16 {
+            peer_manager
+                .connect_fails
+                .insert(SocketAddr::from(([10, 0, 1, 1], port)), 1);
+        }
+
+        let (peer_id, _peer_receiver) = connect_peer(&mut peer_manager, 6881);
+        peer_manager.peer_connect_failed(peer_id);
+        assert_eq!(peer_manager.connect_fails.len(), 2);
+        assert_eq!(peer_manager.connect_fails[&queued], 2);
+        assert_eq!(
+            peer_manager.connect_fails[&SocketAddr::from(([127, 0, 0, 1], 6881))],
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn snubbed_peer_piece_is_reassigned() {
+        let (mut peer_manager, _broker_receiver) = sample_peer_manager().await;
+        peer_manager.snub_timeout = Duration::from_secs(0);
+        let (snubbed, mut snubbed_receiver) = connect_peer(&mut peer_manager, 6881);
+        let (idle, mut idle_receiver) = connect_peer(&mut peer_manager, 6882);
+        start_download(&mut peer_manager, snubbed, Some(0));
+        start_download(&mut peer_manager, idle, None);
+
+        peer_manager.check_peers().await.unwrap();
+        assert!(matches!(
+            snubbed_receiver.try_recv(),
+            Ok(PeerMessage::Cancel)
+        ));
+        assert!(matches!(
+            idle_receiver.try_recv(),
+            Ok(PeerMessage::Download(0))
+        ));
+        assert!(matches!(
+            peer_manager.peer_states[&snubbed].state,
+            TorrentPeerState::Connected {
+                downloading_piece: None,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn extended_handshake_advertises_bound_port() {
+        let (peer_manager, _broker_receiver) =
+            sample_peer_manager_with(|properties| properties.port = 51413).await;
+        let extended_handshake: ExtendedHandshake =
+            peer_manager.extended_handshake.clone().try_into().unwrap();
+        assert_eq!(extended_handshake.port, Some(51413));
+    }
+
+    #[tokio::test]
+    async fn banned_peer_is_disconnected_until_unbanned() {
+        let (mut peer_manager, _broker_receiver) = sample_peer_manager().await;
+        let addr = SocketAddr::from(([127, 0, 0, 2], 6881));
+        let (_, mut receiver) = connect_peer_at(&mut peer_manager, addr);
+
+        let (request, response) = RequestResponse::new((addr.ip(), false));
+        peer_manager.ban_peer_request(request).await;
+        assert!(response.await.unwrap().is_ok());
+        assert!(matches!(receiver.try_recv(), Ok(PeerMessage::Disconnect)));
+
+        // banned address is neither added manually nor taken from announces
+        let (request, response) = RequestResponse::new(addr);
+        peer_manager.add_peer(request).await;
+        assert!(
+            matches!(response.await.unwrap(), Err(RsbtError::PeerBanned(ip)) if ip == addr.ip())
+        );
+        let announced = SocketAddr::from(([127, 0, 0, 2], 6882));
+        peer_manager.peer_announced(announced.into()).await.unwrap();
+        assert!(!peer_manager.candidates.contains(&announced));
+
+        let (request, response) = RequestResponse::new(addr.ip());
+        peer_manager.unban_peer(request).await;
+        assert!(response.await.unwrap().is_ok());
+
+        let (request, response) = RequestResponse::new(announced);
+        peer_manager.add_peer(request).await;
+        assert!(response.await.unwrap().is_ok());
+        assert!(peer_manager.peer_states.values().any(|x| {
+            x.peer == announced.into() && matches!(x.state, TorrentPeerState::Connecting(_))
+        }));
+    }
+
+    #[tokio::test]
+    async fn disable_forgets_peer_pieces() {
+        let (mut peer_manager, _broker_receiver) = sample_peer_manager().await;
+        peer_manager.super_seed = Some(SuperSeed::default());
+        peer_manager.active = true;
+        let (first, _first_receiver) = connect_peer(&mut peer_manager, 6881);
+        let (second, _second_receiver) = connect_peer(&mut peer_manager, 6882);
+        peer_manager.super_seed_offer(first).await.unwrap();
+        peer_manager.peer_piece(second, 0).await.unwrap();
+        assert_eq!(peer_manager.availability.counts(), &[1, 0]);
+
+        let (request, response) = RequestResponse::new(());
+        peer_manager.disable(request).await;
+        assert!(response.await.unwrap().is_ok());
+        assert!(peer_manager.peer_states.is_empty());
+        assert_eq!(peer_manager.availability.counts(), &[0, 0]);
+        let super_seed = peer_manager.super_seed.as_ref().unwrap();
+        assert_eq!(super_seed.offered(&first), None);
+
+        let (request, response) = RequestResponse::new(());
+        peer_manager.enable(request).await;
+        assert!(response.await.unwrap().is_ok());
+        let (third, mut third_receiver) = connect_peer(&mut peer_manager, 6883);
+        peer_manager.super_seed_offer(third).await.unwrap();
+        assert!(matches!(
+            third_receiver.try_recv(),
+            Ok(PeerMessage::Have(0))
+        ));
+    }
 }
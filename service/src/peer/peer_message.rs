@@ -8,7 +8,11 @@ pub(crate) enum PeerMessage {
     Message(Message),
     Download(usize),
     Have(usize),
+    Choke,
+    Unchoke,
     Bitfield(Vec<u8>),
+    /// Bencoded payload of our extended handshake.
+    ExtendedHandshake(Vec<u8>),
     Piece {
         index: u32,
         begin: u32,
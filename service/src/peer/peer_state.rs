@@ -1,8 +1,13 @@
-use crate::{peer::TorrentPeerState, types::Peer};
+use crate::{
+    peer::{ConnectionSlot, TorrentPeerState},
+    types::Peer,
+};
 
 #[derive(Debug)]
 pub(crate) struct PeerState {
     pub(crate) peer: Peer,
     pub(crate) state: TorrentPeerState,
     pub(crate) announce_count: usize,
+    pub(crate) hash_fails: usize,
+    pub(crate) slot: Option<ConnectionSlot>,
 }
@@ -0,0 +1,71 @@
+use flat_storage::index_in_bitarray;
+
+/// Count of connected peers having each piece.
+///
+/// Counts are updated with peer bitfields and `Have` messages, so they are never recomputed.
+#[derive(Debug)]
+pub(crate) struct PieceAvailability {
+    counts: Vec<usize>,
+}
+
+impl PieceAvailability {
+    pub(crate) fn new(pieces_count: usize) -> Self {
+        Self {
+            counts: vec![0; pieces_count],
+        }
+    }
+
+    pub(crate) fn add(&mut self, piece: usize) {
+        if let Some(count) = self.counts.get_mut(piece) {
+            *count += 1;
+        }
+    }
+
+    /// Adds pieces of peer bitfield.
+    pub(crate) fn add_pieces(&mut self, pieces: &[u8]) {
+        for piece in Self::pieces(self.counts.len(), pieces) {
+            self.counts[piece] += 1;
+        }
+    }
+
+    /// Removes pieces of disconnected peer or of bitfield which is replaced.
+    pub(crate) fn remove_pieces(&mut self, pieces: &[u8]) {
+        for piece in Self::pieces(self.counts.len(), pieces) {
+            self.counts[piece] = self.counts[piece].saturating_sub(1);
+        }
+    }
+
+    pub(crate) fn counts(&self) -> &[usize] {
+        &self.counts
+    }
+
+    fn pieces(pieces_count: usize, pieces: &[u8]) -> impl Iterator<Item = usize> + '_ {
+        pieces
+            .iter()
+            .enumerate()
+            .filter(|(_, &x)| x != 0)
+            .flat_map(|(index, &x)| {
+                (index * 8..index * 8 + 8).filter(move |&piece| x & index_in_bitarray(piece).1 != 0)
+            })
+            .filter(move |&piece| piece < pieces_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn piece_availability_updates() {
+        let mut availability = PieceAvailability::new(10);
+
+        availability.add_pieces(&[0b1000_0001, 0b1100_0000]);
+        availability.add_pieces(&[0b1000_0000]);
+        availability.add(9);
+        availability.add(10);
+        assert_eq!(availability.counts(), &[2, 0, 0, 0, 0, 0, 0, 1, 1, 2]);
+
+        availability.remove_pieces(&[0b1000_0001, 0b1100_0000]);
+        assert_eq!(availability.counts(), &[1, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    }
+}
@@ -0,0 +1,95 @@
+use flat_storage::FlatStorageFile;
+use std::collections::HashSet;
+
+/// Order of piece downloading for media playback, players can start before download completes.
+#[derive(Debug, Default)]
+pub(crate) struct PieceOrder {
+    /// Pieces are downloaded in order of index.
+    pub(crate) sequential: bool,
+    /// First and last pieces of each file, these are downloaded first when set.
+    edge_pieces: Option<HashSet<usize>>,
+}
+
+impl PieceOrder {
+    pub(crate) fn set_first_last_pieces(
+        &mut self,
+        enabled: bool,
+        piece_length: usize,
+        files: &[FlatStorageFile],
+    ) {
+        self.edge_pieces = if enabled {
+            Some(edge_pieces(piece_length, files))
+        } else {
+            None
+        };
+    }
+
+    pub(crate) fn first_last_pieces(&self) -> bool {
+        self.edge_pieces.is_some()
+    }
+
+    /// Sort key of piece, lesser key is downloaded first.
+    pub(crate) fn key(&self, piece: usize) -> (bool, usize) {
+        (
+            self.edge_pieces
+                .as_ref()
+                .is_some_and(|x| !x.contains(&piece)),
+            if self.sequential { piece } else { 0 },
+        )
+    }
+}
+
+fn edge_pieces(piece_length: usize, files: &[FlatStorageFile]) -> HashSet<usize> {
+    let mut pieces = HashSet::new();
+    let mut offset = 0;
+    for file in files.iter().filter(|x| x.length > 0) {
+        pieces.insert(offset / piece_length);
+        pieces.insert((offset + file.length - 1) / piece_length);
+        offset += file.length;
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_piece_order() {
+        let files = [
+            FlatStorageFile {
+                path: "a".into(),
+                length: 35,
+            },
+            FlatStorageFile {
+                path: "b".into(),
+                length: 0,
+            },
+            FlatStorageFile {
+                path: "c".into(),
+                length: 30,
+            },
+        ];
+        assert_eq!(
+            edge_pieces(10, &files),
+            [0, 3, 6].iter().cloned().collect::<HashSet<_>>()
+        );
+
+        let mut piece_order = PieceOrder::default();
+        let mut pieces = vec![5, 3, 1, 6, 0];
+        pieces.sort_by_key(|&x| piece_order.key(x));
+        assert_eq!(pieces, vec![5, 3, 1, 6, 0]);
+
+        piece_order.set_first_last_pieces(true, 10, &files);
+        pieces.sort_by_key(|&x| piece_order.key(x));
+        assert_eq!(pieces, vec![3, 6, 0, 5, 1]);
+
+        piece_order.sequential = true;
+        pieces.sort_by_key(|&x| piece_order.key(x));
+        assert_eq!(pieces, vec![0, 3, 6, 1, 5]);
+
+        piece_order.set_first_last_pieces(false, 10, &files);
+        pieces.sort_by_key(|&x| piece_order.key(x));
+        assert_eq!(pieces, vec![0, 1, 3, 5, 6]);
+    }
+}
@@ -0,0 +1,80 @@
+use std::{ops::Range, sync::Weak};
+
+/// Pieces wanted by open file download streams.
+///
+/// Each stream has a window of pieces starting at awaited piece, window is forgotten when stream is dropped.
+#[derive(Debug, Default)]
+pub(crate) struct StreamingPieces {
+    streams: Vec<(Weak<()>, Range<usize>)>,
+}
+
+impl StreamingPieces {
+    /// Moves window of stream to awaited piece and pieces read ahead.
+    pub(crate) fn update(&mut self, stream: &Weak<()>, window: Range<usize>) {
+        if let Some(existing) = self.streams.iter_mut().find(|x| x.0.ptr_eq(stream)) {
+            existing.1 = window;
+        } else {
+            self.streams.push((stream.clone(), window));
+        }
+    }
+
+    /// Forgets windows of closed streams.
+    pub(crate) fn retain_open(&mut self) {
+        self.streams.retain(|x| x.0.strong_count() > 0);
+    }
+
+    /// Distance of piece from nearest awaited piece, `None` if no stream wants it.
+    pub(crate) fn rank(&self, piece: usize) -> Option<usize> {
+        self.streams
+            .iter()
+            .filter(|x| x.1.contains(&piece))
+            .map(|x| piece - x.1.start)
+            .min()
+    }
+
+    /// Pieces streams are waiting for right now.
+    pub(crate) fn awaited(&self) -> Vec<usize> {
+        let mut awaited: Vec<usize> = self
+            .streams
+            .iter()
+            .filter(|x| !x.1.is_empty())
+            .map(|x| x.1.start)
+            .collect();
+        awaited.sort_unstable();
+        awaited.dedup();
+        awaited
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn check_streaming_pieces() {
+        let first = Arc::new(());
+        let second = Arc::new(());
+        let mut streaming_pieces = StreamingPieces::default();
+
+        streaming_pieces.update(&Arc::downgrade(&first), 2..5);
+        streaming_pieces.update(&Arc::downgrade(&second), 4..6);
+        assert_eq!(streaming_pieces.rank(1), None);
+        assert_eq!(streaming_pieces.rank(3), Some(1));
+        assert_eq!(streaming_pieces.rank(4), Some(0));
+        assert_eq!(streaming_pieces.awaited(), vec![2, 4]);
+
+        streaming_pieces.update(&Arc::downgrade(&first), 3..6);
+        assert_eq!(streaming_pieces.awaited(), vec![3, 4]);
+
+        drop(second);
+        streaming_pieces.retain_open();
+        assert_eq!(streaming_pieces.rank(4), Some(1));
+        assert_eq!(streaming_pieces.awaited(), vec![3]);
+
+        drop(first);
+        streaming_pieces.retain_open();
+        assert_eq!(streaming_pieces.rank(4), None);
+        assert!(streaming_pieces.awaited().is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+use flat_storage::bit_by_index;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Super-seeding state (BEP 16), each peer is offered a single piece at a time.
+///
+/// New piece is offered after the previous one is seen at another peer.
+#[derive(Debug, Default)]
+pub(crate) struct SuperSeed {
+    /// Piece offered to each peer.
+    offered: HashMap<Uuid, usize>,
+    /// How many times each piece was offered.
+    offer_count: HashMap<usize, usize>,
+}
+
+impl SuperSeed {
+    /// Picks piece peer does not have, least available and least offered pieces first.
+    pub(crate) fn offer(
+        &mut self,
+        peer_id: Uuid,
+        peer_pieces: &[u8],
+        availability: &[usize],
+    ) -> Option<usize> {
+        let offer_count = &self.offer_count;
+        let piece = (0..availability.len())
+            .filter(|&x| bit_by_index(x, peer_pieces).is_none())
+            .min_by_key(|&x| {
+                (
+                    availability[x],
+                    offer_count.get(&x).cloned().unwrap_or_default(),
+                )
+            })?;
+
+        *self.offer_count.entry(piece).or_default() += 1;
+        self.offered.insert(peer_id, piece);
+
+        Some(piece)
+    }
+
+    pub(crate) fn offered(&self, peer_id: &Uuid) -> Option<usize> {
+        self.offered.get(peer_id).cloned()
+    }
+
+    /// Peers whose offered piece has propagated to `peer_id`.
+    pub(crate) fn propagated(&self, peer_id: Uuid, peer_pieces: &[u8]) -> Vec<Uuid> {
+        self.offered
+            .iter()
+            .filter(|(&id, &piece)| id != peer_id && bit_by_index(piece, peer_pieces).is_some())
+            .map(|(&id, _)| id)
+            .collect()
+    }
+
+    pub(crate) fn remove(&mut self, peer_id: &Uuid) {
+        self.offered.remove(peer_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn super_seed_offers_rare_pieces() {
+        let mut super_seed = SuperSeed::default();
+        let (first, second, third) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let availability = [1, 0, 0, 2];
+
+        assert_eq!(super_seed.offer(first, &[], &availability), Some(1));
+        assert_eq!(super_seed.offer(second, &[], &availability), Some(2));
+        // peer has piece 2, so piece 1 is offered again
+        assert_eq!(
+            super_seed.offer(third, &[0b0010_0000], &availability),
+            Some(1)
+        );
+
+        assert!(super_seed.propagated(second, &[0b1010_0000]).is_empty());
+        let mut propagated = super_seed.propagated(second, &[0b0100_0000]);
+        propagated.sort();
+        let mut expected = vec![first, third];
+        expected.sort();
+        assert_eq!(propagated, expected);
+
+        super_seed.remove(&first);
+        assert_eq!(super_seed.offered(&first), None);
+        assert_eq!(super_seed.offered(&third), Some(1));
+    }
+}
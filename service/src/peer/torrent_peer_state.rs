@@ -1,23 +1,69 @@
-use crate::peer::PeerMessage;
+use crate::{bandwidth::RateMeter, peer::PeerMessage};
 use std::time::Instant;
 use tokio::{sync::mpsc::Sender, task::JoinHandle};
 
+/// Peers spend most of their lifetime connected, boxing connected state would not save memory.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub(crate) enum TorrentPeerState {
     Idle,
     Connecting(JoinHandle<()>),
     Connected {
+        /// Peer chokes us.
         chocked: bool,
+        /// Peer is interested in our pieces.
         interested: bool,
+        /// We choke peer.
+        am_chocking: bool,
+        /// We are interested in peer pieces.
+        am_interested: bool,
         downloading_piece: Option<usize>,
+        /// Time of piece assignment or last received block.
         downloading_since: Option<Instant>,
         downloaded: usize,
         uploaded: usize,
+        download_rate: RateMeter,
+        upload_rate: RateMeter,
         sender: Sender<PeerMessage>,
         pieces: Vec<u8>,
+        /// Client name and version from peer id or extended handshake.
+        client: Option<String>,
+        /// Connection was initiated by peer.
+        incoming: bool,
+        /// Connection is encrypted with MSE.
+        encrypted: bool,
+        connected_since: Instant,
     },
 }
 
+impl TorrentPeerState {
+    pub(crate) fn connected(
+        sender: Sender<PeerMessage>,
+        client: Option<String>,
+        incoming: bool,
+        encrypted: bool,
+    ) -> Self {
+        TorrentPeerState::Connected {
+            chocked: true,
+            interested: false,
+            am_chocking: true,
+            am_interested: false,
+            downloading_piece: None,
+            downloading_since: None,
+            downloaded: 0,
+            uploaded: 0,
+            download_rate: RateMeter::new(),
+            upload_rate: RateMeter::new(),
+            sender,
+            pieces: vec![],
+            client,
+            incoming,
+            encrypted,
+            connected_since: Instant::now(),
+        }
+    }
+}
+
 impl Default for TorrentPeerState {
     fn default() -> Self {
         TorrentPeerState::Idle
@@ -0,0 +1,139 @@
+use crate::{
+    count_parts,
+    types::{info::TorrentInfo, Message},
+    RsbtError, PEER_MAX_REQUEST_LENGTH,
+};
+
+/// Validates message from peer against torrent info.
+///
+/// Message sequence is checked separately, as it depends on peer state.
+pub(crate) fn validate_message(info: &TorrentInfo, message: &Message) -> Result<(), RsbtError> {
+    match message {
+        Message::Bitfield(bitfield) => validate_bitfield(info, bitfield),
+        Message::Have { piece_index } => {
+            if (*piece_index as usize) < info.pieces.len() {
+                Ok(())
+            } else {
+                Err(RsbtError::PeerInvalidHave(*piece_index))
+            }
+        }
+        Message::Request {
+            index,
+            begin,
+            length,
+        }
+        | Message::Cancel {
+            index,
+            begin,
+            length,
+        } => validate_block(info, *index, *begin, *length),
+        Message::Piece {
+            index,
+            begin,
+            block,
+        } => validate_block(info, *index, *begin, block.len() as u32),
+        _ => Ok(()),
+    }
+}
+
+fn validate_bitfield(info: &TorrentInfo, bitfield: &[u8]) -> Result<(), RsbtError> {
+    let pieces_count = info.pieces.len();
+    let expected = count_parts(pieces_count, 8);
+
+    if bitfield.len() != expected {
+        return Err(RsbtError::PeerInvalidBitfieldLength {
+            length: bitfield.len(),
+            expected,
+        });
+    }
+
+    let spare_bits = expected * 8 - pieces_count;
+    if spare_bits > 0 && bitfield[expected - 1] & ((1 << spare_bits) - 1) != 0 {
+        return Err(RsbtError::PeerBitfieldSpareBits);
+    }
+
+    Ok(())
+}
+
+fn validate_block(
+    info: &TorrentInfo,
+    index: u32,
+    begin: u32,
+    length: u32,
+) -> Result<(), RsbtError> {
+    let invalid = || RsbtError::PeerInvalidRequest {
+        index,
+        begin,
+        length,
+    };
+
+    if length == 0 || length as usize > PEER_MAX_REQUEST_LENGTH {
+        return Err(invalid());
+    }
+
+    let piece = index as usize;
+    if piece >= info.pieces.len() {
+        return Err(invalid());
+    }
+
+    let (piece_length, _) = info.sizes(piece);
+    if begin as usize + length as usize > piece_length {
+        return Err(invalid());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::info::PieceChecksum;
+
+    fn info(pieces_count: usize) -> TorrentInfo {
+        TorrentInfo {
+            piece_length: 1 << 15,
+            default_blocks_count: 2,
+            last_piece_length: 100,
+            last_piece_blocks_count: 1,
+            pieces: vec![PieceChecksum([0; 20]); pieces_count],
+            length: (1 << 15) * (pieces_count - 1) + 100,
+            files: vec![],
+        }
+    }
+
+    #[test]
+    fn bitfield_validation() {
+        let info = info(10);
+
+        assert!(validate_message(&info, &Message::Bitfield(vec![0xff, 0xc0])).is_ok());
+        assert!(matches!(
+            validate_message(&info, &Message::Bitfield(vec![0xff])),
+            Err(RsbtError::PeerInvalidBitfieldLength {
+                length: 1,
+                expected: 2
+            })
+        ));
+        assert!(matches!(
+            validate_message(&info, &Message::Bitfield(vec![0xff, 0xe0])),
+            Err(RsbtError::PeerBitfieldSpareBits)
+        ));
+    }
+
+    #[test]
+    fn request_validation() {
+        let info = info(3);
+        let request = |index, begin, length| Message::Request {
+            index,
+            begin,
+            length,
+        };
+
+        assert!(validate_message(&info, &request(0, 1 << 14, 1 << 14)).is_ok());
+        assert!(validate_message(&info, &request(2, 0, 100)).is_ok());
+        assert!(validate_message(&info, &request(0, 1 << 14, 1 << 15)).is_err());
+        assert!(validate_message(&info, &request(2, 50, 100)).is_err());
+        assert!(validate_message(&info, &request(3, 0, 100)).is_err());
+        assert!(validate_message(&info, &request(0, 0, 0)).is_err());
+        assert!(validate_message(&info, &Message::Have { piece_index: 3 }).is_err());
+    }
+}
@@ -0,0 +1,39 @@
+use std::net::Ipv4Addr;
+
+const PROC_NET_ROUTE: &str = "/proc/net/route";
+
+/// Gateway of default route, only Linux routing table is supported.
+pub(crate) fn default_gateway() -> Option<Ipv4Addr> {
+    parse_route_table(&std::fs::read_to_string(PROC_NET_ROUTE).ok()?)
+}
+
+fn parse_route_table(table: &str) -> Option<Ipv4Addr> {
+    table.lines().skip(1).find_map(|line| {
+        let mut columns = line.split_whitespace().skip(1);
+        let destination = columns.next()?;
+        let gateway = u32::from_str_radix(columns.next()?, 16).ok()?;
+        if destination == "00000000" && gateway != 0 {
+            // addresses are in host byte order
+            Some(Ipv4Addr::from(gateway.to_ne_bytes()))
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_route() {
+        let table =
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                     eth0\t0001A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n\
+                     eth0\t00000000\t0101A8C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n";
+        assert_eq!(
+            parse_route_table(table),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use std::{net::IpAddr, sync::RwLock};
+
+/// External address and port reported by gateway, sent in announces.
+#[derive(Debug, Default)]
+pub(crate) struct ExternalIp {
+    ip: RwLock<Option<IpAddr>>,
+    port: RwLock<Option<u16>>,
+}
+
+impl ExternalIp {
+    pub(crate) fn get(&self) -> Option<IpAddr> {
+        *self.ip.read().unwrap()
+    }
+
+    pub(crate) fn set(&self, ip: Option<IpAddr>) {
+        *self.ip.write().unwrap() = ip;
+    }
+
+    /// Mapped port, listen port is announced if there is no mapping.
+    pub(crate) fn port(&self) -> Option<u16> {
+        *self.port.read().unwrap()
+    }
+
+    pub(crate) fn set_port(&self, port: Option<u16>) {
+        *self.port.write().unwrap() = port;
+    }
+}
@@ -0,0 +1,19 @@
+use crate::RsbtError;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::net::UdpSocket;
+
+/// Address of local interface routed to remote address, nothing is sent.
+pub(crate) async fn local_ip(remote: SocketAddr) -> Result<IpAddr, RsbtError> {
+    let udp_socket = UdpSocket::bind(SocketAddr::new(unspecified_ip(&remote), 0)).await?;
+    udp_socket.connect(remote).await?;
+    Ok(udp_socket.local_addr()?.ip())
+}
+
+/// Unspecified address of the same family.
+pub(crate) fn unspecified_ip(addr: &SocketAddr) -> IpAddr {
+    if addr.is_ipv4() {
+        Ipv4Addr::UNSPECIFIED.into()
+    } else {
+        Ipv6Addr::UNSPECIFIED.into()
+    }
+}
@@ -0,0 +1,21 @@
+mod default_gateway;
+mod external_ip;
+mod local_ip;
+mod pmp_gateway;
+mod port_mapping_gateway;
+mod port_mapping_lease;
+mod port_mapping_message;
+mod port_mapping_protocol;
+mod port_mapping_runner;
+mod upnp_gateway;
+
+pub(crate) use default_gateway::default_gateway;
+pub(crate) use external_ip::ExternalIp;
+pub(crate) use local_ip::{local_ip, unspecified_ip};
+pub(crate) use pmp_gateway::{PmpGateway, PMP_PORT};
+pub(crate) use port_mapping_gateway::PortMappingGateway;
+pub(crate) use port_mapping_lease::PortMappingLease;
+pub(crate) use port_mapping_message::PortMappingMessage;
+pub(crate) use port_mapping_protocol::PortMappingProtocol;
+pub(crate) use port_mapping_runner::PortMappingRunner;
+pub(crate) use upnp_gateway::{UpnpGateway, SSDP_ADDR};
@@ -0,0 +1,338 @@
+use crate::{
+    port_mapping::{local_ip, unspecified_ip, PortMappingLease, PortMappingProtocol},
+    RsbtError,
+};
+use bytes::{Buf, BufMut, BytesMut};
+use log::{debug, warn};
+use rand::random;
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+use tokio::{net::UdpSocket, time::timeout};
+
+/// Port of PCP and NAT-PMP server on gateway.
+pub(crate) const PMP_PORT: u16 = 5351;
+
+const NAT_PMP_VERSION: u8 = 0;
+const NAT_PMP_OPCODE_EXTERNAL_ADDRESS: u8 = 0;
+
+const PCP_VERSION: u8 = 2;
+const PCP_OPCODE_ANNOUNCE: u8 = 0;
+const PCP_OPCODE_MAP: u8 = 1;
+const PCP_MAP_RESPONSE_SIZE: usize = 60;
+
+const RESPONSE_BIT: u8 = 0x80;
+
+/// Requests are retried with doubling timeout.
+const PMP_INITIAL_TIMEOUT: Duration = Duration::from_millis(250);
+const PMP_RETRIES: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PmpVersion {
+    NatPmp,
+    Pcp,
+}
+
+/// Gateway with PCP or its predecessor NAT-PMP.
+#[derive(Debug, Clone)]
+pub(crate) struct PmpGateway {
+    addr: SocketAddr,
+    local_ip: IpAddr,
+    version: PmpVersion,
+    /// PCP mapping nonce, the same nonce is required to renew or delete mappings.
+    nonce: [u8; 12],
+}
+
+impl PmpGateway {
+    /// Detects protocol with PCP announce, NAT-PMP gateways reply with their version.
+    pub(crate) async fn discover(addr: SocketAddr) -> Result<Self, RsbtError> {
+        let local_ip = local_ip(addr).await?;
+
+        let response = pmp_request(addr, &pcp_header(PCP_OPCODE_ANNOUNCE, 0, local_ip)).await?;
+        let version = match response.first() {
+            Some(&PCP_VERSION) => PmpVersion::Pcp,
+            Some(&NAT_PMP_VERSION) => PmpVersion::NatPmp,
+            _ => return Err(RsbtError::PortMappingGatewayNotFound),
+        };
+        debug!("gateway {} supports {:?}", addr, version);
+
+        Ok(Self {
+            addr,
+            local_ip,
+            version,
+            nonce: random(),
+        })
+    }
+
+    /// Maps port, zero lifetime removes mapping.
+    pub(crate) async fn map(
+        &self,
+        protocol: PortMappingProtocol,
+        port: u16,
+        lifetime: Duration,
+    ) -> Result<PortMappingLease, RsbtError> {
+        match self.version {
+            PmpVersion::Pcp => self.pcp_map(protocol, port, lifetime).await,
+            PmpVersion::NatPmp => self.nat_pmp_map(protocol, port, lifetime).await,
+        }
+    }
+
+    async fn pcp_map(
+        &self,
+        protocol: PortMappingProtocol,
+        port: u16,
+        lifetime: Duration,
+    ) -> Result<PortMappingLease, RsbtError> {
+        let mut request = pcp_header(PCP_OPCODE_MAP, lifetime.as_secs() as u32, self.local_ip);
+        request.put_slice(&self.nonce);
+        request.put_u8(protocol.number());
+        request.put_slice(&[0; 3]);
+        request.put_u16(port);
+        request.put_u16(port);
+        request.put_slice(
+            &match self.local_ip {
+                IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.to_ipv6_mapped(),
+                IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED,
+            }
+            .octets(),
+        );
+
+        let mut response = BytesMut::from(&pmp_request(self.addr, &request).await?[..]);
+        if response.len() < PCP_MAP_RESPONSE_SIZE
+            || response[0] != PCP_VERSION
+            || response[1] != RESPONSE_BIT | PCP_OPCODE_MAP
+        {
+            return Err(invalid_response());
+        }
+        response.advance(3);
+        let result = response.get_u8();
+        if result != 0 {
+            return Err(RsbtError::PortMappingFailure(format!(
+                "pcp result code {}",
+                result
+            )));
+        }
+        let lifetime = response.get_u32();
+        // epoch and reserved
+        response.advance(16);
+        if response[..12] != self.nonce {
+            return Err(invalid_response());
+        }
+        // nonce, protocol, reserved and internal port
+        response.advance(18);
+        let external_port = response.get_u16();
+        let mut external_ip = [0u8; 16];
+        response.copy_to_slice(&mut external_ip);
+
+        if lifetime != 0 && external_port != port {
+            warn!(
+                "gateway mapped {} port {} to external port {}",
+                protocol.name(),
+                port,
+                external_port
+            );
+        }
+
+        Ok(PortMappingLease {
+            external_ip: Some(from_pcp_ip(external_ip)),
+            external_port,
+            lifetime: Duration::from_secs(lifetime.into()),
+        })
+    }
+
+    async fn nat_pmp_map(
+        &self,
+        protocol: PortMappingProtocol,
+        port: u16,
+        lifetime: Duration,
+    ) -> Result<PortMappingLease, RsbtError> {
+        let opcode = protocol.nat_pmp_opcode();
+        let mut request = BytesMut::new();
+        request.put_slice(&[NAT_PMP_VERSION, opcode, 0, 0]);
+        request.put_u16(port);
+        // suggested external port must be zero on removal
+        request.put_u16(if lifetime.as_secs() == 0 { 0 } else { port });
+        request.put_u32(lifetime.as_secs() as u32);
+
+        let mut response = self.nat_pmp_request(&request, opcode, 16).await?;
+        // internal port
+        response.advance(2);
+        let external_port = response.get_u16();
+        let lifetime = response.get_u32();
+
+        if lifetime != 0 && external_port != port {
+            warn!(
+                "gateway mapped {} port {} to external port {}",
+                protocol.name(),
+                port,
+                external_port
+            );
+        }
+
+        let external_ip = if lifetime != 0 {
+            let mut response = self
+                .nat_pmp_request(
+                    &[NAT_PMP_VERSION, NAT_PMP_OPCODE_EXTERNAL_ADDRESS],
+                    NAT_PMP_OPCODE_EXTERNAL_ADDRESS,
+                    12,
+                )
+                .await?;
+            Some(IpAddr::V4(response.get_u32().into()))
+        } else {
+            None
+        };
+
+        Ok(PortMappingLease {
+            external_ip,
+            external_port,
+            lifetime: Duration::from_secs(lifetime.into()),
+        })
+    }
+
+    /// Checks response header, returned buffer starts after epoch.
+    async fn nat_pmp_request(
+        &self,
+        request: &[u8],
+        opcode: u8,
+        size: usize,
+    ) -> Result<BytesMut, RsbtError> {
+        let mut response = BytesMut::from(&pmp_request(self.addr, request).await?[..]);
+        if response.len() < size
+            || response[0] != NAT_PMP_VERSION
+            || response[1] != RESPONSE_BIT | opcode
+        {
+            return Err(invalid_response());
+        }
+        response.advance(2);
+        let result = response.get_u16();
+        if result != 0 {
+            return Err(RsbtError::PortMappingFailure(format!(
+                "nat-pmp result code {}",
+                result
+            )));
+        }
+        // epoch
+        response.advance(4);
+
+        Ok(response)
+    }
+}
+
+fn pcp_header(opcode: u8, lifetime: u32, client_ip: IpAddr) -> BytesMut {
+    let mut header = BytesMut::new();
+    header.put_slice(&[PCP_VERSION, opcode, 0, 0]);
+    header.put_u32(lifetime);
+    header.put_slice(
+        &match client_ip {
+            IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+            IpAddr::V6(ip) => ip,
+        }
+        .octets(),
+    );
+    header
+}
+
+fn from_pcp_ip(octets: [u8; 16]) -> IpAddr {
+    if octets[..12] == [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff] {
+        IpAddr::from([octets[12], octets[13], octets[14], octets[15]])
+    } else {
+        IpAddr::from(octets)
+    }
+}
+
+fn invalid_response() -> RsbtError {
+    RsbtError::PortMappingFailure("invalid response from gateway".into())
+}
+
+async fn pmp_request(addr: SocketAddr, request: &[u8]) -> Result<Vec<u8>, RsbtError> {
+    let mut udp_socket = UdpSocket::bind(SocketAddr::new(unspecified_ip(&addr), 0)).await?;
+    udp_socket.connect(addr).await?;
+
+    let mut response = vec![0u8; 1100];
+    let mut request_timeout = PMP_INITIAL_TIMEOUT;
+    for _ in 0..PMP_RETRIES {
+        udp_socket.send(request).await?;
+        if let Ok(received) = timeout(request_timeout, udp_socket.recv(&mut response)).await {
+            response.truncate(received?);
+            return Ok(response);
+        }
+        request_timeout *= 2;
+    }
+
+    Err(RsbtError::PortMappingGatewayNotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fake NAT-PMP gateway, replies to announce with unsupported version.
+    ///
+    /// Mapped external port is next to requested one.
+    async fn nat_pmp_stand_in(mut udp_socket: UdpSocket) {
+        let mut request = vec![0u8; 1100];
+        loop {
+            let (size, addr) = udp_socket.recv_from(&mut request).await.unwrap();
+            let response: Vec<u8> = match request[..size] {
+                [PCP_VERSION, ..] => vec![0, RESPONSE_BIT, 0, 1, 0, 0, 0, 1],
+                [0, 0] => vec![0, RESPONSE_BIT, 0, 0, 0, 0, 0, 1, 203, 0, 113, 7],
+                [0, opcode, 0, 0, a, b, _, _, c, d, e, f] => vec![
+                    0,
+                    RESPONSE_BIT | opcode,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    1,
+                    a,
+                    b,
+                    a,
+                    b.wrapping_add(1),
+                    c,
+                    d,
+                    e,
+                    f,
+                ],
+                _ => panic!("unexpected request {:?}", &request[..size]),
+            };
+            udp_socket.send_to(&response, &addr).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn nat_pmp_map() {
+        let udp_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = udp_socket.local_addr().unwrap();
+        tokio::spawn(nat_pmp_stand_in(udp_socket));
+
+        let gateway = PmpGateway::discover(addr).await.unwrap();
+        assert_eq!(gateway.version, PmpVersion::NatPmp);
+
+        let lease = gateway
+            .map(PortMappingProtocol::Tcp, 6881, Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(
+            lease,
+            PortMappingLease {
+                external_ip: Some("203.0.113.7".parse().unwrap()),
+                external_port: 6882,
+                lifetime: Duration::from_secs(3600),
+            }
+        );
+
+        let lease = gateway
+            .map(PortMappingProtocol::Udp, 6881, Duration::from_secs(0))
+            .await
+            .unwrap();
+        assert_eq!(lease.external_ip, None);
+    }
+
+    #[test]
+    fn pcp_ip() {
+        let mapped = Ipv4Addr::new(203, 0, 113, 7).to_ipv6_mapped().octets();
+        assert_eq!(from_pcp_ip(mapped), IpAddr::from([203, 0, 113, 7]));
+    }
+}
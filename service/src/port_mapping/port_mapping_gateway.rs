@@ -0,0 +1,82 @@
+use crate::{
+    port_mapping::{
+        default_gateway, PmpGateway, PortMappingLease, PortMappingProtocol, UpnpGateway, PMP_PORT,
+        SSDP_ADDR,
+    },
+    RsbtError,
+};
+use log::debug;
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+/// Gateway found by discovery, UPnP IGD is preferred.
+#[derive(Debug, Clone)]
+pub(crate) enum PortMappingGateway {
+    Upnp(UpnpGateway),
+    Pmp(PmpGateway),
+}
+
+impl PortMappingGateway {
+    /// Tries UPnP IGD, then PCP and NAT-PMP on configured or default gateway.
+    pub(crate) async fn discover(gateway: Option<IpAddr>) -> Result<Self, RsbtError> {
+        match UpnpGateway::discover(SSDP_ADDR).await {
+            Ok(upnp_gateway) => return Ok(PortMappingGateway::Upnp(upnp_gateway)),
+            Err(err) => debug!("upnp gateway discovery failed: {}", err),
+        }
+
+        let gateway = gateway
+            .or_else(|| default_gateway().map(IpAddr::V4))
+            .ok_or(RsbtError::PortMappingGatewayNotFound)?;
+
+        Ok(PortMappingGateway::Pmp(
+            PmpGateway::discover(SocketAddr::new(gateway, PMP_PORT)).await?,
+        ))
+    }
+
+    /// Maps port for TCP and UDP, the shortest lease is returned.
+    ///
+    /// External port of TCP mapping is returned, peers connect to it.
+    pub(crate) async fn map(
+        &self,
+        port: u16,
+        lifetime: Duration,
+    ) -> Result<PortMappingLease, RsbtError> {
+        let mut leases = vec![];
+        for &protocol in PortMappingProtocol::ALL.iter() {
+            let lease = match self {
+                PortMappingGateway::Upnp(gateway) => gateway.map(protocol, port, lifetime).await?,
+                PortMappingGateway::Pmp(gateway) => gateway.map(protocol, port, lifetime).await?,
+            };
+            leases.push((protocol, lease));
+        }
+
+        Ok(PortMappingLease {
+            external_ip: leases.iter().find_map(|(_, x)| x.external_ip),
+            external_port: leases
+                .iter()
+                .find(|(protocol, _)| *protocol == PortMappingProtocol::Tcp)
+                .map(|(_, x)| x.external_port)
+                .unwrap_or(port),
+            lifetime: leases
+                .iter()
+                .map(|(_, x)| x.lifetime)
+                .min()
+                .unwrap_or(lifetime),
+        })
+    }
+
+    pub(crate) async fn unmap(&self, port: u16) -> Result<(), RsbtError> {
+        for &protocol in PortMappingProtocol::ALL.iter() {
+            match self {
+                PortMappingGateway::Upnp(gateway) => gateway.unmap(protocol, port).await?,
+                PortMappingGateway::Pmp(gateway) => {
+                    gateway.map(protocol, port, Duration::from_secs(0)).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+use std::{net::IpAddr, time::Duration};
+
+/// Result of port mapping, zero lifetime means permanent mapping.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PortMappingLease {
+    pub(crate) external_ip: Option<IpAddr>,
+    /// Port granted by gateway, it may differ from requested one.
+    pub(crate) external_port: u16,
+    pub(crate) lifetime: Duration,
+}
@@ -0,0 +1,9 @@
+use crate::{
+    port_mapping::{PortMappingGateway, PortMappingLease},
+    RsbtError,
+};
+
+pub(crate) enum PortMappingMessage {
+    Map,
+    Mapped(Result<(PortMappingGateway, PortMappingLease), RsbtError>),
+}
@@ -0,0 +1,35 @@
+/// Transport protocol of mapped port.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PortMappingProtocol {
+    Tcp,
+    Udp,
+}
+
+impl PortMappingProtocol {
+    pub(crate) const ALL: [PortMappingProtocol; 2] =
+        [PortMappingProtocol::Tcp, PortMappingProtocol::Udp];
+
+    /// Name used by UPnP IGD.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            PortMappingProtocol::Tcp => "TCP",
+            PortMappingProtocol::Udp => "UDP",
+        }
+    }
+
+    /// IANA protocol number used by PCP.
+    pub(crate) fn number(self) -> u8 {
+        match self {
+            PortMappingProtocol::Tcp => 6,
+            PortMappingProtocol::Udp => 17,
+        }
+    }
+
+    /// Opcode of NAT-PMP mapping request.
+    pub(crate) fn nat_pmp_opcode(self) -> u8 {
+        match self {
+            PortMappingProtocol::Udp => 1,
+            PortMappingProtocol::Tcp => 2,
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{
+    command::Command,
+    event_loop::{EventLoopCommand, EventLoopRunner, EventLoopSender},
+    port_mapping::{ExternalIp, PortMappingGateway, PortMappingMessage},
+    RsbtError,
+};
+use async_trait::async_trait;
+use log::{debug, warn};
+use std::{net::IpAddr, sync::Arc};
+use tokio::time::{delay_for, Duration};
+
+/// Requested lease, mappings are renewed at half of granted lifetime.
+const PORT_MAPPING_LIFETIME: Duration = Duration::from_secs(3600);
+const PORT_MAPPING_RETRY_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Keeps listen port mapped on gateway and tracks external address.
+///
+/// Mapping is removed when runner is stopped, last gateway is kept until removal succeeds.
+pub(crate) struct PortMappingRunner {
+    port: u16,
+    gateway_ip: Option<IpAddr>,
+    gateway: Option<PortMappingGateway>,
+    external_ip: Arc<ExternalIp>,
+    timer: Option<EventLoopCommand>,
+    sender: Option<EventLoopSender<PortMappingMessage, Command>>,
+}
+
+impl PortMappingRunner {
+    pub(crate) fn new(port: u16, gateway_ip: Option<IpAddr>, external_ip: Arc<ExternalIp>) -> Self {
+        Self {
+            port,
+            gateway_ip,
+            gateway: None,
+            external_ip,
+            timer: None,
+            sender: None,
+        }
+    }
+
+    /// Renews lease on known gateway, gateway is discovered again if renewal fails.
+    fn map(&mut self) {
+        let port = self.port;
+        let gateway_ip = self.gateway_ip;
+        let gateway = self.gateway.clone();
+        self.timer = self.command(
+            async move {
+                if let Some(gateway) = gateway {
+                    match gateway.map(port, PORT_MAPPING_LIFETIME).await {
+                        Ok(lease) => return Ok((gateway, lease)),
+                        Err(err) => debug!("cannot renew port mapping on {:?}: {}", gateway, err),
+                    }
+                }
+                let gateway = PortMappingGateway::discover(gateway_ip).await?;
+                let lease = gateway.map(port, PORT_MAPPING_LIFETIME).await?;
+                Ok((gateway, lease))
+            },
+            PortMappingMessage::Mapped,
+        );
+    }
+
+    fn schedule(&mut self, interval: Duration) {
+        self.timer = self.command(
+            async move {
+                delay_for(interval).await;
+                Ok(())
+            },
+            |_: Result<(), RsbtError>| PortMappingMessage::Map,
+        );
+    }
+}
+
+#[async_trait]
+impl EventLoopRunner<PortMappingMessage, Command> for PortMappingRunner {
+    fn set_sender(&mut self, sender: EventLoopSender<PortMappingMessage, Command>) {
+        self.sender = Some(sender);
+    }
+
+    fn sender(&mut self) -> Option<&mut EventLoopSender<PortMappingMessage, Command>> {
+        self.sender.as_mut()
+    }
+
+    async fn start(&mut self) -> Result<(), RsbtError> {
+        self.map();
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<(), RsbtError> {
+        if let Some(timer) = self.timer.take() {
+            timer.abort();
+        }
+        self.external_ip.set(None);
+        self.external_ip.set_port(None);
+        // gateway is kept if removal fails, so mapping is removed on next stop
+        if let Some(gateway) = &self.gateway {
+            debug!("removing port mapping for {}", self.port);
+            gateway.unmap(self.port).await?;
+            self.gateway = None;
+        }
+        Ok(())
+    }
+
+    async fn quit(&mut self) -> Result<(), RsbtError> {
+        self.stop().await
+    }
+
+    async fn handle(&mut self, message: PortMappingMessage) -> Result<(), RsbtError> {
+        match message {
+            PortMappingMessage::Map => self.map(),
+            PortMappingMessage::Mapped(Ok((gateway, lease))) => {
+                debug!(
+                    "port {} mapped with {:?}, external ip {:?}, external port {}",
+                    self.port, gateway, lease.external_ip, lease.external_port
+                );
+                self.gateway = Some(gateway);
+                self.external_ip.set(lease.external_ip);
+                self.external_ip.set_port(Some(lease.external_port));
+                let lifetime = if lease.lifetime.as_secs() == 0 {
+                    PORT_MAPPING_LIFETIME
+                } else {
+                    lease.lifetime
+                };
+                self.schedule(lifetime / 2);
+            }
+            PortMappingMessage::Mapped(Err(err)) => {
+                // last gateway is kept, its lease may be still active and must be removed on stop
+                warn!("cannot map port {}: {}", self.port, err);
+                self.schedule(PORT_MAPPING_RETRY_INTERVAL);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,344 @@
+use crate::{
+    port_mapping::{local_ip, PortMappingLease, PortMappingProtocol},
+    RsbtError,
+};
+use hyper::{Body, Client, Method, Request, Uri};
+use log::debug;
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+use tokio::{net::UdpSocket, time::timeout};
+
+/// SSDP multicast address.
+pub(crate) const SSDP_ADDR: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 1900);
+
+const SSDP_TIMEOUT: Duration = Duration::from_secs(2);
+const SSDP_SEARCH_TARGET: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+
+const WAN_SERVICES: [&str; 2] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:",
+    "urn:schemas-upnp-org:service:WANPPPConnection:",
+];
+
+const MAPPING_DESCRIPTION: &str = "rsbt";
+
+/// Gateway requires permanent mappings.
+const ONLY_PERMANENT_LEASES_SUPPORTED: &str = "<errorCode>725</errorCode>";
+
+/// Internet gateway device found with SSDP.
+#[derive(Debug, Clone)]
+pub(crate) struct UpnpGateway {
+    control_url: Uri,
+    service_type: String,
+    local_ip: IpAddr,
+}
+
+impl UpnpGateway {
+    /// Searches gateway with SSDP and reads its description.
+    pub(crate) async fn discover(ssdp_addr: SocketAddr) -> Result<Self, RsbtError> {
+        let mut udp_socket = UdpSocket::bind("0.0.0.0:0").await?;
+        let search = format!(
+            "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\n\r\n",
+            SSDP_ADDR, SSDP_SEARCH_TARGET
+        );
+        udp_socket.send_to(search.as_bytes(), &ssdp_addr).await?;
+
+        let mut response = vec![0u8; 1500];
+        let size = timeout(SSDP_TIMEOUT, udp_socket.recv(&mut response))
+            .await
+            .map_err(|_| RsbtError::PortMappingGatewayNotFound)??;
+
+        let location = String::from_utf8_lossy(&response[..size])
+            .lines()
+            .filter_map(|x| {
+                let mut header = x.splitn(2, ':');
+                match (header.next(), header.next()) {
+                    (Some(name), Some(value)) if name.trim().eq_ignore_ascii_case("location") => {
+                        Some(value.trim().to_string())
+                    }
+                    _ => None,
+                }
+            })
+            .next()
+            .ok_or(RsbtError::PortMappingGatewayNotFound)?;
+        debug!("found upnp gateway at {}", location);
+
+        Self::from_location(location.parse()?).await
+    }
+
+    /// Finds WAN connection service in device description.
+    pub(crate) async fn from_location(location: Uri) -> Result<Self, RsbtError> {
+        let description = http_body(Client::new().get(location.clone()).await?).await?;
+
+        let (service_type, control_url) = description
+            .split("<service>")
+            .skip(1)
+            .filter_map(|x| Some((xml_text(x, "serviceType")?, xml_text(x, "controlURL")?)))
+            .find(|(service_type, _)| WAN_SERVICES.iter().any(|x| service_type.starts_with(x)))
+            .ok_or(RsbtError::PortMappingGatewayNotFound)?;
+
+        let control_url = resolve_url(&location, control_url)?;
+        let host = control_url.host().unwrap_or_default();
+        let port = control_url.port_u16().unwrap_or(80);
+        let remote = tokio::net::lookup_host((host.trim_matches(|x| x == '[' || x == ']'), port))
+            .await?
+            .next()
+            .ok_or(RsbtError::PortMappingGatewayNotFound)?;
+
+        Ok(Self {
+            control_url,
+            service_type: service_type.into(),
+            local_ip: local_ip(remote).await?,
+        })
+    }
+
+    /// Maps port, gateways supporting only permanent mappings get zero lease duration.
+    pub(crate) async fn map(
+        &self,
+        protocol: PortMappingProtocol,
+        port: u16,
+        lifetime: Duration,
+    ) -> Result<PortMappingLease, RsbtError> {
+        let lifetime = match self.add_port_mapping(protocol, port, lifetime).await {
+            Err(RsbtError::PortMappingFailure(message))
+                if message.contains(ONLY_PERMANENT_LEASES_SUPPORTED) =>
+            {
+                self.add_port_mapping(protocol, port, Duration::from_secs(0))
+                    .await?;
+                Duration::from_secs(0)
+            }
+            result => {
+                result?;
+                lifetime
+            }
+        };
+
+        Ok(PortMappingLease {
+            external_ip: self.external_ip().await?,
+            external_port: port,
+            lifetime,
+        })
+    }
+
+    pub(crate) async fn unmap(
+        &self,
+        protocol: PortMappingProtocol,
+        port: u16,
+    ) -> Result<(), RsbtError> {
+        self.soap(
+            "DeletePortMapping",
+            &format!(
+                "<NewRemoteHost></NewRemoteHost><NewExternalPort>{}</NewExternalPort><NewProtocol>{}</NewProtocol>",
+                port,
+                protocol.name()
+            ),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn add_port_mapping(
+        &self,
+        protocol: PortMappingProtocol,
+        port: u16,
+        lifetime: Duration,
+    ) -> Result<(), RsbtError> {
+        self.soap(
+            "AddPortMapping",
+            &format!(
+                "<NewRemoteHost></NewRemoteHost><NewExternalPort>{port}</NewExternalPort><NewProtocol>{}</NewProtocol><NewInternalPort>{port}</NewInternalPort><NewInternalClient>{}</NewInternalClient><NewEnabled>1</NewEnabled><NewPortMappingDescription>{}</NewPortMappingDescription><NewLeaseDuration>{}</NewLeaseDuration>",
+                protocol.name(),
+                self.local_ip,
+                MAPPING_DESCRIPTION,
+                lifetime.as_secs(),
+                port = port,
+            ),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn external_ip(&self) -> Result<Option<IpAddr>, RsbtError> {
+        let response = self.soap("GetExternalIPAddress", "").await?;
+
+        Ok(xml_text(&response, "NewExternalIPAddress").and_then(|x| x.parse().ok()))
+    }
+
+    async fn soap(&self, action: &str, arguments: &str) -> Result<String, RsbtError> {
+        let body = format!(
+            "<?xml version=\"1.0\"?><s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\"><s:Body><u:{action} xmlns:u=\"{}\">{}</u:{action}></s:Body></s:Envelope>",
+            self.service_type,
+            arguments,
+            action = action,
+        );
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(self.control_url.clone())
+            .header("Content-Type", "text/xml; charset=\"utf-8\"")
+            .header(
+                "SOAPAction",
+                format!("\"{}#{}\"", self.service_type, action),
+            )
+            .body(Body::from(body))
+            .map_err(|err| RsbtError::PortMappingFailure(err.to_string()))?;
+
+        let response = Client::new().request(request).await?;
+        let success = response.status().is_success();
+        let body = http_body(response).await?;
+        if !success {
+            return Err(RsbtError::PortMappingFailure(format!(
+                "{} failed: {}",
+                action, body
+            )));
+        }
+
+        Ok(body)
+    }
+}
+
+async fn http_body(response: hyper::Response<Body>) -> Result<String, RsbtError> {
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Text of first element with tag, namespace prefixes are ignored.
+fn xml_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("{}>", tag))? + tag.len() + 1;
+    let end = start + xml[start..].find('<')?;
+    Some(xml[start..end].trim())
+}
+
+/// Resolves control URL relative to description location.
+fn resolve_url(location: &Uri, url: &str) -> Result<Uri, RsbtError> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        return Ok(url.parse()?);
+    }
+
+    let authority = location
+        .authority()
+        .ok_or(RsbtError::PortMappingGatewayNotFound)?;
+    Ok(format!(
+        "{}://{}/{}",
+        location.scheme_str().unwrap_or("http"),
+        authority,
+        url.trim_start_matches('/')
+    )
+    .parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Response, Server,
+    };
+    use std::convert::Infallible;
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root><device><serviceList>\
+        <service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType><controlURL>/l3f</controlURL></service>\
+        <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType><controlURL>/ctl/IPConn</controlURL></service>\
+        </serviceList></device></root>";
+
+    /// Fake gateway, accepts only permanent mappings.
+    async fn gateway_stand_in(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+        let action = request
+            .headers()
+            .get("SOAPAction")
+            .map(|x| x.to_str().unwrap().to_string())
+            .unwrap_or_default();
+        let path = request.uri().path().to_string();
+        let body = http_body(Response::new(request.into_body())).await.unwrap();
+
+        let response = match (path.as_str(), action.as_str()) {
+            ("/rootDesc.xml", _) => Response::new(Body::from(DESCRIPTION)),
+            ("/ctl/IPConn", x) if x.ends_with("#AddPortMapping\"") => {
+                assert!(body.contains("<NewInternalClient>127.0.0.1</NewInternalClient>"));
+                if body.contains("<NewLeaseDuration>0</NewLeaseDuration>") {
+                    Response::new(Body::empty())
+                } else {
+                    Response::builder()
+                        .status(500)
+                        .body(Body::from(
+                            "<s:Envelope><UPnPError><errorCode>725</errorCode></UPnPError></s:Envelope>",
+                        ))
+                        .unwrap()
+                }
+            }
+            ("/ctl/IPConn", x) if x.ends_with("#GetExternalIPAddress\"") => Response::new(
+                Body::from("<s:Envelope><u:GetExternalIPAddressResponse><NewExternalIPAddress>203.0.113.7</NewExternalIPAddress></u:GetExternalIPAddressResponse></s:Envelope>"),
+            ),
+            ("/ctl/IPConn", x) if x.ends_with("#DeletePortMapping\"") => {
+                Response::new(Body::empty())
+            }
+            _ => Response::builder().status(404).body(Body::empty()).unwrap(),
+        };
+
+        Ok(response)
+    }
+
+    #[tokio::test]
+    async fn discover_and_map() {
+        let server =
+            Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service_fn(|_| async {
+                Ok::<_, Infallible>(service_fn(gateway_stand_in))
+            }));
+        let http_addr = server.local_addr();
+        tokio::spawn(server);
+
+        let mut ssdp_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let ssdp_addr = ssdp_socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut request = vec![0u8; 1500];
+            let (size, addr) = ssdp_socket.recv_from(&mut request).await.unwrap();
+            assert!(request[..size].starts_with(b"M-SEARCH"));
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nST: {}\r\nLocation: http://{}/rootDesc.xml\r\n\r\n",
+                SSDP_SEARCH_TARGET, http_addr
+            );
+            ssdp_socket
+                .send_to(response.as_bytes(), &addr)
+                .await
+                .unwrap();
+        });
+
+        let gateway = UpnpGateway::discover(ssdp_addr).await.unwrap();
+        assert_eq!(gateway.control_url.path(), "/ctl/IPConn");
+        assert_eq!(
+            gateway.service_type,
+            "urn:schemas-upnp-org:service:WANIPConnection:1"
+        );
+
+        let lease = gateway
+            .map(PortMappingProtocol::Tcp, 6881, Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(
+            lease,
+            PortMappingLease {
+                external_ip: Some("203.0.113.7".parse().unwrap()),
+                external_port: 6881,
+                lifetime: Duration::from_secs(0),
+            }
+        );
+
+        gateway.unmap(PortMappingProtocol::Tcp, 6881).await.unwrap();
+    }
+
+    #[test]
+    fn control_url() {
+        let location: Uri = "http://192.168.1.1:5000/rootDesc.xml".parse().unwrap();
+        assert_eq!(
+            resolve_url(&location, "ctl/IPConn").unwrap(),
+            "http://192.168.1.1:5000/ctl/IPConn"
+        );
+        assert_eq!(
+            resolve_url(&location, "http://192.168.1.1:6000/ctl").unwrap(),
+            "http://192.168.1.1:6000/ctl"
+        );
+    }
+}
@@ -6,12 +6,19 @@ use crate::{
     result::RsbtResult,
     storage::TorrentStorageState,
     types::{
-        public::{AnnounceView, FileView, PeerView, TorrentDownloadState},
+        public::{AnnounceView, FilePriority, FileView, PeerView, TorrentDownloadState},
         Properties,
     },
+    RsbtError,
 };
 use log::debug;
-use std::{ops::Range, sync::Arc};
+use std::{
+    collections::HashSet,
+    net::{IpAddr, SocketAddr},
+    ops::Range,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
 use tokio::sync::watch;
 
 #[derive(Debug, Clone)]
@@ -23,6 +30,9 @@ pub struct TorrentProcess {
     pub properties: Arc<Properties>,
     pub storage_state_watch: watch::Receiver<TorrentStorageState>,
     pub statistics_watch: watch::Receiver<TorrentDownloadState>,
+    /// Enabled, but waiting for free active torrent slot.
+    pub queued: bool,
+    pub super_seed: bool,
 }
 
 impl TorrentProcess {
@@ -52,9 +62,164 @@ impl TorrentProcess {
 
         self.request((), TorrentEvent::Disable).await?;
 
+        self.queued = false;
+
         self.update_state(TorrentProcessStatus::Disabled).await
     }
 
+    /// Enables torrent without starting it, it waits for free active torrent slot.
+    pub(crate) async fn enable_queued(&mut self) -> RsbtResult<()> {
+        debug!("enable queued {}", self.id);
+
+        self.queued = true;
+
+        self.update_state(TorrentProcessStatus::Enabled).await
+    }
+
+    /// Stops enabled torrent over active torrents limit, saved state is kept.
+    pub(crate) async fn queue(&mut self) -> RsbtResult<()> {
+        debug!("queue {}", self.id);
+
+        self.request((), TorrentEvent::Disable).await?;
+
+        self.queued = true;
+
+        Ok(())
+    }
+
+    pub(crate) async fn unqueue(&mut self) -> RsbtResult<()> {
+        debug!("unqueue {}", self.id);
+
+        self.request((), TorrentEvent::Enable).await?;
+
+        self.queued = false;
+
+        Ok(())
+    }
+
+    pub(crate) async fn set_super_seed(&mut self, enabled: bool) -> RsbtResult<()> {
+        debug!("super-seed {} for {}", enabled, self.id);
+
+        self.request(enabled, TorrentEvent::SuperSeed).await?;
+
+        self.super_seed = enabled;
+
+        Ok(())
+    }
+
+    /// Changes download priority of file, `skip` excludes it from download.
+    pub(crate) async fn set_file_priority(
+        &mut self,
+        file_id: usize,
+        priority: FilePriority,
+    ) -> RsbtResult<()> {
+        debug!("file {} priority {:?} for {}", file_id, priority, self.id);
+
+        self.request((file_id, priority), TorrentEvent::FilePriority)
+            .await?;
+
+        let file_priorities = &mut self.header.file_priorities;
+        if file_priorities.len() <= file_id {
+            file_priorities.resize(file_id + 1, FilePriority::default());
+        }
+        file_priorities[file_id] = priority;
+
+        Ok(())
+    }
+
+    /// Downloads pieces in order of index.
+    pub(crate) async fn set_sequential(&mut self, enabled: bool) -> RsbtResult<()> {
+        debug!("sequential {} for {}", enabled, self.id);
+
+        self.request(enabled, TorrentEvent::Sequential).await?;
+
+        self.header.sequential = enabled;
+
+        Ok(())
+    }
+
+    /// Downloads first and last pieces of each file before others.
+    pub(crate) async fn set_first_last_pieces(&mut self, enabled: bool) -> RsbtResult<()> {
+        debug!("first and last pieces {} for {}", enabled, self.id);
+
+        self.request(enabled, TorrentEvent::FirstLastPieces).await?;
+
+        self.header.first_last_pieces = enabled;
+
+        Ok(())
+    }
+
+    /// Starts moving torrent data to `save_to` directory, progress is reported by storage state.
+    pub(crate) async fn move_to(&mut self, save_to: PathBuf) -> RsbtResult<()> {
+        debug!("move {} to {:?}", self.id, save_to);
+
+        if self.storage_state_watch.borrow().moving.is_some() {
+            return Err(RsbtError::TorrentDataMoving);
+        }
+
+        self.request(save_to, TorrentEvent::Move).await
+    }
+
+    /// Starts renaming file, or folder of all files if `file_id` is missing, data is renamed on disk.
+    ///
+    /// Renamed header is returned, it is applied when files are renamed.
+    pub(crate) async fn rename(
+        &self,
+        file_id: Option<usize>,
+        name: String,
+    ) -> RsbtResult<TorrentProcessHeader> {
+        debug!("rename {:?} of {} to {}", file_id, self.id, name);
+
+        if !is_file_name(&name) {
+            return Err(RsbtError::TorrentFileNameInvalid(name));
+        }
+        // header is updated when previous move is finished, renamed paths would be lost
+        if self.storage_state_watch.borrow().moving.is_some() {
+            return Err(RsbtError::TorrentDataMoving);
+        }
+
+        let info = &self.process.info;
+        let mut header = self.header.clone();
+        if let Some(file_id) = file_id {
+            if file_id >= info.files.len() {
+                return Err(RsbtError::TorrentFileNotFound(file_id));
+            }
+            let file_paths = &mut header.file_paths;
+            let renamed = file_paths.len().min(info.files.len());
+            file_paths.extend(info.files[renamed..].iter().map(|x| x.path.clone()));
+            file_paths[file_id].set_file_name(&name);
+        } else {
+            header.folder = Some(name.clone());
+        }
+
+        let files = header.files(info);
+        let paths: HashSet<_> = files.iter().map(|x| &x.path).collect();
+        if paths.len() < files.len() {
+            return Err(RsbtError::TorrentFileNameInvalid(name));
+        }
+
+        self.request(files, TorrentEvent::Rename).await?;
+
+        Ok(header)
+    }
+
+    /// Saves storage state, which is otherwise saved in batches.
+    pub(crate) async fn save_state(&self) -> RsbtResult<()> {
+        debug!("save state of {}", self.id);
+
+        self.request((), TorrentEvent::SaveState).await
+    }
+
+    pub(crate) async fn recheck(&mut self) -> RsbtResult<()> {
+        debug!("recheck {}", self.id);
+
+        if self.super_seed {
+            self.set_super_seed(false).await?;
+        }
+
+        self.request((), TorrentEvent::Recheck).await
+    }
+
     async fn update_state(&mut self, state: TorrentProcessStatus) -> RsbtResult<()> {
         self.header.state = state;
 
@@ -73,6 +238,30 @@ impl TorrentProcess {
         self.request((), TorrentEvent::PeersView).await
     }
 
+    pub(crate) async fn add_peer(&self, addr: SocketAddr) -> RsbtResult<()> {
+        debug!("add peer {} for {}", addr, self.id);
+
+        self.request(addr, TorrentEvent::AddPeer).await
+    }
+
+    pub(crate) async fn ban_peer(&self, ip: IpAddr, global: bool) -> RsbtResult<()> {
+        debug!("ban peer {} for {}", ip, self.id);
+
+        self.request((ip, global), TorrentEvent::BanPeer).await
+    }
+
+    pub(crate) async fn unban_peer(&self, ip: IpAddr) -> RsbtResult<()> {
+        debug!("unban peer {} for {}", ip, self.id);
+
+        self.request(ip, TorrentEvent::UnbanPeer).await
+    }
+
+    pub(crate) async fn disconnect_blocked(&self) -> RsbtResult<()> {
+        debug!("disconnect blocked peers for {}", self.id);
+
+        self.request((), TorrentEvent::DisconnectBlocked).await
+    }
+
     pub(crate) async fn announces(&self) -> RsbtResult<Vec<AnnounceView>> {
         debug!("peers for {}", self.id);
 
@@ -95,3 +284,29 @@ impl TorrentProcess {
             .await
     }
 }
+
+/// Name is a single path component, so renamed file stays in its directory.
+fn is_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(x)), None) if x == name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_is_file_name() {
+        assert!(is_file_name("movie.mkv"));
+        assert!(is_file_name("my folder"));
+        assert!(!is_file_name(""));
+        assert!(!is_file_name("."));
+        assert!(!is_file_name(".."));
+        assert!(!is_file_name("a/b"));
+        assert!(!is_file_name("a/"));
+        assert!(!is_file_name("/a"));
+    }
+}
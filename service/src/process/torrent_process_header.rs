@@ -1,8 +1,98 @@
-use crate::process::TorrentProcessStatus;
+use crate::{
+    process::TorrentProcessStatus,
+    types::{info::TorrentInfo, public::FilePriority},
+};
+use flat_storage::FlatStorageFile;
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TorrentProcessHeader {
     pub file: String,
     pub state: TorrentProcessStatus,
+    /// Torrent upload limit in bytes per second
+    pub upload_limit: Option<u64>,
+    /// Torrent download limit in bytes per second
+    pub download_limit: Option<u64>,
+    /// Priorities by file index, missing files have normal priority
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub file_priorities: Vec<FilePriority>,
+    /// Pieces are downloaded in order
+    #[serde(default)]
+    pub sequential: bool,
+    /// First and last pieces of each file are downloaded first
+    #[serde(default)]
+    pub first_last_pieces: bool,
+    /// Directory of torrent data, `save_to` setting if missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub save_to: Option<PathBuf>,
+    /// Folder of torrent files inside data directory, files are stored directly if missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    /// Renamed file paths by file index, missing files keep torrent paths
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub file_paths: Vec<PathBuf>,
+}
+
+impl TorrentProcessHeader {
+    /// Files as they are stored on disk, peers still see layout of torrent.
+    pub(crate) fn files(&self, info: &TorrentInfo) -> Vec<FlatStorageFile> {
+        info.files
+            .iter()
+            .enumerate()
+            .map(|(id, file)| {
+                let path = self.file_paths.get(id).unwrap_or(&file.path);
+                FlatStorageFile {
+                    path: self
+                        .folder
+                        .as_ref()
+                        .map_or_else(|| path.clone(), |x| Path::new(x).join(path)),
+                    length: file.length,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_header_files() {
+        let file = |path: &str, length| FlatStorageFile {
+            path: PathBuf::from(path),
+            length,
+        };
+        let info = TorrentInfo {
+            piece_length: 16384,
+            default_blocks_count: 1,
+            last_piece_length: 16384,
+            last_piece_blocks_count: 1,
+            pieces: vec![],
+            length: 32768,
+            files: vec![file("a/1.mp4", 16384), file("b.txt", 16384)],
+        };
+        let mut header = TorrentProcessHeader {
+            file: "sample.torrent".into(),
+            state: TorrentProcessStatus::Enabled,
+            upload_limit: None,
+            download_limit: None,
+            file_priorities: vec![],
+            sequential: false,
+            first_last_pieces: false,
+            save_to: None,
+            folder: None,
+            file_paths: vec![],
+        };
+        assert_eq!(header.files(&info), info.files);
+
+        // only first file is renamed, other keeps its torrent path
+        header.file_paths = vec![PathBuf::from("a/2.mp4")];
+        header.folder = Some("folder".into());
+        assert_eq!(
+            header.files(&info),
+            vec![file("folder/a/2.mp4", 16384), file("folder/b.txt", 16384)]
+        );
+    }
 }
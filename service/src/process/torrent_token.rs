@@ -1,9 +1,12 @@
 use crate::{
+    bandwidth::TorrentBandwidth,
     event::TorrentEvent,
+    port_mapping::ExternalIp,
     process::TorrentTokenProvider,
     types::{info::TorrentInfo, Torrent},
     SHA1_SIZE,
 };
+use std::{net::IpAddr, sync::Arc};
 use tokio::sync::mpsc::Sender;
 
 #[derive(Debug)]
@@ -11,8 +14,12 @@ pub struct TorrentToken {
     pub(crate) torrent: Torrent,
     pub info: TorrentInfo,
     pub(crate) hash_id: [u8; SHA1_SIZE],
+    /// Our peer id, connections from peers with the same id are connections to self
+    pub(crate) peer_id: [u8; 20],
     pub(crate) handshake: Vec<u8>,
     pub(crate) broker_sender: Sender<TorrentEvent>,
+    pub(crate) bandwidth: TorrentBandwidth,
+    pub(crate) external_ip: Arc<ExternalIp>,
 }
 
 impl TorrentTokenProvider for TorrentToken {
@@ -23,4 +30,42 @@ impl TorrentTokenProvider for TorrentToken {
     fn hash_id(&self) -> &[u8; SHA1_SIZE] {
         &self.hash_id
     }
+
+    fn external_ip(&self) -> Option<IpAddr> {
+        self.external_ip.get()
+    }
+
+    fn external_port(&self) -> Option<u16> {
+        self.external_ip.port()
+    }
+}
+
+#[cfg(test)]
+impl TorrentToken {
+    /// Token of sample torrent, torrent events are sent to `broker_sender`.
+    pub(crate) fn sample(broker_sender: Sender<TorrentEvent>) -> Arc<Self> {
+        use crate::{bandwidth::BandwidthLimiter, parser::parse_torrent, types::HANDSHAKE_PREFIX};
+
+        let torrent = parse_torrent(include_bytes!("../../tests/ferris.gif.torrent"))
+            .expect("sample torrent");
+        let hash_id = torrent.info_sha1_hash();
+        let info = torrent.info().expect("sample torrent info");
+        let peer_id = *b"-rs0100-zzzzxxxxyyyy";
+        let mut handshake = HANDSHAKE_PREFIX.to_vec();
+        handshake.extend_from_slice(&hash_id);
+        handshake.extend_from_slice(&peer_id);
+        Arc::new(Self {
+            torrent,
+            info,
+            hash_id,
+            peer_id,
+            handshake,
+            broker_sender,
+            bandwidth: TorrentBandwidth {
+                torrent: BandwidthLimiter::new(Default::default()),
+                global: Arc::new(BandwidthLimiter::new(Default::default())),
+            },
+            external_ip: Default::default(),
+        })
+    }
 }
@@ -1,6 +1,11 @@
 use crate::{types::info::TorrentInfo, SHA1_SIZE};
+use std::net::IpAddr;
 
 pub(crate) trait TorrentTokenProvider {
     fn info(&self) -> &TorrentInfo;
     fn hash_id(&self) -> &[u8; SHA1_SIZE];
+    /// External address reported by gateway, if any.
+    fn external_ip(&self) -> Option<IpAddr>;
+    /// External port mapped on gateway, if any.
+    fn external_port(&self) -> Option<u16>;
 }
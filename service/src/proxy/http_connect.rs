@@ -0,0 +1,100 @@
+use crate::{types::ProxyConfig, RsbtError};
+use tokio::{net::TcpStream, prelude::*};
+
+/// Proxy response headers larger than this are rejected.
+const HTTP_CONNECT_MAX_RESPONSE: usize = 8192;
+
+/// Opens TCP tunnel through HTTP proxy with CONNECT method.
+pub(crate) async fn http_connect(
+    proxy: &ProxyConfig,
+    host: &str,
+    port: u16,
+) -> Result<TcpStream, RsbtError> {
+    let mut stream = TcpStream::connect((proxy.host.as_str(), proxy.port)).await?;
+
+    let authority = if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority);
+    if let Some((username, password)) = proxy.credentials() {
+        request += &format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            base64::encode(format!("{}:{}", username, password))
+        );
+    }
+    request += "\r\n";
+    stream.write_all(request.as_bytes()).await?;
+
+    // response is read by byte, so no tunneled data is consumed
+    let mut response = vec![];
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= HTTP_CONNECT_MAX_RESPONSE {
+            return Err(RsbtError::ProxyInvalidResponse);
+        }
+        response.push(stream.read_u8().await?);
+    }
+
+    let status = std::str::from_utf8(&response)
+        .ok()
+        .and_then(|x| x.split_whitespace().nth(1))
+        .and_then(|x| x.parse::<u16>().ok())
+        .ok_or(RsbtError::ProxyInvalidResponse)?;
+
+    match status {
+        200..=299 => Ok(stream),
+        407 => Err(RsbtError::ProxyAuthenticationFailure),
+        _ => Err(RsbtError::ProxyConnectFailure(format!(
+            "http status {}",
+            status
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ProxyKind;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn connect_with_password() {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = ProxyConfig {
+            kind: ProxyKind::Http,
+            host: "127.0.0.1".into(),
+            port: listener.local_addr().unwrap().port(),
+            username: Some("user".into()),
+            password: Some("pass".into()),
+            peers: true,
+            trackers: true,
+            refuse_incoming: false,
+        };
+
+        let stand_in = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![];
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\npong")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let mut stream = http_connect(&proxy, "::1", 6969).await.unwrap();
+        let mut data = [0u8; 4];
+        stream.read_exact(&mut data).await.unwrap();
+
+        assert_eq!(&data, b"pong");
+        assert_eq!(
+            stand_in.await.unwrap(),
+            "CONNECT [::1]:6969 HTTP/1.1\r\nHost: [::1]:6969\r\n\
+             Proxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n"
+        );
+    }
+}
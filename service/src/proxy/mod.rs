@@ -0,0 +1,13 @@
+mod http_connect;
+mod proxy_connect;
+mod proxy_connector;
+mod socks5;
+mod socks5_udp_codec;
+mod socks5_udp_framed;
+
+pub(crate) use http_connect::http_connect;
+pub(crate) use proxy_connect::proxy_connect;
+pub(crate) use proxy_connector::ProxyConnector;
+pub(crate) use socks5::{get_socks5_addr, put_socks5_addr, socks5_connect, socks5_udp_associate};
+pub(crate) use socks5_udp_codec::Socks5UdpCodec;
+pub(crate) use socks5_udp_framed::Socks5UdpFramed;
@@ -0,0 +1,22 @@
+use crate::{
+    proxy::{http_connect, socks5_connect},
+    types::{ProxyConfig, ProxyKind},
+    RsbtError, PEER_CONNECT_TIMEOUT,
+};
+use tokio::{net::TcpStream, time::timeout};
+
+/// Opens TCP connection through configured proxy, unresponsive proxy fails by timeout.
+pub(crate) async fn proxy_connect(
+    proxy: &ProxyConfig,
+    host: &str,
+    port: u16,
+) -> Result<TcpStream, RsbtError> {
+    let connect = async {
+        match proxy.kind {
+            ProxyKind::Socks5 => socks5_connect(proxy, host, port).await,
+            ProxyKind::Http => http_connect(proxy, host, port).await,
+        }
+    };
+
+    timeout(PEER_CONNECT_TIMEOUT, connect).await?
+}
@@ -0,0 +1,44 @@
+use crate::{proxy::proxy_connect, types::ProxyConfig};
+use futures::future::BoxFuture;
+use hyper::{service::Service, Uri};
+use std::{
+    error::Error,
+    task::{Context, Poll},
+};
+use tokio::net::TcpStream;
+
+const HTTP_DEFAULT_PORT: u16 = 80;
+
+/// Connector for HTTP client, every connection is tunneled through proxy.
+#[derive(Clone)]
+pub(crate) struct ProxyConnector {
+    proxy: ProxyConfig,
+}
+
+impl ProxyConnector {
+    pub(crate) fn new(proxy: ProxyConfig) -> Self {
+        Self { proxy }
+    }
+}
+
+impl Service<Uri> for ProxyConnector {
+    type Response = TcpStream;
+    type Error = Box<dyn Error + Send + Sync>;
+    type Future = BoxFuture<'static, Result<TcpStream, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let proxy = self.proxy.clone();
+        Box::pin(async move {
+            let host = uri.host().ok_or("uri without host")?;
+            let port = uri.port_u16().unwrap_or(HTTP_DEFAULT_PORT);
+
+            proxy_connect(&proxy, host, port)
+                .await
+                .map_err(|err| err.to_string().into())
+        })
+    }
+}
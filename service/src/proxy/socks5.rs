@@ -0,0 +1,282 @@
+use crate::{types::ProxyConfig, RsbtError};
+use bytes::{Buf, BufMut, BytesMut};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use tokio::{net::TcpStream, prelude::*};
+
+const SOCKS5_VERSION: u8 = 5;
+
+const AUTH_NONE: u8 = 0;
+const AUTH_PASSWORD: u8 = 2;
+const AUTH_PASSWORD_VERSION: u8 = 1;
+
+const COMMAND_CONNECT: u8 = 1;
+const COMMAND_UDP_ASSOCIATE: u8 = 3;
+
+const ADDRESS_IPV4: u8 = 1;
+const ADDRESS_DOMAIN: u8 = 3;
+const ADDRESS_IPV6: u8 = 4;
+
+/// Opens TCP connection through SOCKS5 proxy, host names are resolved by proxy.
+pub(crate) async fn socks5_connect(
+    proxy: &ProxyConfig,
+    host: &str,
+    port: u16,
+) -> Result<TcpStream, RsbtError> {
+    let mut stream = TcpStream::connect((proxy.host.as_str(), proxy.port)).await?;
+
+    socks5_authenticate(&mut stream, proxy).await?;
+    socks5_command(&mut stream, COMMAND_CONNECT, host, port).await?;
+
+    Ok(stream)
+}
+
+/// Requests UDP relay, association lasts while returned control connection is open.
+pub(crate) async fn socks5_udp_associate(
+    proxy: &ProxyConfig,
+) -> Result<(TcpStream, SocketAddr), RsbtError> {
+    let mut stream = TcpStream::connect((proxy.host.as_str(), proxy.port)).await?;
+
+    socks5_authenticate(&mut stream, proxy).await?;
+    let relay = socks5_command(&mut stream, COMMAND_UDP_ASSOCIATE, "0.0.0.0", 0).await?;
+
+    let relay = if relay.ip().is_unspecified() {
+        SocketAddr::new(stream.peer_addr()?.ip(), relay.port())
+    } else {
+        relay
+    };
+
+    Ok((stream, relay))
+}
+
+async fn socks5_authenticate(stream: &mut TcpStream, proxy: &ProxyConfig) -> Result<(), RsbtError> {
+    let credentials = proxy.credentials();
+
+    let methods: &[u8] = if credentials.is_some() {
+        &[AUTH_NONE, AUTH_PASSWORD]
+    } else {
+        &[AUTH_NONE]
+    };
+    let mut greeting = vec![SOCKS5_VERSION, methods.len() as u8];
+    greeting.extend_from_slice(methods);
+    stream.write_all(&greeting).await?;
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != SOCKS5_VERSION {
+        return Err(RsbtError::ProxyInvalidResponse);
+    }
+
+    match (reply[1], credentials) {
+        (AUTH_NONE, _) => Ok(()),
+        (AUTH_PASSWORD, Some((username, password)))
+            if username.len() <= 255 && password.len() <= 255 =>
+        {
+            let mut request = vec![AUTH_PASSWORD_VERSION, username.len() as u8];
+            request.extend_from_slice(username.as_bytes());
+            request.push(password.len() as u8);
+            request.extend_from_slice(password.as_bytes());
+            stream.write_all(&request).await?;
+
+            stream.read_exact(&mut reply).await?;
+            if reply[1] == 0 {
+                Ok(())
+            } else {
+                Err(RsbtError::ProxyAuthenticationFailure)
+            }
+        }
+        _ => Err(RsbtError::ProxyAuthenticationFailure),
+    }
+}
+
+/// Sends command and returns address bound by proxy.
+async fn socks5_command(
+    stream: &mut TcpStream,
+    command: u8,
+    host: &str,
+    port: u16,
+) -> Result<SocketAddr, RsbtError> {
+    let mut request = BytesMut::new();
+    request.put_slice(&[SOCKS5_VERSION, command, 0]);
+    put_socks5_addr(&mut request, host, port)?;
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != SOCKS5_VERSION {
+        return Err(RsbtError::ProxyInvalidResponse);
+    }
+    if reply[1] != 0 {
+        return Err(RsbtError::ProxyConnectFailure(
+            socks5_reply_message(reply[1]).into(),
+        ));
+    }
+
+    let ip = match reply[3] {
+        ADDRESS_IPV4 => {
+            let mut octets = [0u8; 4];
+            stream.read_exact(&mut octets).await?;
+            IpAddr::from(octets)
+        }
+        ADDRESS_IPV6 => {
+            let mut octets = [0u8; 16];
+            stream.read_exact(&mut octets).await?;
+            IpAddr::from(octets)
+        }
+        ADDRESS_DOMAIN => {
+            let mut domain = vec![0u8; stream.read_u8().await? as usize];
+            stream.read_exact(&mut domain).await?;
+            Ipv4Addr::UNSPECIFIED.into()
+        }
+        _ => return Err(RsbtError::ProxyInvalidResponse),
+    };
+    let port = stream.read_u16().await?;
+
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Writes address in SOCKS5 format, anything except IP address is sent as domain name.
+pub(crate) fn put_socks5_addr(buf: &mut BytesMut, host: &str, port: u16) -> Result<(), RsbtError> {
+    match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(IpAddr::V4(ip)) => {
+            buf.put_u8(ADDRESS_IPV4);
+            buf.put_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            buf.put_u8(ADDRESS_IPV6);
+            buf.put_slice(&ip.octets());
+        }
+        Err(_) if host.len() <= 255 => {
+            buf.put_u8(ADDRESS_DOMAIN);
+            buf.put_u8(host.len() as u8);
+            buf.put_slice(host.as_bytes());
+        }
+        Err(_) => {
+            return Err(RsbtError::ProxyConnectFailure(format!(
+                "host name {} is too long",
+                host
+            )))
+        }
+    }
+    buf.put_u16(port);
+
+    Ok(())
+}
+
+/// Reads IP address in SOCKS5 format, domain names are not expected from relay.
+pub(crate) fn get_socks5_addr(buf: &mut BytesMut) -> Option<SocketAddr> {
+    if buf.is_empty() {
+        return None;
+    }
+
+    let ip = match buf.get_u8() {
+        ADDRESS_IPV4 if buf.len() >= 6 => {
+            let mut octets = [0u8; 4];
+            buf.copy_to_slice(&mut octets);
+            IpAddr::from(octets)
+        }
+        ADDRESS_IPV6 if buf.len() >= 18 => {
+            let mut octets = [0u8; 16];
+            buf.copy_to_slice(&mut octets);
+            IpAddr::from(octets)
+        }
+        _ => return None,
+    };
+
+    Some(SocketAddr::new(ip, buf.get_u16()))
+}
+
+fn socks5_reply_message(reply: u8) -> &'static str {
+    match reply {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "ttl expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ProxyKind;
+    use tokio::net::TcpListener;
+
+    /// Minimal SOCKS5 server, accepts one connection with password and echoes data.
+    async fn socks5_stand_in(mut listener: TcpListener) -> (String, u16) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        let mut greeting = [0u8; 4];
+        stream.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(greeting, [SOCKS5_VERSION, 2, AUTH_NONE, AUTH_PASSWORD]);
+        stream
+            .write_all(&[SOCKS5_VERSION, AUTH_PASSWORD])
+            .await
+            .unwrap();
+
+        let mut auth = [0u8; 11];
+        stream.read_exact(&mut auth).await.unwrap();
+        assert_eq!(&auth, b"\x01\x04user\x04pass");
+        stream.write_all(&[AUTH_PASSWORD_VERSION, 0]).await.unwrap();
+
+        let mut request = [0u8; 5];
+        stream.read_exact(&mut request).await.unwrap();
+        assert_eq!(
+            request,
+            [SOCKS5_VERSION, COMMAND_CONNECT, 0, ADDRESS_DOMAIN, 11]
+        );
+        let mut host = vec![0u8; request[4] as usize];
+        stream.read_exact(&mut host).await.unwrap();
+        let port = stream.read_u16().await.unwrap();
+        stream
+            .write_all(&[SOCKS5_VERSION, 0, 0, ADDRESS_IPV4, 127, 0, 0, 1, 0x1f, 0x90])
+            .await
+            .unwrap();
+
+        let mut data = [0u8; 4];
+        stream.read_exact(&mut data).await.unwrap();
+        stream.write_all(&data).await.unwrap();
+
+        (String::from_utf8(host).unwrap(), port)
+    }
+
+    #[tokio::test]
+    async fn connect_with_password() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = ProxyConfig {
+            kind: ProxyKind::Socks5,
+            host: "127.0.0.1".into(),
+            port: listener.local_addr().unwrap().port(),
+            username: Some("user".into()),
+            password: Some("pass".into()),
+            peers: true,
+            trackers: true,
+            refuse_incoming: false,
+        };
+        let stand_in = tokio::spawn(socks5_stand_in(listener));
+
+        let mut stream = socks5_connect(&proxy, "example.org", 6969).await.unwrap();
+        stream.write_all(b"ping").await.unwrap();
+        let mut data = [0u8; 4];
+        stream.read_exact(&mut data).await.unwrap();
+
+        assert_eq!(&data, b"ping");
+        assert_eq!(stand_in.await.unwrap(), ("example.org".into(), 6969));
+    }
+
+    #[test]
+    fn socks5_addr_roundtrip() {
+        let addr: SocketAddr = "[2001:db8::1]:6881".parse().unwrap();
+        let mut buf = BytesMut::new();
+        put_socks5_addr(&mut buf, &addr.ip().to_string(), addr.port()).unwrap();
+        assert_eq!(get_socks5_addr(&mut buf), Some(addr));
+        assert!(buf.is_empty());
+    }
+}
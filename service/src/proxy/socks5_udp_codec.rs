@@ -0,0 +1,99 @@
+use crate::proxy::{get_socks5_addr, put_socks5_addr};
+use bytes::{Buf, BufMut, BytesMut};
+use std::{io, net::SocketAddr};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Datagram codec with SOCKS5 UDP request header, items are paired with remote address.
+pub(crate) struct Socks5UdpCodec<C> {
+    inner: C,
+    /// Remote host name resolved by proxy, only port of item address is used with it.
+    host: Option<String>,
+}
+
+impl<C> Socks5UdpCodec<C> {
+    pub(crate) fn new(inner: C, host: Option<String>) -> Self {
+        Self { inner, host }
+    }
+}
+
+impl<C> Decoder for Socks5UdpCodec<C>
+where
+    C: Decoder,
+{
+    type Item = (C::Item, SocketAddr);
+    type Error = C::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        // reserved bytes and fragment number, fragmented datagrams are not supported
+        if buf.len() < 3 || buf[2] != 0 {
+            return Err(invalid_header().into());
+        }
+        buf.advance(3);
+
+        let addr = get_socks5_addr(buf).ok_or_else(invalid_header)?;
+
+        Ok(self.inner.decode(buf)?.map(|item| (item, addr)))
+    }
+}
+
+impl<C, I> Encoder<(I, SocketAddr)> for Socks5UdpCodec<C>
+where
+    C: Encoder<I>,
+{
+    type Error = C::Error;
+
+    fn encode(
+        &mut self,
+        (item, addr): (I, SocketAddr),
+        buf: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        buf.put_slice(&[0, 0, 0]);
+        match &self.host {
+            Some(host) => put_socks5_addr(buf, host, addr.port()),
+            None => put_socks5_addr(buf, &addr.ip().to_string(), addr.port()),
+        }
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+        self.inner.encode(item, buf)
+    }
+}
+
+fn invalid_header() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid socks5 udp header")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_util::codec::BytesCodec;
+
+    #[test]
+    fn socks5_udp_roundtrip() {
+        let addr: SocketAddr = "10.0.0.1:6969".parse().unwrap();
+        let mut codec = Socks5UdpCodec::new(BytesCodec::new(), None);
+
+        let mut buf = BytesMut::new();
+        codec
+            .encode((bytes::Bytes::from_static(b"data"), addr), &mut buf)
+            .unwrap();
+        assert_eq!(&buf[..], b"\x00\x00\x00\x01\x0a\x00\x00\x01\x1b\x39data");
+
+        let (data, decoded_addr) = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(&data[..], b"data");
+        assert_eq!(decoded_addr, addr);
+
+        let mut fragmented = BytesMut::from(&b"\x00\x00\x01\x01\x0a\x00\x00\x01\x1b\x39data"[..]);
+        assert!(codec.decode(&mut fragmented).is_err());
+    }
+
+    #[test]
+    fn socks5_udp_host_name() {
+        let addr: SocketAddr = "0.0.0.0:6969".parse().unwrap();
+        let mut codec = Socks5UdpCodec::new(BytesCodec::new(), Some("tracker.org".into()));
+
+        let mut buf = BytesMut::new();
+        codec
+            .encode((bytes::Bytes::from_static(b"data"), addr), &mut buf)
+            .unwrap();
+        assert_eq!(&buf[..], b"\x00\x00\x00\x03\x0btracker.org\x1b\x39data");
+    }
+}
@@ -0,0 +1,73 @@
+use crate::proxy::Socks5UdpCodec;
+use futures::{Sink, Stream};
+use std::{
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio_util::{
+    codec::{Decoder, Encoder},
+    udp::UdpFramed,
+};
+
+/// Datagrams sent through SOCKS5 UDP relay, addresses are of remote side.
+pub(crate) struct Socks5UdpFramed<C> {
+    framed: UdpFramed<Socks5UdpCodec<C>>,
+    relay: SocketAddr,
+    /// Association is closed by proxy when control connection is dropped.
+    _control: TcpStream,
+}
+
+impl<C> Socks5UdpFramed<C> {
+    pub(crate) fn new(
+        udp_socket: UdpSocket,
+        codec: C,
+        relay: SocketAddr,
+        control: TcpStream,
+        host: Option<String>,
+    ) -> Self {
+        Self {
+            framed: UdpFramed::new(udp_socket, Socks5UdpCodec::new(codec, host)),
+            relay,
+            _control: control,
+        }
+    }
+}
+
+impl<C> Stream for Socks5UdpFramed<C>
+where
+    C: Decoder + Unpin,
+{
+    type Item = Result<(C::Item, SocketAddr), C::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().framed)
+            .poll_next(cx)
+            .map(|x| x.map(|x| x.map(|(item, _relay)| item)))
+    }
+}
+
+impl<C, I> Sink<(I, SocketAddr)> for Socks5UdpFramed<C>
+where
+    C: Encoder<I> + Unpin,
+{
+    type Error = C::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().framed).poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: (I, SocketAddr)) -> Result<(), Self::Error> {
+        let pin = self.get_mut();
+        Pin::new(&mut pin.framed).start_send((item, pin.relay))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().framed).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().framed).poll_close(cx)
+    }
+}
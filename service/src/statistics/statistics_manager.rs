@@ -23,6 +23,7 @@ impl StatisticsManager {
             TorrentDownloadState {
                 downloaded: storage_state.bytes_write,
                 uploaded: storage_state.bytes_read,
+                hash_fails: 0,
             }
         };
         let (watch_sender, watch_receiver) = watch::channel(torrent_download_state.clone());
@@ -79,6 +80,12 @@ impl EventLoopRunner<TorrentStatisticMessage, TorrentEvent> for StatisticsManage
                     error!("cannot broadcast downloaded torrent statistics: {}", err);
                 }
             }
+            TorrentStatisticMessage::HashFailed => {
+                self.torrent_download_state.hash_fails += 1;
+                if let Err(err) = self.watch_sender.broadcast(self.torrent_download_state) {
+                    error!("cannot broadcast hash fails torrent statistics: {}", err);
+                }
+            }
         }
         Ok(())
     }
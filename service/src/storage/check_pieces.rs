@@ -0,0 +1,94 @@
+use crate::{
+    storage::{TorrentFlatStorage, CHECK_PIECES_BATCH},
+    types::info::PieceChecksum,
+    RsbtError,
+};
+use flat_storage::{index_in_bitarray, FlatStorage};
+use futures::future::try_join_all;
+use sha1::{Digest, Sha1};
+use std::sync::Arc;
+use tokio::task::spawn_blocking;
+
+/// Hashes stored pieces on blocking pool and returns bitfield of valid ones.
+///
+/// `progress` is called with count of checked pieces after each batch.
+pub(crate) async fn check_pieces<F>(
+    storage: Arc<TorrentFlatStorage>,
+    pieces: &[PieceChecksum],
+    mut progress: F,
+) -> Result<Vec<u8>, RsbtError>
+where
+    F: FnMut(u32),
+{
+    let mut downloaded = vec![0u8; pieces.len().div_ceil(8)];
+
+    for (batch, checksums) in pieces.chunks(CHECK_PIECES_BATCH).enumerate() {
+        let first = batch * CHECK_PIECES_BATCH;
+        let checks = checksums.iter().enumerate().map(|(offset, checksum)| {
+            let storage = storage.clone();
+            let checksum = checksum.clone();
+            spawn_blocking(move || {
+                storage
+                    .read_piece(first + offset)
+                    .map(|data| data.is_some_and(|data| Sha1::digest(&data)[..] == checksum.0[..]))
+            })
+        });
+
+        for (offset, valid) in try_join_all(checks).await?.into_iter().enumerate() {
+            if valid? {
+                let (block_index, bit) = index_in_bitarray(first + offset);
+                downloaded[block_index] |= bit;
+            }
+        }
+
+        progress((first + checksums.len()) as u32);
+    }
+
+    if downloaded.iter().all(|&x| x == 0) {
+        downloaded.clear();
+    }
+
+    Ok(downloaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flat_storage::FlatStorageFile;
+    use flat_storage_memory::MemoryFlatStorage;
+    use std::convert::TryInto;
+
+    #[tokio::test]
+    async fn check_stored_pieces() {
+        let files = vec![
+            FlatStorageFile {
+                path: "a".into(),
+                length: 10,
+            },
+            FlatStorageFile {
+                path: "b".into(),
+                length: 15,
+            },
+        ];
+        let storage = Arc::new(TorrentFlatStorage::Memory(MemoryFlatStorage::create(
+            10, files,
+        )));
+
+        let data: Vec<u8> = (0..25).collect();
+        let pieces: Vec<PieceChecksum> = data
+            .chunks(10)
+            .map(|x| PieceChecksum(Sha1::digest(x)[..].try_into().unwrap()))
+            .collect();
+
+        storage.write_piece(0, data[0..10].to_vec()).unwrap();
+        storage.write_piece(2, data[20..25].to_vec()).unwrap();
+
+        let mut checked = vec![];
+        let downloaded = check_pieces(storage.clone(), &pieces, |x| checked.push(x))
+            .await
+            .unwrap();
+
+        assert_eq!(downloaded, vec![0b1010_0000]);
+        assert_eq!(checked.last(), Some(&3));
+    }
+}
@@ -1,10 +1,31 @@
+mod check_pieces;
+mod move_file;
+mod move_task;
+mod piece_priorities;
+mod state_saver;
+mod torrent_flat_storage;
 mod torrent_piece;
 mod torrent_storage;
 mod torrent_storage_message;
 mod torrent_storage_state;
 
-const TORRENT_STORAGE_FORMAT_VERSION: u8 = 0;
+const TORRENT_STORAGE_FORMAT_VERSION: u8 = 1;
+/// Saved pieces after which storage state is saved.
+const STATE_SAVE_PIECES: usize = 32;
+/// Storage state with unsaved changes is saved at least this often.
+const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+/// Pieces hashed in parallel while checking.
+const CHECK_PIECES_BATCH: usize = 8;
+/// Progress of moved data is reported this often.
+const MOVE_PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
 
+use check_pieces::check_pieces;
+use move_file::{move_file, remove_empty_dirs};
+use move_task::MoveTask;
+use piece_priorities::{piece_priorities, wanted_pieces_left};
+use state_saver::StateSaver;
+use std::time::Duration;
+use torrent_flat_storage::TorrentFlatStorage;
 pub use torrent_piece::TorrentPiece;
 pub use torrent_storage::TorrentStorage;
 use torrent_storage_message::TorrentStorageMessage;
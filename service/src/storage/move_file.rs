@@ -0,0 +1,123 @@
+use log::debug;
+use std::{
+    fs::{create_dir_all, remove_dir, remove_file, rename, File},
+    io::{self, Read, Write},
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Size of chunk copied at once when file is moved across filesystems.
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Moves file, it is copied and deleted if rename fails, e.g. across filesystems.
+///
+/// Count of moved bytes is added to `moved` while file is copied.
+/// Returns `false` if there is nothing to move, existing target is not overwritten.
+pub(crate) fn move_file(from: &Path, to: &Path, moved: &AtomicU64) -> io::Result<bool> {
+    if !from.is_file() {
+        return Ok(false);
+    }
+    if to.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to.to_string_lossy()),
+        ));
+    }
+    if let Some(parent) = to.parent() {
+        create_dir_all(parent)?;
+    }
+
+    match rename(from, to) {
+        Ok(()) => {
+            moved.fetch_add(to.metadata()?.len(), Ordering::Relaxed);
+        }
+        Err(err) => {
+            debug!("cannot rename {:?}: {}, copying", from, err);
+            if let Err(err) = copy_file(from, to, moved) {
+                let _ = remove_file(to);
+                return Err(err);
+            }
+            remove_file(from)?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Copies file in chunks, copy is synced before source may be deleted.
+fn copy_file(from: &Path, to: &Path, moved: &AtomicU64) -> io::Result<()> {
+    let mut source = File::open(from)?;
+    let mut target = File::create(to)?;
+    let mut buffer = vec![0; COPY_CHUNK_SIZE];
+    loop {
+        let len = source.read(&mut buffer)?;
+        if len == 0 {
+            break;
+        }
+        target.write_all(&buffer[..len])?;
+        moved.fetch_add(len as u64, Ordering::Relaxed);
+    }
+    target.sync_all()
+}
+
+/// Removes empty directories from `path` up to `root`.
+pub(crate) fn remove_empty_dirs(root: &Path, path: &Path) {
+    for dir in path
+        .ancestors()
+        .take_while(|x| *x != root && x.starts_with(root))
+    {
+        if remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{read, write};
+
+    #[test]
+    fn check_move_file() -> io::Result<()> {
+        let root = std::env::temp_dir().join(format!("rsbt-move-file-{}", std::process::id()));
+        let from = root.join("from").join("dir").join("a");
+        let to = root.join("to").join("dir").join("a");
+        create_dir_all(from.parent().unwrap())?;
+        write(&from, b"data")?;
+
+        let moved = AtomicU64::new(0);
+        assert!(move_file(&from, &to, &moved)?);
+        assert!(!from.exists());
+        assert_eq!(read(&to)?, b"data");
+        assert_eq!(moved.load(Ordering::Relaxed), 4);
+        assert!(!move_file(&from, &to, &moved)?);
+
+        write(&from, b"other")?;
+        assert!(move_file(&from, &to, &moved).is_err());
+        assert!(from.exists());
+        remove_file(&from)?;
+
+        remove_empty_dirs(&root.join("from"), from.parent().unwrap());
+        assert!(!root.join("from").join("dir").exists());
+        assert!(root.join("from").exists());
+
+        std::fs::remove_dir_all(&root)
+    }
+
+    #[test]
+    fn check_copy_file() -> io::Result<()> {
+        let root = std::env::temp_dir().join(format!("rsbt-copy-file-{}", std::process::id()));
+        create_dir_all(&root)?;
+        let from = root.join("a");
+        let to = root.join("b");
+        let data = vec![7; COPY_CHUNK_SIZE + 10];
+        write(&from, &data)?;
+
+        let moved = AtomicU64::new(0);
+        copy_file(&from, &to, &moved)?;
+        assert_eq!(read(&to)?, data);
+        assert_eq!(moved.load(Ordering::Relaxed), data.len() as u64);
+
+        std::fs::remove_dir_all(&root)
+    }
+}
@@ -0,0 +1,55 @@
+use crate::{storage::TorrentStorageState, RsbtError};
+use flat_storage::FlatStorageFile;
+use log::error;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::task::JoinHandle;
+
+/// Data files moved in background, storage loop serves other messages meanwhile.
+pub(crate) struct MoveTask {
+    /// Data directory after successful move.
+    pub(crate) save_to: PathBuf,
+    /// Data files after successful move.
+    pub(crate) files: Vec<FlatStorageFile>,
+    /// Directory for complete download after successful move.
+    pub(crate) complete_to: Option<PathBuf>,
+    pub(crate) moved_bytes: Arc<AtomicU64>,
+    pub(crate) handle: JoinHandle<Result<(), RsbtError>>,
+}
+
+impl MoveTask {
+    pub(crate) fn moved_bytes(&self) -> u64 {
+        self.moved_bytes.load(Ordering::Relaxed)
+    }
+
+    pub(crate) async fn finished(&mut self) -> Result<(), RsbtError> {
+        (&mut self.handle).await?
+    }
+
+    /// Data location is changed on success, reason of failure is kept in `move_error`.
+    pub(crate) fn apply(
+        self,
+        result: Result<(), RsbtError>,
+        state: &mut TorrentStorageState,
+        files: &mut Vec<FlatStorageFile>,
+    ) {
+        state.moving = None;
+        match result {
+            Ok(()) => {
+                state.save_to = self.save_to;
+                state.complete_to = self.complete_to;
+                state.move_error = None;
+                *files = self.files;
+            }
+            Err(err) => {
+                error!("cannot move torrent data: {}", err);
+                state.move_error = Some(err.to_string());
+            }
+        }
+    }
+}
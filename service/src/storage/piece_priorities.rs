@@ -0,0 +1,75 @@
+use crate::types::public::FilePriority;
+use flat_storage::{bit_by_index, FlatStorageMapping};
+
+/// Priority of piece is the highest priority of its files.
+pub(crate) fn piece_priorities(
+    mapping: &[FlatStorageMapping],
+    file_priorities: &[FilePriority],
+) -> Vec<FilePriority> {
+    mapping
+        .iter()
+        .map(|x| {
+            x.0.iter()
+                .map(|block| {
+                    file_priorities
+                        .get(block.file_index)
+                        .cloned()
+                        .unwrap_or_default()
+                })
+                .max()
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Count of wanted pieces, which are not downloaded.
+pub(crate) fn wanted_pieces_left(downloaded: &[u8], piece_priorities: &[FilePriority]) -> u32 {
+    piece_priorities
+        .iter()
+        .enumerate()
+        .filter(|(index, &priority)| {
+            priority != FilePriority::Skip && bit_by_index(*index, downloaded).is_none()
+        })
+        .count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flat_storage::{map_pieces_to_files, FlatStorageFile};
+
+    #[test]
+    fn check_piece_priorities() {
+        let mapping = map_pieces_to_files(
+            10,
+            &[
+                FlatStorageFile {
+                    path: "a".into(),
+                    length: 15,
+                },
+                FlatStorageFile {
+                    path: "b".into(),
+                    length: 10,
+                },
+                FlatStorageFile {
+                    path: "c".into(),
+                    length: 10,
+                },
+            ],
+        );
+
+        let priorities = piece_priorities(&mapping, &[FilePriority::Skip, FilePriority::High]);
+        assert_eq!(
+            priorities,
+            vec![
+                FilePriority::Skip,
+                FilePriority::High,
+                FilePriority::High,
+                FilePriority::Normal
+            ]
+        );
+
+        assert_eq!(wanted_pieces_left(&[], &priorities), 3);
+        assert_eq!(wanted_pieces_left(&[0b1100_0000], &priorities), 2);
+    }
+}
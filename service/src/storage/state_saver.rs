@@ -0,0 +1,102 @@
+use crate::{
+    storage::{TorrentFlatStorage, TorrentStorageState, STATE_SAVE_INTERVAL, STATE_SAVE_PIECES},
+    RsbtError,
+};
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::task::spawn_blocking;
+
+/// Coalesces saves of storage state.
+///
+/// State is saved after `STATE_SAVE_PIECES` saved pieces or `STATE_SAVE_INTERVAL` after
+/// first unsaved change, nothing is saved without state file.
+pub(crate) struct StateSaver {
+    state_file: Option<PathBuf>,
+    unsaved_pieces: usize,
+    unsaved_since: Option<Instant>,
+}
+
+impl StateSaver {
+    pub(crate) fn new(state_file: Option<PathBuf>) -> Self {
+        Self {
+            state_file,
+            unsaved_pieces: 0,
+            unsaved_since: None,
+        }
+    }
+
+    /// Remembers unsaved change, `piece` tells if a piece was saved.
+    pub(crate) fn changed(&mut self, piece: bool) {
+        if self.state_file.is_none() {
+            return;
+        }
+        if piece {
+            self.unsaved_pieces += 1;
+        }
+        self.unsaved_since.get_or_insert_with(Instant::now);
+    }
+
+    pub(crate) fn is_due(&self) -> bool {
+        self.unsaved_pieces >= STATE_SAVE_PIECES
+            || self
+                .unsaved_since
+                .is_some_and(|x| x.elapsed() >= STATE_SAVE_INTERVAL)
+    }
+
+    /// Time left until unsaved changes must be saved, `None` if everything is saved.
+    pub(crate) fn due_in(&self) -> Option<Duration> {
+        self.unsaved_since.map(|x| {
+            STATE_SAVE_INTERVAL
+                .checked_sub(x.elapsed())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Flushes data before state is saved, so state never claims pieces which are not on disk.
+    pub(crate) async fn save(
+        &mut self,
+        storage: Arc<TorrentFlatStorage>,
+        state: &TorrentStorageState,
+    ) -> Result<(), RsbtError> {
+        self.unsaved_pieces = 0;
+        self.unsaved_since = None;
+        if let Some(state_file) = &self.state_file {
+            spawn_blocking(move || storage.flush()).await??;
+            state.save(state_file).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_state_saver() {
+        let mut state_saver = StateSaver::new(None);
+        state_saver.changed(true);
+        assert_eq!(state_saver.due_in(), None);
+
+        let mut state_saver = StateSaver::new(Some("a.torrent.state".into()));
+        assert_eq!(state_saver.due_in(), None);
+        assert!(!state_saver.is_due());
+
+        state_saver.changed(false);
+        assert!(state_saver.due_in().is_some());
+        assert!(!state_saver.is_due());
+
+        for _ in 0..STATE_SAVE_PIECES {
+            state_saver.changed(true);
+        }
+        assert!(state_saver.is_due());
+
+        state_saver.unsaved_pieces = 0;
+        state_saver.unsaved_since = Instant::now().checked_sub(STATE_SAVE_INTERVAL);
+        assert!(state_saver.is_due());
+        assert_eq!(state_saver.due_in(), Some(Duration::default()));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::types::{info::TorrentInfo, Properties, StorageBackend};
+use flat_storage::{
+    FileInfo, FlatStorage, FlatStorageError, FlatStorageFile, FlatStoragePieceIndex,
+};
+use flat_storage_file::FileFlatStorage;
+use flat_storage_memory::MemoryFlatStorage;
+use flat_storage_mmap::MmapFlatStorage;
+use std::path::Path;
+
+/// Storage of torrent data, selected by `[storage] backend` setting.
+pub(crate) enum TorrentFlatStorage {
+    Mmap(MmapFlatStorage),
+    File(FileFlatStorage),
+    Memory(MemoryFlatStorage),
+}
+
+impl TorrentFlatStorage {
+    /// Skipped files are not created, shared blocks with other files are kept in `part_file`.
+    ///
+    /// Layout of pieces is taken from `info`, `files` may be renamed.
+    pub(crate) fn create(
+        properties: &Properties,
+        download_path: &Path,
+        info: &TorrentInfo,
+        files: Vec<FlatStorageFile>,
+        downloaded: &[u8],
+        skipped: &[bool],
+        part_file: &Path,
+    ) -> Result<Self, std::io::Error> {
+        let piece_count = info.pieces.len();
+        let piece_size = info.piece_length;
+        Ok(match properties.storage_backend {
+            StorageBackend::Mmap => Self::Mmap(MmapFlatStorage::create(
+                download_path,
+                piece_count,
+                piece_size,
+                files,
+                downloaded,
+                skipped,
+                part_file,
+            )?),
+            StorageBackend::File => Self::File(FileFlatStorage::create(
+                download_path,
+                piece_count,
+                piece_size,
+                files,
+                downloaded,
+                skipped,
+                part_file,
+                properties.max_open_files,
+            )?),
+            StorageBackend::Memory => Self::Memory(MemoryFlatStorage::create(piece_size, files)),
+        })
+    }
+
+    /// Flushes written data to disk, before state file claims pieces are saved.
+    pub(crate) fn flush(&self) -> Result<(), std::io::Error> {
+        match self {
+            Self::Mmap(storage) => storage.flush(),
+            Self::File(storage) => storage.flush(),
+            Self::Memory(_) => Ok(()),
+        }
+    }
+
+    /// Flushes and closes data files before they are moved, memory storage is kept.
+    pub(crate) fn close(&self) -> Result<(), std::io::Error> {
+        match self {
+            Self::Mmap(storage) => storage.close(),
+            Self::File(storage) => storage.close(),
+            Self::Memory(_) => Ok(()),
+        }
+    }
+
+    pub(crate) fn delete_files<P: AsRef<Path>>(
+        &self,
+        download_path: P,
+    ) -> Result<(), std::io::Error> {
+        match self {
+            Self::Mmap(storage) => storage.delete_files(download_path),
+            Self::File(storage) => storage.delete_files(download_path),
+            Self::Memory(storage) => storage.delete_files(download_path),
+        }
+    }
+
+    pub(crate) fn saved(&self) -> Vec<usize> {
+        match self {
+            Self::Mmap(storage) => storage.saved(),
+            Self::File(storage) => storage.saved(),
+            Self::Memory(storage) => storage.saved(),
+        }
+    }
+
+    pub(crate) fn set_downloaded(&self, downloaded: &[u8]) {
+        match self {
+            Self::Mmap(storage) => storage.set_downloaded(downloaded),
+            Self::File(storage) => storage.set_downloaded(downloaded),
+            Self::Memory(storage) => storage.set_downloaded(downloaded),
+        }
+    }
+
+    pub(crate) fn set_skipped(
+        &self,
+        file_index: usize,
+        skipped: bool,
+    ) -> Result<(), std::io::Error> {
+        match self {
+            Self::Mmap(storage) => storage.set_skipped(file_index, skipped),
+            Self::File(storage) => storage.set_skipped(file_index, skipped),
+            Self::Memory(storage) => storage.set_skipped(file_index, skipped),
+        }
+    }
+
+    pub(crate) fn file_info(&self, file_id: usize) -> Option<FileInfo> {
+        match self {
+            Self::Mmap(storage) => storage.file_info(file_id),
+            Self::File(storage) => storage.file_info(file_id),
+            Self::Memory(storage) => storage.file_info(file_id),
+        }
+    }
+}
+
+impl FlatStorage for TorrentFlatStorage {
+    fn files(&self) -> &[FlatStorageFile] {
+        match self {
+            Self::Mmap(storage) => storage.files(),
+            Self::File(storage) => storage.files(),
+            Self::Memory(storage) => storage.files(),
+        }
+    }
+
+    fn read_piece<I: Into<FlatStoragePieceIndex>>(
+        &self,
+        index: I,
+    ) -> Result<Option<Vec<u8>>, FlatStorageError> {
+        match self {
+            Self::Mmap(storage) => storage.read_piece(index),
+            Self::File(storage) => storage.read_piece(index),
+            Self::Memory(storage) => storage.read_piece(index),
+        }
+    }
+
+    fn write_piece<I: Into<FlatStoragePieceIndex>>(
+        &self,
+        index: I,
+        block: Vec<u8>,
+    ) -> Result<(), FlatStorageError> {
+        match self {
+            Self::Mmap(storage) => storage.write_piece(index, block),
+            Self::File(storage) => storage.write_piece(index, block),
+            Self::Memory(storage) => storage.write_piece(index, block),
+        }
+    }
+}
@@ -1,20 +1,28 @@
 use crate::process::TorrentToken;
 use crate::{
     file_download::{FileDownloadState, FileDownloadStream},
-    storage::{TorrentPiece, TorrentStorageMessage, TorrentStorageState},
-    types::{public::FileView, Properties},
+    storage::{
+        check_pieces, move_file, piece_priorities, remove_empty_dirs, wanted_pieces_left, MoveTask,
+        StateSaver, TorrentFlatStorage, TorrentPiece, TorrentStorageMessage, TorrentStorageState,
+        MOVE_PROGRESS_INTERVAL,
+    },
+    types::{
+        info::{PieceChecksum, TorrentInfo},
+        public::{FilePriority, FileView},
+        Properties, StorageBackend,
+    },
     RsbtError, DEFAULT_CHANNEL_BUFFER,
 };
 use failure::ResultExt;
-use flat_storage::{index_in_bitarray, FlatStorage};
-use flat_storage_mmap::MmapFlatStorage;
-use futures::StreamExt;
-use log::{debug, error};
+use flat_storage::{index_in_bitarray, map_pieces_to_files, FlatStorage, FlatStorageFile};
+use futures::{future, StreamExt};
+use log::{debug, error, warn};
 use std::{
     ops::Range,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{atomic::AtomicU64, Arc, Mutex},
     thread,
+    time::Duration,
 };
 use tokio::{
     fs,
@@ -24,6 +32,7 @@ use tokio::{
         oneshot, watch,
     },
     task::spawn_blocking,
+    time::delay_for,
 };
 
 #[derive(Debug)]
@@ -35,19 +44,40 @@ pub struct TorrentStorage {
 }
 
 impl TorrentStorage {
+    /// Torrent data is stored in `save_to` directory, or in incomplete directory until download is complete.
+    ///
+    /// Data files are stored as `files`, which may be renamed from torrent.
     pub async fn new<P: AsRef<Path>>(
         properties: Arc<Properties>,
         torrent_name: P,
         torrent_process: Arc<TorrentToken>,
+        mut file_priorities: Vec<FilePriority>,
+        save_to: PathBuf,
+        files: Vec<FlatStorageFile>,
     ) -> Result<Self, RsbtError> {
-        let (state_file, state) = prepare_storage_state(
+        let (save_to, complete_to) = data_dir(&properties, &files, save_to);
+        let (mut state, check) = prepare_storage_state(
             properties.clone(),
             torrent_name.as_ref(),
             torrent_process.clone(),
+            &save_to,
+            &files,
         )
         .await?;
         let (sender, channel_receiver) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
 
+        if check {
+            state.checking = Some(0);
+        }
+        state.save_to = save_to;
+        state.complete_to = complete_to;
+
+        let info = &torrent_process.info;
+        file_priorities.resize(info.files.len(), FilePriority::default());
+        let mapping = map_pieces_to_files(info.piece_length, &info.files);
+        state.piece_priorities = Arc::new(piece_priorities(&mapping, &file_priorities));
+        state.pieces_left = wanted_pieces_left(&state.downloaded, &state.piece_priorities);
+
         let (watch_sender, receiver) = watch::channel(state.clone());
 
         let thread_torrent_process = torrent_process.clone();
@@ -60,7 +90,8 @@ impl TorrentStorage {
                 thread_torrent_process,
                 thread_torrent_name,
                 state,
-                state_file,
+                file_priorities,
+                files,
                 channel_receiver,
                 watch_sender,
             ) {
@@ -109,6 +140,49 @@ impl TorrentStorage {
             .await
     }
 
+    /// Verifies stored data against piece hashes in background.
+    pub async fn recheck(&self) -> Result<(), RsbtError> {
+        self.sender
+            .clone()
+            .send(TorrentStorageMessage::Recheck)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_file_priority(
+        &self,
+        file_id: usize,
+        priority: FilePriority,
+    ) -> Result<(), RsbtError> {
+        self.message(|sender| TorrentStorageMessage::SetFilePriority {
+            file_id,
+            priority,
+            sender,
+        })
+        .await
+    }
+
+    /// Starts moving torrent data to `save_to`, response is sent once `moving` is broadcasted.
+    ///
+    /// Progress and failure are sent to watchers, pieces are not saved or loaded until files are moved.
+    pub async fn move_to(&self, save_to: PathBuf) -> Result<(), RsbtError> {
+        self.message(|sender| TorrentStorageMessage::Move { save_to, sender })
+            .await
+    }
+
+    /// Starts renaming data files on disk, response is sent once `moving` is broadcasted.
+    ///
+    /// Storage is opened with new paths when files are renamed, failure is sent to watchers.
+    pub async fn rename(&self, files: Vec<FlatStorageFile>) -> Result<(), RsbtError> {
+        self.message(|sender| TorrentStorageMessage::Rename { files, sender })
+            .await
+    }
+
+    /// Saves state with pending changes, data is flushed first.
+    pub async fn save_state(&self) -> Result<(), RsbtError> {
+        self.message(TorrentStorageMessage::SaveState).await
+    }
+
     pub async fn files(&self) -> Result<Vec<FileView>, RsbtError> {
         self.message(TorrentStorageMessage::Files).await
     }
@@ -135,6 +209,8 @@ impl TorrentStorage {
         } else {
             (file_size, file_info.piece, file_info.piece_offset)
         };
+        let last_piece =
+            piece + (piece_offset + size.max(1) - 1) / self.torrent_process.info.piece_length;
         Ok(FileDownloadStream {
             name: file_info.file.path.to_string_lossy().into(),
             file_size,
@@ -146,6 +222,8 @@ impl TorrentStorage {
             state: FileDownloadState::Idle,
             torrent_process: self.torrent_process.clone(),
             waker: Arc::new(Mutex::new(None)),
+            last_piece,
+            alive: Arc::new(()),
         })
     }
 }
@@ -160,21 +238,68 @@ async fn cleanup_storage_state<P: AsRef<Path>>(
         fs::remove_file(&storage_torrent_file).await?;
     }
 
-    let mut torrent_storage_state_file = storage_torrent_file.clone();
-    torrent_storage_state_file.set_extension("torrent.state");
+    let torrent_storage_state_file = state_file_path(&properties, &torrent_name);
 
     if torrent_storage_state_file.is_file() {
         fs::remove_file(&torrent_storage_state_file).await?;
     }
 
+    let part_file = part_file_path(&properties, torrent_name);
+    if part_file.is_file() {
+        fs::remove_file(&part_file).await?;
+    }
+
     Ok(())
 }
 
+/// Data is kept in incomplete directory, unless it is already in `save_to`.
+///
+/// Returns directory of data and directory to move it to on completion.
+fn data_dir(
+    properties: &Properties,
+    files: &[FlatStorageFile],
+    save_to: PathBuf,
+) -> (PathBuf, Option<PathBuf>) {
+    match &properties.incomplete_dir {
+        Some(incomplete_dir)
+            if properties.storage_backend != StorageBackend::Memory
+                && !files.iter().any(|x| save_to.join(&x.path).is_file()) =>
+        {
+            (incomplete_dir.clone(), Some(save_to))
+        }
+        _ => (save_to, None),
+    }
+}
+
+fn state_file_path<P: AsRef<Path>>(properties: &Properties, torrent_name: P) -> PathBuf {
+    let mut state_file = properties.storage.join(torrent_name.as_ref());
+    state_file.set_extension("torrent.state");
+    state_file
+}
+
+/// Blocks of skipped files, which are shared with wanted files.
+fn part_file_path<P: AsRef<Path>>(properties: &Properties, torrent_name: P) -> PathBuf {
+    let mut part_file = properties.storage.join(torrent_name.as_ref());
+    part_file.set_extension("torrent.parts");
+    part_file
+}
+
+/// Loads or creates storage state, also tells if existing data must be checked.
+///
+/// State of memory storage is not saved.
 async fn prepare_storage_state<P: AsRef<Path>>(
     properties: Arc<Properties>,
     torrent_name: P,
     torrent_process: Arc<TorrentToken>,
-) -> Result<(PathBuf, TorrentStorageState), RsbtError> {
+    save_to: &Path,
+    files: &[FlatStorageFile],
+) -> Result<(TorrentStorageState, bool), RsbtError> {
+    let pieces_count = torrent_process.info.pieces.len() as u32;
+
+    if properties.storage_backend == StorageBackend::Memory {
+        return Ok((TorrentStorageState::new(pieces_count), false));
+    }
+
     let storage_torrent_file = properties.storage.join(torrent_name.as_ref());
 
     if !storage_torrent_file.is_file() {
@@ -189,8 +314,9 @@ async fn prepare_storage_state<P: AsRef<Path>>(
             })?;
     }
 
-    let mut torrent_storage_state_file = storage_torrent_file.clone();
-    torrent_storage_state_file.set_extension("torrent.state");
+    let torrent_storage_state_file = state_file_path(&properties, &torrent_name);
+
+    let mut check = false;
 
     let torrent_storage_state = if torrent_storage_state_file.is_file() {
         debug!("loading state from: {:?}", torrent_storage_state_file);
@@ -203,43 +329,252 @@ async fn prepare_storage_state<P: AsRef<Path>>(
                     err
                 )
             })?;
-        let state = TorrentStorageState::from_reader(data.as_slice())?;
-        debug!("loaded state: {:?}", state);
-        state
+        match TorrentStorageState::from_reader(data.as_slice()) {
+            Ok(state) => {
+                debug!("loaded state: {:?}", state);
+                state
+            }
+            Err(err) => {
+                warn!(
+                    "cannot load state from {:?}: {}, data is checked",
+                    torrent_storage_state_file, err
+                );
+                check = true;
+                TorrentStorageState::new(pieces_count)
+            }
+        }
     } else {
         debug!("creating new state in: {:?}", torrent_storage_state_file);
-        let state = TorrentStorageState {
-            downloaded: vec![],
-            bytes_write: 0,
-            bytes_read: 0,
-            pieces_left: torrent_process.info.pieces.len() as u32,
-        };
+        let state = TorrentStorageState::new(pieces_count);
         state.save(&torrent_storage_state_file).await?;
+        check = files.iter().any(|x| save_to.join(&x.path).is_file());
         state
     };
-    Ok((torrent_storage_state_file, torrent_storage_state))
+    Ok((torrent_storage_state, check))
 }
 
+#[allow(clippy::too_many_arguments)]
 fn torrent_storage_message_loop(
     properties: Arc<Properties>,
     torrent_process: Arc<TorrentToken>,
     torrent_name: PathBuf,
     mut state: TorrentStorageState,
-    state_file: PathBuf,
+    mut file_priorities: Vec<FilePriority>,
+    mut files: Vec<FlatStorageFile>,
     mut channel_receiver: Receiver<TorrentStorageMessage>,
     watch_sender: watch::Sender<TorrentStorageState>,
 ) -> Result<(), RsbtError> {
     let info = &torrent_process.info;
-    let mut rt = Builder::new().basic_scheduler().enable_io().build()?;
-    let mmap_storage = Arc::new(MmapFlatStorage::create(
-        properties.save_to.clone(),
-        info.pieces.len(),
-        info.piece_length,
-        info.files.clone(),
-        &state.downloaded,
+    let mapping = map_pieces_to_files(info.piece_length, &info.files);
+    let mut state_saver =
+        StateSaver::new(if properties.storage_backend == StorageBackend::Memory {
+            None
+        } else {
+            Some(state_file_path(&properties, &torrent_name))
+        });
+    let part_file = part_file_path(&properties, &torrent_name);
+    let mut rt = Builder::new()
+        .basic_scheduler()
+        .enable_io()
+        .enable_time()
+        .build()?;
+    let mut data_storage = Arc::new(open_storage(
+        &properties,
+        info,
+        &files,
+        &state,
+        &file_priorities,
+        &part_file,
     )?);
     rt.block_on(async move {
-        while let Some(message) = channel_receiver.next().await {
+        if state.checking.is_some() {
+            if let Err(err) = recheck_storage(
+                data_storage.clone(),
+                &info.pieces,
+                &mut state,
+                &mut state_saver,
+                &watch_sender,
+            )
+            .await
+            {
+                error!("cannot check torrent data: {}", err);
+            }
+        }
+
+        let mut move_task: Option<MoveTask> = None;
+        let mut recheck_after_move = false;
+        // data which cannot be opened after move is not accessed until it is opened again
+        let mut storage_closed = false;
+
+        loop {
+            if move_task.is_none() {
+                if recheck_after_move && !storage_closed {
+                    recheck_after_move = false;
+                    if let Err(err) = recheck_storage(
+                        data_storage.clone(),
+                        &info.pieces,
+                        &mut state,
+                        &mut state_saver,
+                        &watch_sender,
+                    )
+                    .await
+                    {
+                        error!("cannot check torrent data: {}", err);
+                    }
+                }
+                if state.pieces_left == 0 && state.checking.is_none() {
+                    if let Some(complete_to) = state.complete_to.take() {
+                        debug!("download is complete, moving to {:?}", complete_to);
+                        move_task = Some(start_move(
+                            data_storage.clone(),
+                            &files,
+                            &mut state,
+                            complete_to,
+                            files.clone(),
+                            None,
+                            &watch_sender,
+                        ));
+                    }
+                }
+            }
+
+            // progress of move is reported while waiting for messages
+            let wait = [
+                state_saver.due_in(),
+                move_task.as_ref().map(|_| MOVE_PROGRESS_INTERVAL),
+            ]
+            .iter()
+            .flatten()
+            .min()
+            .cloned();
+            let message = tokio::select! {
+                message = channel_receiver.next() => message,
+                result = move_finished(&mut move_task) => {
+                    if let Some(task) = move_task.take() {
+                        task.apply(result, &mut state, &mut files);
+                    }
+                    storage_closed = !reopen_storage(
+                        &properties,
+                        info,
+                        &files,
+                        &mut state,
+                        &file_priorities,
+                        &part_file,
+                        &mut data_storage,
+                    );
+                    if let Err(err) = watch_sender.broadcast(state.clone()) {
+                        error!("cannot notify watchers: {}", err);
+                    }
+                    continue;
+                }
+                _ = delay(wait) => {
+                    if let Some(task) = &move_task {
+                        state.moving = Some(task.moved_bytes());
+                        if let Err(err) = watch_sender.broadcast(state.clone()) {
+                            error!("cannot notify watchers: {}", err);
+                        }
+                    }
+                    if state_saver.is_due() {
+                        if let Err(err) = state_saver.save(data_storage.clone(), &state).await {
+                            error!("cannot save state: {}", err);
+                        }
+                    }
+                    continue;
+                }
+            };
+            let message = if let Some(message) = message {
+                message
+            } else {
+                // moved data location must be saved in state
+                if let Some(mut task) = move_task.take() {
+                    let result = task.finished().await;
+                    task.apply(result, &mut state, &mut files);
+                    state_saver.changed(false);
+                }
+                if state_saver.due_in().is_some() {
+                    if let Err(err) = state_saver.save(data_storage.clone(), &state).await {
+                        error!("cannot save state: {}", err);
+                    }
+                }
+                break;
+            };
+
+            // data files are closed while moved, so they are not accessed until move is finished
+            if move_task.is_some() {
+                match message {
+                    TorrentStorageMessage::SavePiece { sender, .. }
+                    | TorrentStorageMessage::SetFilePriority { sender, .. } => {
+                        if sender.send(Err(RsbtError::TorrentDataMoving)).is_err() {
+                            error!("cannot send moving result with oneshot message");
+                        }
+                        continue;
+                    }
+                    TorrentStorageMessage::LoadPiece { sender, .. } => {
+                        if sender.send(Err(RsbtError::TorrentDataMoving)).is_err() {
+                            error!("cannot send piece with oneshot message");
+                        }
+                        continue;
+                    }
+                    TorrentStorageMessage::Move { sender, .. }
+                    | TorrentStorageMessage::Rename { sender, .. } => {
+                        if sender.send(Err(RsbtError::TorrentDataMoving)).is_err() {
+                            error!("cannot send move result with oneshot message");
+                        }
+                        continue;
+                    }
+                    TorrentStorageMessage::Recheck => {
+                        debug!("data is checked after move");
+                        recheck_after_move = true;
+                        continue;
+                    }
+                    _ => (),
+                }
+            }
+
+            if storage_closed
+                && matches!(
+                    message,
+                    TorrentStorageMessage::SavePiece { .. }
+                        | TorrentStorageMessage::LoadPiece { .. }
+                        | TorrentStorageMessage::SetFilePriority { .. }
+                        | TorrentStorageMessage::Recheck
+                )
+            {
+                match open_storage(
+                    &properties,
+                    info,
+                    &files,
+                    &state,
+                    &file_priorities,
+                    &part_file,
+                ) {
+                    Ok(storage) => {
+                        data_storage = Arc::new(storage);
+                        storage_closed = false;
+                    }
+                    Err(err) => {
+                        error!("cannot open storage: {}", err);
+                        // piece is not marked as downloaded, so it is requested again
+                        match message {
+                            TorrentStorageMessage::SavePiece { sender, .. }
+                            | TorrentStorageMessage::SetFilePriority { sender, .. } => {
+                                if sender.send(Err(err.into())).is_err() {
+                                    error!("cannot send closed result with oneshot message");
+                                }
+                                continue;
+                            }
+                            TorrentStorageMessage::LoadPiece { sender, .. } => {
+                                if sender.send(Err(err.into())).is_err() {
+                                    error!("cannot send piece with oneshot message");
+                                }
+                                continue;
+                            }
+                            _ => continue,
+                        }
+                    }
+                }
+            }
+
             match message {
                 TorrentStorageMessage::SavePiece {
                     index,
@@ -248,7 +583,7 @@ fn torrent_storage_message_loop(
                 } => {
                     let (block_index, bit) = index_in_bitarray(index);
 
-                    let storage = mmap_storage.clone();
+                    let storage = data_storage.clone();
                     let len = data.len();
 
                     match spawn_blocking(move || storage.write_piece(index, data)).await? {
@@ -266,13 +601,18 @@ fn torrent_storage_message_loop(
                         state.downloaded.push(0);
                     }
                     if state.downloaded[block_index] & bit == 0 {
-                        state.pieces_left -= 1;
+                        if state.piece_priorities.get(index) != Some(&FilePriority::Skip) {
+                            state.pieces_left -= 1;
+                        }
                         state.bytes_write += len as u64;
                     }
                     state.downloaded[block_index] |= bit;
 
-                    if let Err(err) = state.save(&state_file).await {
-                        error!("cannot save state: {}", err);
+                    state_saver.changed(true);
+                    if state_saver.is_due() {
+                        if let Err(err) = state_saver.save(data_storage.clone(), &state).await {
+                            error!("cannot save state: {}", err);
+                        }
                     }
                     if let Err(err) = watch_sender.broadcast(state.clone()) {
                         error!("cannot notify watchers: {}", err);
@@ -283,7 +623,7 @@ fn torrent_storage_message_loop(
                     }
                 }
                 TorrentStorageMessage::LoadPiece { index, sender } => {
-                    let storage = mmap_storage.clone();
+                    let storage = data_storage.clone();
 
                     let piece = match spawn_blocking(move || storage.read_piece(index)).await? {
                         Ok(data) => data.map(TorrentPiece),
@@ -300,8 +640,11 @@ fn torrent_storage_message_loop(
                         state.bytes_read += piece.as_ref().len() as u64;
                     }
 
-                    if let Err(err) = state.save(&state_file).await {
-                        error!("cannot save state: {}", err);
+                    state_saver.changed(false);
+                    if state_saver.is_due() {
+                        if let Err(err) = state_saver.save(data_storage.clone(), &state).await {
+                            error!("cannot save state: {}", err);
+                        }
                     }
                     if let Err(err) = watch_sender.broadcast(state.clone()) {
                         error!("cannot notify watchers: {}", err);
@@ -311,14 +654,30 @@ fn torrent_storage_message_loop(
                         error!("cannot send piece with oneshot message");
                     }
                 }
-                TorrentStorageMessage::Delete { files, sender } => {
+                TorrentStorageMessage::Delete {
+                    files: delete_files,
+                    sender,
+                } => {
+                    // data is deleted where move has left it
+                    if let Some(mut task) = move_task.take() {
+                        let result = task.finished().await;
+                        task.apply(result, &mut state, &mut files);
+                        reopen_storage(
+                            &properties,
+                            info,
+                            &files,
+                            &mut state,
+                            &file_priorities,
+                            &part_file,
+                            &mut data_storage,
+                        );
+                    }
                     let mut result = cleanup_storage_state(properties.clone(), torrent_name).await;
-                    if files {
-                        let storage = mmap_storage.clone();
+                    if delete_files {
+                        let storage = data_storage.clone();
+                        let save_to = state.save_to.clone();
                         result = spawn_blocking(move || {
-                            storage
-                                .delete_files(properties.save_to.clone())
-                                .map_err(RsbtError::from)
+                            storage.delete_files(save_to).map_err(RsbtError::from)
                         })
                         .await?;
                     }
@@ -327,21 +686,138 @@ fn torrent_storage_message_loop(
                     }
                     break;
                 }
+                TorrentStorageMessage::Recheck => {
+                    if let Err(err) = recheck_storage(
+                        data_storage.clone(),
+                        &info.pieces,
+                        &mut state,
+                        &mut state_saver,
+                        &watch_sender,
+                    )
+                    .await
+                    {
+                        error!("cannot check torrent data: {}", err);
+                    }
+                }
+                TorrentStorageMessage::SetFilePriority {
+                    file_id,
+                    priority,
+                    sender,
+                } => {
+                    if file_id >= file_priorities.len() {
+                        if sender
+                            .send(Err(RsbtError::TorrentFileNotFound(file_id)))
+                            .is_err()
+                        {
+                            error!("cannot send file priority result with oneshot message");
+                        }
+                        continue;
+                    }
+
+                    let storage = data_storage.clone();
+                    let skipped = priority == FilePriority::Skip;
+                    if let Err(err) =
+                        spawn_blocking(move || storage.set_skipped(file_id, skipped)).await?
+                    {
+                        error!("cannot change file priority: {}", err);
+                        if sender.send(Err(err.into())).is_err() {
+                            error!("cannot send file priority result with oneshot message");
+                        }
+                        continue;
+                    }
+
+                    file_priorities[file_id] = priority;
+                    state.piece_priorities = Arc::new(piece_priorities(&mapping, &file_priorities));
+                    state.pieces_left =
+                        wanted_pieces_left(&state.downloaded, &state.piece_priorities);
+
+                    if let Err(err) = state_saver.save(data_storage.clone(), &state).await {
+                        error!("cannot save state: {}", err);
+                    }
+                    if let Err(err) = watch_sender.broadcast(state.clone()) {
+                        error!("cannot notify watchers: {}", err);
+                    }
+
+                    if sender.send(Ok(())).is_err() {
+                        error!("cannot send file priority result with oneshot message");
+                    }
+                }
+                TorrentStorageMessage::Move { save_to, sender } => {
+                    if properties.storage_backend == StorageBackend::Memory {
+                        state.save_to = save_to;
+                        if let Err(err) = watch_sender.broadcast(state.clone()) {
+                            error!("cannot notify watchers: {}", err);
+                        }
+                        if sender.send(Ok(())).is_err() {
+                            error!("cannot send move result with oneshot message");
+                        }
+                        continue;
+                    }
+
+                    // response is sent once moving is visible to watchers, outcome is reported by state
+                    move_task = Some(start_move(
+                        data_storage.clone(),
+                        &files,
+                        &mut state,
+                        save_to,
+                        files.clone(),
+                        None,
+                        &watch_sender,
+                    ));
+                    if sender.send(Ok(())).is_err() {
+                        error!("cannot send move result with oneshot message");
+                    }
+                }
+                TorrentStorageMessage::Rename {
+                    files: renamed,
+                    sender,
+                } => {
+                    if properties.storage_backend == StorageBackend::Memory {
+                        files = renamed;
+                        if sender.send(Ok(())).is_err() {
+                            error!("cannot send rename result with oneshot message");
+                        }
+                        continue;
+                    }
+
+                    // response is sent once moving is visible to watchers, outcome is reported by state
+                    let save_to = state.save_to.clone();
+                    let complete_to = state.complete_to.clone();
+                    move_task = Some(start_move(
+                        data_storage.clone(),
+                        &files,
+                        &mut state,
+                        save_to,
+                        renamed,
+                        complete_to,
+                        &watch_sender,
+                    ));
+                    if sender.send(Ok(())).is_err() {
+                        error!("cannot send rename result with oneshot message");
+                    }
+                }
+                TorrentStorageMessage::SaveState(sender) => {
+                    let result = state_saver.save(data_storage.clone(), &state).await;
+                    if sender.send(result).is_err() {
+                        error!("cannot send save state result with oneshot message");
+                    }
+                }
                 TorrentStorageMessage::Files(sender) => {
-                    let storage = mmap_storage.clone();
+                    let storage = data_storage.clone();
                     let saved = spawn_blocking(move || storage.saved())
                         .await
                         .map_err(RsbtError::from);
                     let files_view = saved.map(|saved| {
                         saved
                             .into_iter()
-                            .zip(info.files.iter())
+                            .zip(files.iter())
                             .enumerate()
-                            .map(|(id, (saved, info))| FileView {
+                            .map(|(id, (saved, file))| FileView {
                                 id,
-                                name: info.path.to_string_lossy().into(),
+                                name: file.path.to_string_lossy().into(),
                                 saved,
-                                size: info.length,
+                                size: file.length,
+                                priority: file_priorities[id],
                             })
                             .collect()
                     });
@@ -350,7 +826,7 @@ fn torrent_storage_message_loop(
                     }
                 }
                 TorrentStorageMessage::FileInfo { file_id, sender } => {
-                    let storage = mmap_storage.clone();
+                    let storage = data_storage.clone();
                     let file_info = spawn_blocking(move || storage.file_info(file_id))
                         .await
                         .map_err(RsbtError::from)
@@ -370,3 +846,559 @@ fn torrent_storage_message_loop(
 
     Ok(())
 }
+
+/// Rebuilds downloaded pieces from data on disk, progress is sent to watchers.
+async fn recheck_storage(
+    storage: Arc<TorrentFlatStorage>,
+    pieces: &[PieceChecksum],
+    state: &mut TorrentStorageState,
+    state_saver: &mut StateSaver,
+    watch_sender: &watch::Sender<TorrentStorageState>,
+) -> Result<(), RsbtError> {
+    debug!("checking {} pieces", pieces.len());
+
+    state.checking = Some(0);
+    if let Err(err) = watch_sender.broadcast(state.clone()) {
+        error!("cannot notify watchers: {}", err);
+    }
+
+    let downloaded = check_pieces(storage.clone(), pieces, |checked| {
+        state.checking = Some(checked);
+        if let Err(err) = watch_sender.broadcast(state.clone()) {
+            error!("cannot notify watchers: {}", err);
+        }
+    })
+    .await;
+
+    state.checking = None;
+
+    if let Ok(downloaded) = &downloaded {
+        state.pieces_left = wanted_pieces_left(downloaded, &state.piece_priorities);
+        state.downloaded = downloaded.clone();
+        storage.set_downloaded(&state.downloaded);
+        debug!("checked, pieces left: {}", state.pieces_left);
+    }
+
+    if let Err(err) = watch_sender.broadcast(state.clone()) {
+        error!("cannot notify watchers: {}", err);
+    }
+
+    downloaded?;
+
+    state_saver.save(storage, state).await
+}
+
+/// Starts moving data `files` to `save_to` as `moved_files`, start of move is sent to watchers.
+///
+/// Download is moved to `complete_to` when complete after successful move.
+fn start_move(
+    storage: Arc<TorrentFlatStorage>,
+    files: &[FlatStorageFile],
+    state: &mut TorrentStorageState,
+    save_to: PathBuf,
+    moved_files: Vec<FlatStorageFile>,
+    complete_to: Option<PathBuf>,
+    watch_sender: &watch::Sender<TorrentStorageState>,
+) -> MoveTask {
+    state.moving = Some(0);
+    state.move_error = None;
+    if let Err(err) = watch_sender.broadcast(state.clone()) {
+        error!("cannot notify watchers: {}", err);
+    }
+
+    let moved_bytes = Arc::new(AtomicU64::new(0));
+    let handle = tokio::spawn(move_files(
+        storage,
+        files.to_vec(),
+        state.save_to.clone(),
+        moved_files.clone(),
+        save_to.clone(),
+        moved_bytes.clone(),
+    ));
+
+    MoveTask {
+        save_to,
+        files: moved_files,
+        complete_to,
+        moved_bytes,
+        handle,
+    }
+}
+
+/// Completes when move is finished, never completes without move.
+async fn move_finished(move_task: &mut Option<MoveTask>) -> Result<(), RsbtError> {
+    match move_task {
+        Some(move_task) => move_task.finished().await,
+        None => future::pending().await,
+    }
+}
+
+/// Completes after `duration`, never completes without it.
+async fn delay(duration: Option<Duration>) {
+    match duration {
+        Some(duration) => delay_for(duration).await,
+        None => future::pending().await,
+    }
+}
+
+/// Moves `files` in `from` to `save_to` as `moved_files`, on failure already moved files are moved back.
+async fn move_files(
+    storage: Arc<TorrentFlatStorage>,
+    files: Vec<FlatStorageFile>,
+    from_dir: PathBuf,
+    moved_files: Vec<FlatStorageFile>,
+    save_to: PathBuf,
+    moved_bytes: Arc<AtomicU64>,
+) -> Result<(), RsbtError> {
+    if save_to == from_dir && files == moved_files {
+        return Ok(());
+    }
+    debug!("moving {:?} to {:?}", from_dir, save_to);
+
+    spawn_blocking(move || storage.close()).await??;
+
+    let mut moved = vec![];
+    let mut result = Ok(());
+    for (file, moved_file) in files.iter().zip(&moved_files) {
+        let from = from_dir.join(&file.path);
+        let to = save_to.join(&moved_file.path);
+        let (task_from, task_to, task_moved_bytes) =
+            (from.clone(), to.clone(), moved_bytes.clone());
+        match spawn_blocking(move || move_file(&task_from, &task_to, &task_moved_bytes)).await? {
+            Ok(true) => moved.push((from, to)),
+            Ok(false) => (),
+            Err(err) => {
+                result = Err(err).with_context(|err| {
+                    format!(
+                        "cannot move {} to {}: {}",
+                        from.to_string_lossy(),
+                        to.to_string_lossy(),
+                        err
+                    )
+                });
+                break;
+            }
+        }
+    }
+
+    // directories left empty are removed, at old path on success and at new path on failure
+    let left_root = if result.is_ok() {
+        from_dir
+    } else {
+        for (from, to) in moved.iter_mut().rev() {
+            let (task_from, task_to) = (from.clone(), to.clone());
+            if let Err(err) =
+                spawn_blocking(move || move_file(&task_to, &task_from, &AtomicU64::new(0))).await?
+            {
+                error!("cannot move file back: {}", err);
+            }
+            std::mem::swap(from, to);
+        }
+        save_to
+    };
+    spawn_blocking(move || {
+        for (from, _) in moved {
+            if let Some(parent) = from.parent() {
+                remove_empty_dirs(&left_root, parent);
+            }
+        }
+    })
+    .await?;
+
+    result.map_err(|x| x.into())
+}
+
+/// Opens data files after move, failure is kept in `move_error`.
+///
+/// Returns `false` if data cannot be opened, closed storage is kept.
+fn reopen_storage(
+    properties: &Properties,
+    info: &TorrentInfo,
+    files: &[FlatStorageFile],
+    state: &mut TorrentStorageState,
+    file_priorities: &[FilePriority],
+    part_file: &Path,
+    data_storage: &mut Arc<TorrentFlatStorage>,
+) -> bool {
+    match open_storage(properties, info, files, state, file_priorities, part_file) {
+        Ok(storage) => {
+            *data_storage = Arc::new(storage);
+            true
+        }
+        Err(err) => {
+            error!("cannot open moved storage: {}", err);
+            state.move_error.get_or_insert_with(|| err.to_string());
+            false
+        }
+    }
+}
+
+/// Opens data files at current directory of storage state.
+fn open_storage(
+    properties: &Properties,
+    info: &TorrentInfo,
+    files: &[FlatStorageFile],
+    state: &TorrentStorageState,
+    file_priorities: &[FilePriority],
+    part_file: &Path,
+) -> Result<TorrentFlatStorage, std::io::Error> {
+    TorrentFlatStorage::create(
+        properties,
+        &state.save_to,
+        info,
+        files.to_vec(),
+        &state.downloaded,
+        &file_priorities
+            .iter()
+            .map(|&x| x == FilePriority::Skip)
+            .collect::<Vec<_>>(),
+        part_file,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Settings;
+    use tokio::time::timeout;
+    use uuid::Uuid;
+
+    fn sample_properties(root: &Path) -> Properties {
+        let mut properties = Properties::from((Settings::default(), root.to_path_buf()));
+        properties.storage_backend = StorageBackend::File;
+        properties
+    }
+
+    /// Storage of sample torrent with data files in `save_to`.
+    async fn sample_storage(properties: Properties, save_to: PathBuf) -> TorrentStorage {
+        let (broker_sender, _) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        open_sample(properties, TorrentToken::sample(broker_sender), save_to).await
+    }
+
+    async fn open_sample(
+        properties: Properties,
+        torrent_process: Arc<TorrentToken>,
+        save_to: PathBuf,
+    ) -> TorrentStorage {
+        std::fs::create_dir_all(&properties.storage).unwrap();
+
+        let files = sample_files(&torrent_process.info);
+        TorrentStorage::new(
+            Arc::new(properties),
+            "ferris.gif",
+            torrent_process,
+            vec![],
+            save_to,
+            files,
+        )
+        .await
+        .unwrap()
+    }
+
+    fn sample_files(info: &TorrentInfo) -> Vec<FlatStorageFile> {
+        info.files
+            .iter()
+            .map(|x| FlatStorageFile {
+                path: x.path.clone(),
+                length: x.length,
+            })
+            .collect()
+    }
+
+    /// Saves all pieces of sample torrent, saved pieces are returned.
+    async fn save_pieces(storage: &TorrentStorage) -> Vec<Vec<u8>> {
+        let info = &storage.torrent_process.info;
+        let pieces = vec![vec![1; info.piece_length], vec![2; info.last_piece_length]];
+        for (index, piece) in pieces.iter().enumerate() {
+            storage.save(index, piece.clone()).await.unwrap();
+        }
+        pieces
+    }
+
+    /// Waits for storage state matching `f`.
+    async fn wait_state(
+        storage: &mut TorrentStorage,
+        f: impl Fn(&TorrentStorageState) -> bool,
+    ) -> TorrentStorageState {
+        let receiver = &mut storage.receiver;
+        let state = async {
+            while let Some(state) = receiver.recv().await {
+                if f(&state) {
+                    return state;
+                }
+            }
+            panic!("storage is closed");
+        };
+        timeout(Duration::from_secs(5), state).await.unwrap()
+    }
+
+    /// Waits until data is not moved anymore.
+    async fn moved(storage: &mut TorrentStorage) -> TorrentStorageState {
+        wait_state(storage, |x| x.moving.is_none()).await
+    }
+
+    /// Closes storage, pending state is saved before data directory is removed.
+    async fn close(storage: TorrentStorage) {
+        let TorrentStorage { handle, sender, .. } = storage;
+        drop(sender);
+        spawn_blocking(move || handle.join().unwrap().unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn check_data_dir() {
+        let root = std::env::temp_dir().join(format!("rsbt-data-dir-{}", Uuid::new_v4()));
+        let (save_to, incomplete_dir) = (root.join("save"), root.join("incomplete"));
+        let mut properties = sample_properties(&root);
+        let (broker_sender, _) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        let files = sample_files(&TorrentToken::sample(broker_sender).info);
+
+        assert_eq!(
+            data_dir(&properties, &files, save_to.clone()),
+            (save_to.clone(), None)
+        );
+
+        properties.incomplete_dir = Some(incomplete_dir.clone());
+        assert_eq!(
+            data_dir(&properties, &files, save_to.clone()),
+            (incomplete_dir, Some(save_to.clone()))
+        );
+
+        // data already in save_to stays there
+        std::fs::create_dir_all(&save_to).unwrap();
+        std::fs::write(save_to.join("ferris.gif"), b"data").unwrap();
+        assert_eq!(
+            data_dir(&properties, &files, save_to.clone()),
+            (save_to.clone(), None)
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+
+        properties.storage_backend = StorageBackend::Memory;
+        assert_eq!(
+            data_dir(&properties, &files, save_to.clone()),
+            (save_to, None)
+        );
+    }
+
+    #[tokio::test]
+    async fn complete_download_is_moved() {
+        let root = std::env::temp_dir().join(format!("rsbt-storage-complete-{}", Uuid::new_v4()));
+        let (save_to, incomplete_dir) = (root.join("save"), root.join("incomplete"));
+        let mut properties = sample_properties(&root);
+        properties.incomplete_dir = Some(incomplete_dir.clone());
+        let mut storage = sample_storage(properties, save_to.clone()).await;
+        assert_eq!(storage.receiver.borrow().save_to, incomplete_dir);
+
+        let pieces = save_pieces(&storage).await;
+        let state = wait_state(&mut storage, |x| {
+            x.pieces_left == 0 && x.complete_to.is_none() && x.moving.is_none()
+        })
+        .await;
+        assert_eq!(state.save_to, save_to);
+        assert_eq!(state.move_error, None);
+        assert!(!incomplete_dir.join("ferris.gif").exists());
+        assert!(save_to.join("ferris.gif").is_file());
+        let piece = storage.load(0).await.unwrap().unwrap();
+        assert_eq!(piece.as_ref(), pieces[0].as_slice());
+
+        close(storage).await;
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn streamed_piece_of_skipped_file_is_stored() {
+        for backend in [StorageBackend::Mmap, StorageBackend::File] {
+            let root = std::env::temp_dir().join(format!("rsbt-storage-skip-{}", Uuid::new_v4()));
+            let save_to = root.join("save");
+            let mut properties = sample_properties(&root);
+            properties.storage_backend = backend;
+            std::fs::create_dir_all(&properties.storage).unwrap();
+            let (broker_sender, _) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+            let torrent_process = TorrentToken::sample(broker_sender);
+            let files = sample_files(&torrent_process.info);
+            let storage = TorrentStorage::new(
+                Arc::new(properties),
+                "ferris.gif",
+                torrent_process,
+                vec![FilePriority::Skip],
+                save_to.clone(),
+                files,
+            )
+            .await
+            .unwrap();
+            assert!(!save_to.join("ferris.gif").exists());
+
+            // skipped file has no part file slots, so it is created for streamed piece
+            let piece = vec![1; storage.torrent_process.info.piece_length];
+            storage.save(0, piece.clone()).await.unwrap();
+            assert!(save_to.join("ferris.gif").is_file());
+            let loaded = storage.load(0).await.unwrap().unwrap();
+            assert_eq!(loaded.as_ref(), piece.as_slice());
+
+            close(storage).await;
+            std::fs::remove_dir_all(&root).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn storage_move() {
+        let root = std::env::temp_dir().join(format!("rsbt-storage-move-{}", Uuid::new_v4()));
+        let (from, to) = (root.join("from"), root.join("to"));
+        let mut storage = sample_storage(sample_properties(&root), from.clone()).await;
+        let length = storage.torrent_process.info.length;
+        let pieces = save_pieces(&storage).await;
+
+        storage.move_to(to.clone()).await.unwrap();
+        let state = moved(&mut storage).await;
+        assert_eq!(state.save_to, to);
+        assert_eq!(state.move_error, None);
+        assert!(!from.join("ferris.gif").exists());
+        assert_eq!(
+            std::fs::metadata(to.join("ferris.gif")).unwrap().len(),
+            length as u64
+        );
+        let piece = storage.load(1).await.unwrap().unwrap();
+        assert_eq!(piece.as_ref(), pieces[1].as_slice());
+
+        // existing file is not overwritten, data stays where it is
+        std::fs::write(from.join("ferris.gif"), b"other").unwrap();
+        storage.move_to(from.clone()).await.unwrap();
+        let state = moved(&mut storage).await;
+        assert_eq!(state.save_to, to);
+        assert!(state.move_error.is_some());
+        let piece = storage.load(0).await.unwrap().unwrap();
+        assert_eq!(piece.as_ref(), pieces[0].as_slice());
+
+        close(storage).await;
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn storage_is_served_while_moving() {
+        let root = std::env::temp_dir().join(format!("rsbt-storage-serve-{}", Uuid::new_v4()));
+        let (from, to) = (root.join("from"), root.join("to"));
+        let mut storage = sample_storage(sample_properties(&root), from.clone()).await;
+        let pieces = save_pieces(&storage).await;
+
+        // messages queued behind move are answered without waiting for data
+        let (moved_to, piece, files) = timeout(Duration::from_secs(5), async {
+            futures::join!(
+                storage.move_to(to.clone()),
+                storage.load(0),
+                storage.files()
+            )
+        })
+        .await
+        .unwrap();
+        moved_to.unwrap();
+        match piece {
+            Ok(piece) => assert_eq!(piece.unwrap().as_ref(), pieces[0].as_slice()),
+            Err(err) => assert!(matches!(err, RsbtError::TorrentDataMoving)),
+        }
+        assert_eq!(files.unwrap()[0].saved, storage.torrent_process.info.length);
+
+        let state = moved(&mut storage).await;
+        assert_eq!(state.save_to, to);
+        let piece = storage.load(0).await.unwrap().unwrap();
+        assert_eq!(piece.as_ref(), pieces[0].as_slice());
+
+        close(storage).await;
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn pieces_are_rejected_until_moved_data_is_opened() {
+        let root = std::env::temp_dir().join(format!("rsbt-storage-reopen-{}", Uuid::new_v4()));
+        let (from, to) = (root.join("from"), root.join("to"));
+        let mut properties = sample_properties(&root);
+        properties.storage_backend = StorageBackend::Mmap;
+        let mut storage = sample_storage(properties, from.clone()).await;
+
+        // data file is mapped when storage serves messages
+        storage.files().await.unwrap();
+        // nothing is moved, data file cannot be mapped at new path
+        std::fs::remove_file(from.join("ferris.gif")).unwrap();
+        std::fs::create_dir_all(to.join("ferris.gif")).unwrap();
+        storage.move_to(to.clone()).await.unwrap();
+        let state = moved(&mut storage).await;
+        assert_eq!(state.save_to, to);
+        assert!(state.move_error.is_some());
+
+        let piece = vec![1; storage.torrent_process.info.piece_length];
+        assert!(storage.save(0, piece.clone()).await.is_err());
+        assert!(storage.load(0).await.is_err());
+        let state = storage.receiver.borrow().clone();
+        assert!(state.downloaded.iter().all(|&x| x == 0));
+        assert_eq!(state.pieces_left, 2);
+        assert!(!from.join("ferris.gif").exists());
+
+        // storage is opened again with next piece
+        std::fs::remove_dir(to.join("ferris.gif")).unwrap();
+        storage.save(0, piece.clone()).await.unwrap();
+        let loaded = storage.load(0).await.unwrap().unwrap();
+        assert_eq!(loaded.as_ref(), piece.as_slice());
+        assert_eq!(storage.receiver.borrow().pieces_left, 1);
+
+        close(storage).await;
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn storage_rename() {
+        let root = std::env::temp_dir().join(format!("rsbt-storage-rename-{}", Uuid::new_v4()));
+        let save_to = root.join("save");
+        // sample torrent is split to two files, one for each piece
+        let (broker_sender, _) = mpsc::channel(DEFAULT_CHANNEL_BUFFER);
+        let mut torrent_process = Arc::try_unwrap(TorrentToken::sample(broker_sender)).unwrap();
+        let info = &mut torrent_process.info;
+        info.files = vec![
+            FlatStorageFile {
+                path: PathBuf::from("a"),
+                length: info.piece_length,
+            },
+            FlatStorageFile {
+                path: PathBuf::from("b"),
+                length: info.last_piece_length,
+            },
+        ];
+        let files = info.files.clone();
+        let mut storage = open_sample(
+            sample_properties(&root),
+            Arc::new(torrent_process),
+            save_to.clone(),
+        )
+        .await;
+        let pieces = save_pieces(&storage).await;
+
+        let renamed: Vec<_> = files
+            .iter()
+            .map(|x| FlatStorageFile {
+                path: Path::new("folder").join(&x.path),
+                length: x.length,
+            })
+            .collect();
+        storage.rename(renamed.clone()).await.unwrap();
+        let state = moved(&mut storage).await;
+        assert_eq!(state.move_error, None);
+        assert!(!save_to.join("a").exists());
+        assert!(save_to.join("folder").join("a").is_file());
+        assert!(save_to.join("folder").join("b").is_file());
+        let piece = storage.load(1).await.unwrap().unwrap();
+        assert_eq!(piece.as_ref(), pieces[1].as_slice());
+
+        // second file exists, first one is moved back
+        std::fs::write(save_to.join("b"), b"other").unwrap();
+        storage.rename(files).await.unwrap();
+        let state = moved(&mut storage).await;
+        assert!(state.move_error.is_some());
+        assert!(!save_to.join("a").exists());
+        assert!(save_to.join("folder").join("a").is_file());
+        assert_eq!(std::fs::read(save_to.join("b")).unwrap(), b"other");
+        let piece = storage.load(0).await.unwrap().unwrap();
+        assert_eq!(piece.as_ref(), pieces[0].as_slice());
+
+        close(storage).await;
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,5 +1,10 @@
-use crate::{storage::TorrentPiece, types::public::FileView, RsbtError};
-use flat_storage_mmap::FileInfo;
+use crate::{
+    storage::TorrentPiece,
+    types::public::{FilePriority, FileView},
+    RsbtError,
+};
+use flat_storage::{FileInfo, FlatStorageFile};
+use std::path::PathBuf;
 use tokio::sync::oneshot;
 
 pub(crate) enum TorrentStorageMessage {
@@ -16,6 +21,25 @@ pub(crate) enum TorrentStorageMessage {
         files: bool,
         sender: oneshot::Sender<Result<(), RsbtError>>,
     },
+    /// Checking runs in storage loop, progress is broadcasted with state.
+    Recheck,
+    SetFilePriority {
+        file_id: usize,
+        priority: FilePriority,
+        sender: oneshot::Sender<Result<(), RsbtError>>,
+    },
+    /// Storage is closed while files are moved, then it is opened at new path.
+    Move {
+        save_to: PathBuf,
+        sender: oneshot::Sender<Result<(), RsbtError>>,
+    },
+    /// Storage is closed while files are renamed, then it is opened with new paths.
+    Rename {
+        files: Vec<FlatStorageFile>,
+        sender: oneshot::Sender<Result<(), RsbtError>>,
+    },
+    /// Saves state with pending changes, which are otherwise saved in batches.
+    SaveState(oneshot::Sender<Result<(), RsbtError>>),
     Files(oneshot::Sender<Result<Vec<FileView>, RsbtError>>),
     FileInfo {
         file_id: usize,
@@ -1,63 +1,202 @@
-use crate::{storage::TORRENT_STORAGE_FORMAT_VERSION, RsbtError};
+use crate::{storage::TORRENT_STORAGE_FORMAT_VERSION, types::public::FilePriority, RsbtError};
 use byteorder::{BigEndian, ReadBytesExt};
 use failure::ResultExt;
-use std::{convert::TryInto, io::Read, path::Path};
-use tokio::{fs::File, io::AsyncWriteExt};
+use sha1::{Digest, Sha1};
+use std::{
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{
+    fs::{self, File},
+    io::AsyncWriteExt,
+};
+
+/// Length of SHA-1 checksum at the end of state file.
+const CHECKSUM_LEN: usize = 20;
 
 #[derive(Clone, Debug)]
 pub struct TorrentStorageState {
     pub downloaded: Vec<u8>,
     pub bytes_write: u64,
     pub bytes_read: u64,
+    /// Count of wanted pieces left to download
     pub pieces_left: u32,
+    /// Priorities of pieces from priorities of files, not saved.
+    pub piece_priorities: Arc<Vec<FilePriority>>,
+    /// Count of checked pieces while verifying data, not saved.
+    pub checking: Option<u32>,
+    /// Directory of torrent data, not saved.
+    pub save_to: PathBuf,
+    /// Count of moved bytes while data is moved, not saved.
+    pub moving: Option<u64>,
+    /// Reason of last failed move, not saved.
+    pub move_error: Option<String>,
+    /// Directory data is moved to when download is complete, not saved.
+    pub complete_to: Option<PathBuf>,
 }
 
 impl TorrentStorageState {
+    pub(crate) fn new(pieces_left: u32) -> Self {
+        Self {
+            downloaded: vec![],
+            bytes_write: 0,
+            bytes_read: 0,
+            pieces_left,
+            piece_priorities: Default::default(),
+            checking: None,
+            save_to: PathBuf::new(),
+            moving: None,
+            move_error: None,
+            complete_to: None,
+        }
+    }
+
+    /// Reads state, checksum is verified and state of version 0 is migrated.
     pub(crate) fn from_reader(mut rdr: impl Read) -> Result<Self, RsbtError> {
-        let version = rdr.read_u8()?;
-        if version != TORRENT_STORAGE_FORMAT_VERSION {
-            return Err(RsbtError::StorageVersion(version));
+        let mut data = vec![];
+        rdr.read_to_end(&mut data)?;
+        let (&version, content) = data
+            .split_first()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        match version {
+            TORRENT_STORAGE_FORMAT_VERSION => {
+                if content.len() < CHECKSUM_LEN {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                let (fields, checksum) = data.split_at(data.len() - CHECKSUM_LEN);
+                if Sha1::digest(fields)[..] != *checksum {
+                    return Err(RsbtError::StorageChecksum);
+                }
+                Self::read_fields(&fields[1..])
+            }
+            // version 0 has no checksum, it is written in current version on next save
+            0 => Self::read_fields(content),
+            _ => Err(RsbtError::StorageVersion(version)),
         }
+    }
+
+    fn read_fields(mut rdr: &[u8]) -> Result<Self, RsbtError> {
         let bytes_write: u64 = rdr.read_u64::<BigEndian>()?;
         let bytes_read = rdr.read_u64::<BigEndian>()?;
         let pieces_left = rdr.read_u32::<BigEndian>()?;
-        let mut downloaded = vec![];
-        rdr.read_to_end(&mut downloaded)?;
+        let downloaded = rdr.to_vec();
         Ok(Self {
             downloaded,
             bytes_write,
             bytes_read,
             pieces_left,
+            ..Self::new(pieces_left)
         })
     }
 
-    pub(crate) async fn write_to_file(&self, mut f: File) -> Result<(), RsbtError> {
-        f.write_u8(TORRENT_STORAGE_FORMAT_VERSION).await?;
-        f.write_u64(self.bytes_write.try_into()?).await?;
-        f.write_u64(self.bytes_read.try_into()?).await?;
-        f.write_u32(self.pieces_left.try_into()?).await?;
-        f.write_all(&self.downloaded).await?;
-        Ok(())
+    /// Serialized state, checksum of all previous bytes is written last.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(21 + self.downloaded.len() + CHECKSUM_LEN);
+        data.push(TORRENT_STORAGE_FORMAT_VERSION);
+        data.extend_from_slice(&self.bytes_write.to_be_bytes());
+        data.extend_from_slice(&self.bytes_read.to_be_bytes());
+        data.extend_from_slice(&self.pieces_left.to_be_bytes());
+        data.extend_from_slice(&self.downloaded);
+        let checksum = Sha1::digest(&data);
+        data.extend_from_slice(&checksum);
+        data
     }
 
+    /// State is written to temporary file and renamed over `state_file` after sync,
+    /// so a crash leaves either old or new state.
     pub(crate) async fn save<P: AsRef<Path>>(&self, state_file: P) -> Result<(), RsbtError> {
-        let state_file_ref = &state_file.as_ref();
-        let f = File::create(&state_file).await.with_context(|err| {
-            format!(
-                "cannot create state file {}: {}",
-                state_file_ref.to_string_lossy(),
-                err
-            )
-        })?;
-        self.write_to_file(f)
+        let state_file = state_file.as_ref();
+        let mut temp_file = state_file.as_os_str().to_owned();
+        temp_file.push(".tmp");
+        let temp_file = PathBuf::from(temp_file);
+
+        let data = self.to_bytes();
+        let write = async {
+            let mut f = File::create(&temp_file).await?;
+            f.write_all(&data).await?;
+            f.sync_all().await?;
+            fs::rename(&temp_file, state_file).await?;
+            #[cfg(unix)]
+            {
+                if let Some(dir) = state_file.parent() {
+                    File::open(dir).await?.sync_all().await?;
+                }
+            }
+            Ok::<(), io::Error>(())
+        };
+        write
             .await
             .with_context(|err| {
                 format!(
                     "cannot write state file {}: {}",
-                    state_file.as_ref().to_string_lossy(),
+                    state_file.to_string_lossy(),
                     err
                 )
             })
             .map_err(|x| x.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_state_format() -> Result<(), RsbtError> {
+        let mut state = TorrentStorageState::new(3);
+        state.downloaded = vec![0b1010_0000];
+        state.bytes_write = 1024;
+        state.bytes_read = 512;
+
+        let data = state.to_bytes();
+        let loaded = TorrentStorageState::from_reader(data.as_slice())?;
+        assert_eq!(loaded.downloaded, state.downloaded);
+        assert_eq!(loaded.bytes_write, 1024);
+        assert_eq!(loaded.bytes_read, 512);
+        assert_eq!(loaded.pieces_left, 3);
+
+        let mut corrupted = data.clone();
+        corrupted[9] ^= 1;
+        assert!(matches!(
+            TorrentStorageState::from_reader(corrupted.as_slice()),
+            Err(RsbtError::StorageChecksum)
+        ));
+        assert!(TorrentStorageState::from_reader(&data[..data.len() - 1]).is_err());
+        assert!(TorrentStorageState::from_reader(&[][..]).is_err());
+
+        let mut old = vec![0];
+        old.extend_from_slice(&data[1..data.len() - CHECKSUM_LEN]);
+        let migrated = TorrentStorageState::from_reader(old.as_slice())?;
+        assert_eq!(migrated.downloaded, state.downloaded);
+        assert_eq!(migrated.bytes_write, 1024);
+        assert_eq!(migrated.pieces_left, 3);
+
+        assert!(matches!(
+            TorrentStorageState::from_reader(&[2][..]),
+            Err(RsbtError::StorageVersion(2))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_state_save() -> Result<(), RsbtError> {
+        let dir = std::env::temp_dir().join(format!("rsbt-state-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let state_file = dir.join("a.torrent.state");
+
+        let mut state = TorrentStorageState::new(2);
+        state.save(&state_file).await?;
+        state.downloaded = vec![0b1000_0000];
+        state.pieces_left = 1;
+        state.save(&state_file).await?;
+
+        let loaded = TorrentStorageState::from_reader(std::fs::read(&state_file)?.as_slice())?;
+        assert_eq!(loaded.downloaded, state.downloaded);
+        assert_eq!(loaded.pieces_left, 1);
+        assert!(!dir.join("a.torrent.state.tmp").exists());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
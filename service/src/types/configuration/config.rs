@@ -1,3 +1,7 @@
+use crate::types::{
+    configuration::{EncryptionMode, ProxyConfig, StorageBackend},
+    public::AltSpeedPeriod,
+};
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
@@ -33,6 +37,11 @@ pub struct Config {
     /// Storage path
     #[structopt(long, env = "RSBT_PATH_STORAGE")]
     pub storage: Option<String>,
+    /// Path of incomplete downloads
+    ///
+    /// Data is moved to download path, when download is complete.
+    #[structopt(long, env = "RSBT_PATH_INCOMPLETE")]
+    pub incomplete_dir: Option<String>,
 }
 
 /// Global application settings
@@ -40,12 +49,95 @@ pub struct Config {
 pub struct Settings {
     pub config: Config,
     pub peers: Peers,
+    #[serde(default)]
+    pub bandwidth: Bandwidth,
+    #[serde(default)]
+    pub alt_speed: AltSpeed,
+    #[serde(default)]
+    pub ip_filter: IpFilter,
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    #[serde(default)]
+    pub port_mapping: PortMapping,
+    #[serde(default)]
+    pub storage: Storage,
+    #[serde(default)]
+    pub streaming: Streaming,
 }
 
 #[derive(Default, Serialize, Deserialize, Debug)]
 pub struct Peers {
     /// Maximum count of connections with peers
-    max_connections: Option<usize>,
+    pub max_connections: Option<usize>,
+    /// Maximum count of connections with peers for single torrent
+    pub max_connections_per_torrent: Option<usize>,
+    /// Maximum count of simultaneously connecting (half-open) sockets
+    pub max_half_open: Option<usize>,
+    /// Seconds without any message from peer before disconnect
+    pub idle_timeout: Option<u64>,
+    /// Seconds without requested block from peer before piece is reassigned
+    pub snub_timeout: Option<u64>,
+    /// Hides client name and version in peer id
+    pub anonymous: Option<bool>,
+    /// Message stream encryption: disabled, enabled or forced
+    pub encryption: Option<EncryptionMode>,
+}
+
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct Bandwidth {
+    /// Global upload limit in bytes per second
+    pub upload_limit: Option<u64>,
+    /// Global download limit in bytes per second
+    pub download_limit: Option<u64>,
+    /// Maximum count of running torrents, the rest are queued
+    pub max_active_torrents: Option<usize>,
+}
+
+/// Alternative speed limits, enabled manually or by schedule
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct AltSpeed {
+    /// Alternative global upload limit in bytes per second
+    pub upload_limit: Option<u64>,
+    /// Alternative global download limit in bytes per second
+    pub download_limit: Option<u64>,
+    /// Alternative maximum count of running torrents
+    pub max_active_torrents: Option<usize>,
+    /// Weekly periods when alternative limits are enabled
+    #[serde(default)]
+    pub schedule: Vec<AltSpeedPeriod>,
+}
+
+/// Global filter of peer addresses
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct IpFilter {
+    /// Filter lists in P2P, eMule dat or CIDR format, optionally gzip-compressed
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+/// Mapping of listen port on home router
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct PortMapping {
+    /// Map listen port with UPnP IGD, PCP or NAT-PMP, disabled if omitted
+    pub enabled: Option<bool>,
+    /// Gateway address for PCP and NAT-PMP, default route is used if omitted
+    pub gateway: Option<IpAddr>,
+}
+
+/// Torrent data storage
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct Storage {
+    /// Storage implementation: mmap, file or memory, mmap if omitted
+    pub backend: Option<StorageBackend>,
+    /// Maximum count of open files per torrent for file backend
+    pub max_open_files: Option<usize>,
+}
+
+/// Streaming of files while torrent is downloaded
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct Streaming {
+    /// Count of pieces after stream position downloaded before others
+    pub read_ahead: Option<usize>,
 }
 
 impl Settings {
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// Message stream encryption policy for peer connections.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EncryptionMode {
+    /// Plaintext connections only
+    Disabled,
+    /// Encrypted connections are preferred, plaintext peers are accepted
+    #[default]
+    Enabled,
+    /// Encrypted connections only
+    Forced,
+}
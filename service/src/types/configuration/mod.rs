@@ -1,7 +1,15 @@
 mod config;
+mod encryption_mode;
 mod properties;
 mod properties_provider;
+mod proxy_config;
+mod proxy_kind;
+mod storage_backend;
 
 pub use config::{Config, Settings};
+pub use encryption_mode::EncryptionMode;
 pub use properties::Properties;
 pub(crate) use properties_provider::PropertiesProvider;
+pub use proxy_config::ProxyConfig;
+pub use proxy_kind::ProxyKind;
+pub use storage_backend::StorageBackend;
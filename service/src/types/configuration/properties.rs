@@ -1,15 +1,25 @@
-use crate::types::{configuration::PropertiesProvider, Settings};
+use crate::{
+    types::{
+        configuration::{EncryptionMode, PropertiesProvider, ProxyConfig, StorageBackend},
+        generate_peer_id,
+        public::AltSpeedPeriod,
+        Settings,
+    },
+    PEER_IDLE_TIMEOUT, PEER_MAX_CONNECTIONS, PEER_MAX_CONNECTIONS_PER_TORRENT, PEER_MAX_HALF_OPEN,
+    PEER_SNUB_TIMEOUT, STORAGE_MAX_OPEN_FILES, STREAM_READ_AHEAD,
+};
 use std::{
     net::{IpAddr, Ipv4Addr},
     path::PathBuf,
+    time::Duration,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Properties {
     pub compact: Option<bool>,
     /// Address to listen to
     pub listen: IpAddr,
-    /// Port to listen on
+    /// Port to listen on, replaced with actually bound port on start
     pub port: u16,
     /// Max port
     ///
@@ -19,14 +29,75 @@ pub struct Properties {
     pub save_to: PathBuf,
     /// Storage path
     pub storage: PathBuf,
+    /// Path of incomplete downloads, data is moved to download path when complete
+    pub incomplete_dir: Option<PathBuf>,
     /// Config path
     pub config_dir: PathBuf,
+    /// Maximum count of connections with peers
+    pub max_connections: usize,
+    /// Maximum count of connections with peers for single torrent
+    pub max_connections_per_torrent: usize,
+    /// Maximum count of simultaneously connecting (half-open) sockets
+    pub max_half_open: usize,
+    /// Time without any message from peer before disconnect
+    pub peer_idle_timeout: Duration,
+    /// Time without requested block from peer before piece is reassigned
+    pub peer_snub_timeout: Duration,
+    /// Global upload limit in bytes per second
+    pub upload_limit: Option<u64>,
+    /// Global download limit in bytes per second
+    pub download_limit: Option<u64>,
+    /// Maximum count of running torrents, the rest are queued
+    pub max_active_torrents: Option<usize>,
+    /// Alternative global upload limit in bytes per second
+    pub alt_upload_limit: Option<u64>,
+    /// Alternative global download limit in bytes per second
+    pub alt_download_limit: Option<u64>,
+    /// Alternative maximum count of running torrents
+    pub alt_max_active_torrents: Option<usize>,
+    /// Weekly periods when alternative limits are enabled
+    pub alt_speed_schedule: Vec<AltSpeedPeriod>,
+    /// Anonymous mode, client name and version are not revealed
+    pub anonymous: bool,
+    /// Peer id of current session, sent in handshakes and announces
+    pub peer_id: [u8; 20],
+    /// Message stream encryption policy
+    pub encryption: EncryptionMode,
+    /// Filter lists of blocked addresses, relative paths are resolved from config path
+    pub ip_filter: Vec<PathBuf>,
+    /// Proxy for peer connections and tracker requests
+    pub proxy: Option<ProxyConfig>,
+    /// Map listen port on gateway
+    pub port_mapping: bool,
+    /// Gateway address for PCP and NAT-PMP
+    pub port_mapping_gateway: Option<IpAddr>,
+    /// Implementation of torrent data storage
+    pub storage_backend: StorageBackend,
+    /// Maximum count of open files per torrent for file storage
+    pub max_open_files: usize,
+    /// Count of pieces after stream position downloaded before others
+    pub stream_read_ahead: usize,
 }
 
 impl From<(Settings, PathBuf)> for Properties {
     fn from(value: (Settings, PathBuf)) -> Self {
         let config = value.0.config;
+        let peers = value.0.peers;
+        let bandwidth = value.0.bandwidth;
+        let alt_speed = value.0.alt_speed;
+        let ip_filter = value.0.ip_filter;
+        let proxy = value.0.proxy;
+        let port_mapping = value.0.port_mapping;
+        let storage_settings = value.0.storage;
+        let streaming = value.0.streaming;
         let config_dir = value.1;
+        let ip_filter = ip_filter
+            .paths
+            .into_iter()
+            .map(|x| config_dir.join(x))
+            .collect();
+        let anonymous = peers.anonymous.unwrap_or_default();
+        let encryption = peers.encryption.unwrap_or_default();
         let (save_to, storage) = match (
             config.save_to.map(PathBuf::from),
             config.storage.map(PathBuf::from),
@@ -45,7 +116,40 @@ impl From<(Settings, PathBuf)> for Properties {
             port_max: config.port_max,
             save_to,
             storage,
+            incomplete_dir: config.incomplete_dir.map(PathBuf::from),
             config_dir,
+            max_connections: peers.max_connections.unwrap_or(PEER_MAX_CONNECTIONS),
+            max_connections_per_torrent: peers
+                .max_connections_per_torrent
+                .unwrap_or(PEER_MAX_CONNECTIONS_PER_TORRENT),
+            max_half_open: peers.max_half_open.unwrap_or(PEER_MAX_HALF_OPEN),
+            peer_idle_timeout: peers
+                .idle_timeout
+                .map(Duration::from_secs)
+                .unwrap_or(PEER_IDLE_TIMEOUT),
+            peer_snub_timeout: peers
+                .snub_timeout
+                .map(Duration::from_secs)
+                .unwrap_or(PEER_SNUB_TIMEOUT),
+            upload_limit: bandwidth.upload_limit,
+            download_limit: bandwidth.download_limit,
+            max_active_torrents: bandwidth.max_active_torrents,
+            alt_upload_limit: alt_speed.upload_limit,
+            alt_download_limit: alt_speed.download_limit,
+            alt_max_active_torrents: alt_speed.max_active_torrents,
+            alt_speed_schedule: alt_speed.schedule,
+            anonymous,
+            peer_id: generate_peer_id(anonymous),
+            encryption,
+            ip_filter,
+            proxy,
+            port_mapping: port_mapping.enabled.unwrap_or_default(),
+            port_mapping_gateway: port_mapping.gateway,
+            storage_backend: storage_settings.backend.unwrap_or_default(),
+            max_open_files: storage_settings
+                .max_open_files
+                .unwrap_or(STORAGE_MAX_OPEN_FILES),
+            stream_read_ahead: streaming.read_ahead.unwrap_or(STREAM_READ_AHEAD),
         }
     }
 }
@@ -54,4 +158,12 @@ impl PropertiesProvider for Properties {
     fn port(&self) -> u16 {
         self.port
     }
+
+    fn peer_id(&self) -> &[u8; 20] {
+        &self.peer_id
+    }
+
+    fn anonymous(&self) -> bool {
+        self.anonymous
+    }
 }
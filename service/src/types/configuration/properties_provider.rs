@@ -1,3 +1,6 @@
 pub(crate) trait PropertiesProvider {
     fn port(&self) -> u16;
+    fn peer_id(&self) -> &[u8; 20];
+    /// External address is not sent to trackers if set.
+    fn anonymous(&self) -> bool;
 }
@@ -0,0 +1,55 @@
+use crate::types::configuration::ProxyKind;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Formatter};
+
+/// Proxy server for peer connections and tracker requests
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProxyConfig {
+    /// Proxy protocol
+    #[serde(rename = "type")]
+    pub kind: ProxyKind,
+    /// Proxy host name or address
+    pub host: String,
+    /// Proxy port
+    pub port: u16,
+    /// User name, password authentication is used if set
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Connect to peers through proxy
+    #[serde(default = "enabled")]
+    pub peers: bool,
+    /// Send tracker requests through proxy
+    #[serde(default = "enabled")]
+    pub trackers: bool,
+    /// Refuse incoming peer connections, so no traffic bypasses proxy
+    #[serde(default)]
+    pub refuse_incoming: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl ProxyConfig {
+    pub(crate) fn credentials(&self) -> Option<(&str, &str)> {
+        self.username
+            .as_deref()
+            .map(|username| (username, self.password.as_deref().unwrap_or_default()))
+    }
+}
+
+/// Password is not shown, properties are logged on start.
+impl Debug for ProxyConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProxyConfig")
+            .field("kind", &self.kind)
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "***"))
+            .field("peers", &self.peers)
+            .field("trackers", &self.trackers)
+            .field("refuse_incoming", &self.refuse_incoming)
+            .finish()
+    }
+}
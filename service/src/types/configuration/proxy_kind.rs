@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+/// Proxy protocol.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyKind {
+    /// SOCKS5, TCP connections and UDP associate
+    Socks5,
+    /// HTTP proxy with CONNECT method, TCP connections only
+    Http,
+}
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// Implementation of torrent data storage.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// Files are memory mapped
+    #[default]
+    Mmap,
+    /// Positional reads and writes with limited count of open files
+    File,
+    /// Data and state are kept in memory only, for tests and ephemeral torrents
+    Memory,
+}
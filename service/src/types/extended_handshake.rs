@@ -0,0 +1,83 @@
+use crate::{types::BencodeBlob, RsbtError};
+use std::convert::{TryFrom, TryInto};
+
+/// Extension protocol (BEP 10) message id of extended handshake.
+pub(crate) const EXTENDED_HANDSHAKE_ID: u8 = 0;
+
+/// Extended handshake, only client name and version and listen port are used.
+#[derive(Debug, PartialEq, Default)]
+pub struct ExtendedHandshake {
+    /// Client name and version (`v`)
+    pub client: Option<String>,
+    /// Local TCP listen port (`p`)
+    pub port: Option<u16>,
+}
+
+try_from_bencode!(ExtendedHandshake,
+    optional: (
+        "p" => port,
+        "v" => client
+    )
+);
+
+impl ExtendedHandshake {
+    /// Bencoded payload, no extension messages are supported yet.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut payload = b"d1:mde".to_vec();
+        if let Some(port) = self.port {
+            payload.extend_from_slice(format!("1:pi{}e", port).as_bytes());
+        }
+        if let Some(client) = &self.client {
+            payload.extend_from_slice(format!("1:v{}:", client.len()).as_bytes());
+            payload.extend_from_slice(client.as_bytes());
+        }
+        payload.push(b'e');
+        payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extended_handshake_roundtrip() {
+        let extended_handshake = ExtendedHandshake {
+            client: Some("rsbt 0.1.0".into()),
+            port: None,
+        };
+        let payload = extended_handshake.encode();
+        assert_eq!(payload, b"d1:mde1:v10:rsbt 0.1.0e".to_vec());
+
+        let decoded: ExtendedHandshake = payload.try_into().unwrap();
+        assert_eq!(decoded, extended_handshake);
+
+        let anonymous: ExtendedHandshake =
+            ExtendedHandshake::default().encode().try_into().unwrap();
+        assert_eq!(anonymous.client, None);
+    }
+
+    #[test]
+    fn extended_handshake_with_port() {
+        let extended_handshake = ExtendedHandshake {
+            client: Some("rsbt 0.1.0".into()),
+            port: Some(6881),
+        };
+        let payload = extended_handshake.encode();
+        assert_eq!(payload, b"d1:mde1:pi6881e1:v10:rsbt 0.1.0e".to_vec());
+
+        let decoded: ExtendedHandshake = payload.try_into().unwrap();
+        assert_eq!(decoded, extended_handshake);
+    }
+
+    #[test]
+    fn extended_handshake_with_unknown_keys() {
+        let decoded: ExtendedHandshake =
+            b"d1:md11:ut_metadatai1ee1:pi6881e1:v13:\xc2\xb5Torrent 3.5e"
+                .to_vec()
+                .try_into()
+                .unwrap();
+        assert_eq!(decoded.client, Some("\u{b5}Torrent 3.5".into()));
+        assert_eq!(decoded.port, Some(6881));
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{parser::parse_handshake, RsbtError, SHA1_SIZE};
 use std::convert::TryFrom;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Handshake {
     pub protocol_prefix: [u8; 20],
     pub reserved: [u8; 8],
@@ -9,6 +9,13 @@ pub struct Handshake {
     pub peer_id: [u8; 20],
 }
 
+impl Handshake {
+    /// Peer supports extension protocol (BEP 10).
+    pub(crate) fn supports_extensions(&self) -> bool {
+        self.reserved[5] & 0x10 != 0
+    }
+}
+
 impl TryFrom<Vec<u8>> for Handshake {
     type Error = RsbtError;
 
@@ -79,6 +79,34 @@ pub enum Message {
     ///
     /// The port message is sent by newer versions of the Mainline that implements a DHT tracker. The listen port is the port this peer's DHT node is listening on. This peer should be inserted in the local routing table (if DHT tracker is supported).
     Port(u16),
+    /// extended: <len=0002+X><id=20><extended message id><payload>
+    ///
+    /// Extension protocol (BEP 10) message, sent only if both peers set bit 20 in handshake reserved bytes. Extended message id 0 is the extended handshake with bencoded dictionary payload.
+    Extended { id: u8, payload: Vec<u8> },
+}
+
+impl Message {
+    /// Size of encoded message including length prefix.
+    pub(crate) fn wire_size(&self) -> usize {
+        4 + match self {
+            Message::KeepAlive => 0,
+            Message::Choke | Message::Unchoke | Message::Interested | Message::NotInterested => 1,
+            Message::Have { .. } => 5,
+            Message::Bitfield(bitfield) => 1 + bitfield.len(),
+            Message::Request { .. } | Message::Cancel { .. } => 13,
+            Message::Piece { block, .. } => 9 + block.len(),
+            Message::Port(_) => 3,
+            Message::Extended { payload, .. } => 2 + payload.len(),
+        }
+    }
+
+    /// Size of torrent data carried by message, the rest is protocol overhead.
+    pub(crate) fn payload_size(&self) -> usize {
+        match self {
+            Message::Piece { block, .. } => block.len(),
+            _ => 0,
+        }
+    }
 }
 
 impl Display for Message {
@@ -89,6 +117,7 @@ impl Display for Message {
                 begin,
                 block,
             } => write!(f, "Piece({}, {}, [{}])", index, begin, block.len()),
+            Message::Extended { id, payload } => write!(f, "Extended({}, [{}])", id, payload.len()),
             _ => write!(f, "{:?}", self),
         }
     }
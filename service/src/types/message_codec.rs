@@ -1,6 +1,7 @@
 use crate::{
     parser::parser_message,
     types::{Message, MessageCodecError},
+    PEER_MAX_MESSAGE_SIZE,
 };
 use bytes::{Buf, BufMut, BytesMut};
 use nom::Offset;
@@ -14,6 +15,16 @@ impl Decoder for MessageCodec {
     type Error = MessageCodecError;
 
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if buf.len() >= 4 {
+            let size = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+            if size > PEER_MAX_MESSAGE_SIZE {
+                return Err(MessageCodecError::MessageTooLarge {
+                    size,
+                    max: PEER_MAX_MESSAGE_SIZE,
+                });
+            }
+        }
+
         let (consumed, f) = match parser_message(buf) {
             Err(e) => {
                 if e.is_incomplete() {
@@ -114,6 +125,13 @@ impl Encoder<Message> for MessageCodec {
                 buf.put_u8(9);
                 buf.put_u16(port);
             }
+            Message::Extended { id, payload } => {
+                buf.reserve(6 + payload.len());
+                buf.put_u32(2 + payload.len() as u32);
+                buf.put_u8(20);
+                buf.put_u8(id);
+                buf.put_slice(&payload);
+            }
         }
         Ok(())
     }
@@ -207,6 +225,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn encode_extended() {
+        encode_message(
+            &[0, 0, 0, 4, 20, 1, 2, 3],
+            Message::Extended {
+                id: 1,
+                payload: vec![2, 3],
+            },
+        );
+    }
+
+    #[test]
+    fn decode_too_large() {
+        let mut buf = BytesMut::from(&[0x7f, 0, 0, 0, 7][..]);
+
+        assert!(matches!(
+            MessageCodec.decode(&mut buf),
+            Err(MessageCodecError::MessageTooLarge { .. })
+        ));
+    }
+
     #[test]
     fn encode_port() {
         encode_message(&[0, 0, 0, 3, 9, 0, 101], Message::Port(101));
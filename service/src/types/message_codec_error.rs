@@ -6,6 +6,8 @@ pub enum MessageCodecError {
     IoError(std::io::Error),
     #[fail(display = "Couldn't parse incoming frame: {}", _0)]
     ParseError(String),
+    #[fail(display = "Message of {} bytes exceeds maximum of {} bytes", size, max)]
+    MessageTooLarge { size: usize, max: usize },
 }
 
 impl From<std::io::Error> for MessageCodecError {
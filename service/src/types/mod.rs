@@ -1,12 +1,15 @@
 #[macro_use]
 mod bencode;
 mod configuration;
+mod extended_handshake;
 mod handshake;
 pub mod info;
 mod message;
 mod message_codec;
 mod message_codec_error;
 mod peer;
+mod peer_client;
+mod peer_id;
 pub mod public;
 mod torrent;
 mod tracker_announce;
@@ -14,16 +17,22 @@ pub(crate) mod udp_tracker;
 
 pub use bencode::{BencodeBlob, BencodeValue};
 pub(crate) use configuration::PropertiesProvider;
-pub use configuration::{Config, Properties, Settings};
+pub use configuration::{
+    Config, EncryptionMode, Properties, ProxyConfig, ProxyKind, Settings, StorageBackend,
+};
+pub(crate) use extended_handshake::{ExtendedHandshake, EXTENDED_HANDSHAKE_ID};
 pub(crate) use handshake::Handshake;
 pub use message::Message;
 pub use message_codec::MessageCodec;
 pub use message_codec_error::MessageCodecError;
 pub use peer::Peer;
+pub(crate) use peer_client::peer_client;
+pub(crate) use peer_id::generate_peer_id;
 pub use torrent::Torrent;
 pub(crate) use tracker_announce::TrackerAnnounce;
 pub use udp_tracker::UdpTrackerCodecError;
 pub(crate) use udp_tracker::{UdpTrackerResponse, UdpTrackerResponseData, UdpTrackerScrape};
 
+/// Protocol name and reserved bytes, extension protocol bit is set.
 pub(crate) const HANDSHAKE_PREFIX: [u8; 28] =
-    *b"\x13BitTorrent protocol\x00\x00\x00\x00\x00\x00\x00\x00";
+    *b"\x13BitTorrent protocol\x00\x00\x00\x00\x00\x10\x00\x00";
@@ -0,0 +1,172 @@
+/// Known clients with Azureus-style peer id `-XXVVVV-`.
+const AZUREUS_CLIENTS: &[(&[u8; 2], &str)] = &[
+    (b"AZ", "Vuze"),
+    (b"BC", "BitComet"),
+    (b"BI", "BiglyBT"),
+    (b"BT", "BitTorrent"),
+    (b"DE", "Deluge"),
+    (b"FD", "Free Download Manager"),
+    (b"KT", "KTorrent"),
+    (b"LT", "libtorrent (Rasterbar)"),
+    (b"lt", "libTorrent (rakshasa)"),
+    (b"qB", "qBittorrent"),
+    (b"rs", "rsbt"),
+    (b"TR", "Transmission"),
+    (b"UM", "\u{b5}Torrent Mac"),
+    (b"UT", "\u{b5}Torrent"),
+    (b"WW", "WebTorrent"),
+    (b"XL", "Xunlei"),
+];
+
+/// Known clients with Shadow-style peer id `XVVVVV---`.
+const SHADOW_CLIENTS: &[(u8, &str)] = &[
+    (b'A', "ABC"),
+    (b'O', "Osprey Permaseed"),
+    (b'Q', "BTQueue"),
+    (b'R', "Tribler"),
+    (b'S', "Shadow"),
+    (b'T', "BitTornado"),
+    (b'U', "UPnP NAT Bit Torrent"),
+];
+
+const SHADOW_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz.-";
+
+/// Decodes client name and version from peer id.
+///
+/// Azureus, Shadow and Mainline styles are recognized, unknown Azureus codes are shown as is.
+pub(crate) fn peer_client(peer_id: &[u8; 20]) -> Option<String> {
+    azureus_client(peer_id)
+        .or_else(|| mainline_client(peer_id))
+        .or_else(|| shadow_client(peer_id))
+}
+
+fn azureus_client(peer_id: &[u8; 20]) -> Option<String> {
+    if peer_id[0] != b'-' || peer_id[7] != b'-' {
+        return None;
+    }
+
+    let code = &peer_id[1..3];
+    if !code.iter().all(u8::is_ascii_alphanumeric) {
+        return None;
+    }
+
+    let version = peer_id[3..7]
+        .iter()
+        .map(|&x| (x as char).to_digit(36))
+        .collect::<Option<Vec<_>>>()?;
+
+    let name = AZUREUS_CLIENTS
+        .iter()
+        .find(|(x, _)| &x[..] == code)
+        .map(|(_, name)| (*name).to_string())
+        .unwrap_or_else(|| String::from_utf8_lossy(code).into_owned());
+
+    Some(format!("{} {}", name, format_version(&version)))
+}
+
+fn mainline_client(peer_id: &[u8; 20]) -> Option<String> {
+    if peer_id[0] != b'M' {
+        return None;
+    }
+
+    let version = peer_id[1..8]
+        .split(|&x| x == b'-')
+        .filter(|x| !x.is_empty())
+        .map(|x| std::str::from_utf8(x).ok()?.parse::<u32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    if version.len() != 3 {
+        return None;
+    }
+
+    Some(format!("Mainline {}", format_version(&version)))
+}
+
+fn shadow_client(peer_id: &[u8; 20]) -> Option<String> {
+    let name = SHADOW_CLIENTS
+        .iter()
+        .find(|(x, _)| *x == peer_id[0])
+        .map(|(_, name)| *name)?;
+
+    if &peer_id[6..9] != b"---" {
+        return None;
+    }
+
+    let version = peer_id[1..6]
+        .iter()
+        .take_while(|&&x| x != b'-')
+        .map(|x| {
+            SHADOW_ALPHABET
+                .iter()
+                .position(|y| y == x)
+                .map(|x| x as u32)
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    if version.is_empty() {
+        return None;
+    }
+
+    Some(format!("{} {}", name, format_version(&version)))
+}
+
+/// Joins version parts, trailing zero parts are omitted.
+fn format_version(version: &[u32]) -> String {
+    let significant = version
+        .iter()
+        .rposition(|&x| x != 0)
+        .map(|x| x + 1)
+        .unwrap_or_default()
+        .max(2)
+        .min(version.len());
+
+    version[..significant]
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn azureus_style() {
+        assert_eq!(
+            peer_client(b"-TR2940-pm2sh9i76t4d"),
+            Some("Transmission 2.9.4".into())
+        );
+        assert_eq!(
+            peer_client(b"-qB4250-abcdefghijkl"),
+            Some("qBittorrent 4.2.5".into())
+        );
+        assert_eq!(peer_client(b"-XX1000-abcdefghijkl"), Some("XX 1.0".into()));
+    }
+
+    #[test]
+    fn shadow_style() {
+        assert_eq!(
+            peer_client(b"S58B-----abcdefghijk"),
+            Some("Shadow 5.8.11".into())
+        );
+        assert_eq!(
+            peer_client(b"T03I--00abcdefghijkl"),
+            None,
+            "version must be followed by dashes"
+        );
+    }
+
+    #[test]
+    fn mainline_style() {
+        assert_eq!(
+            peer_client(b"M4-3-6--abcdefghijkl"),
+            Some("Mainline 4.3.6".into())
+        );
+    }
+
+    #[test]
+    fn unknown_style() {
+        assert_eq!(peer_client(b"rsbt                "), None);
+    }
+}
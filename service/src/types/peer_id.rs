@@ -0,0 +1,66 @@
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+/// Client code in Azureus-style peer id.
+const CLIENT_CODE: &[u8; 2] = b"rs";
+
+/// Generates peer id for current session.
+///
+/// Azureus-style `-rsXYZW-` prefix with crate version is followed by random characters.
+/// In anonymous mode the whole id is random, so client and its version are not revealed.
+pub(crate) fn generate_peer_id(anonymous: bool) -> [u8; 20] {
+    let mut peer_id = [0u8; 20];
+
+    let prefix = if anonymous {
+        0
+    } else {
+        peer_id[0] = b'-';
+        peer_id[1..3].copy_from_slice(CLIENT_CODE);
+        peer_id[3..7].copy_from_slice(&version_code());
+        peer_id[7] = b'-';
+        8
+    };
+
+    let mut rng = thread_rng();
+    for x in peer_id[prefix..].iter_mut() {
+        *x = rng.sample(Alphanumeric) as u8;
+    }
+
+    peer_id
+}
+
+/// Crate version as four characters: major, minor (two characters) and patch.
+fn version_code() -> [u8; 4] {
+    let mut version = env!("CARGO_PKG_VERSION")
+        .split(|x: char| !x.is_ascii_digit())
+        .map(|x| x.parse::<u32>().unwrap_or_default());
+    let major = version.next().unwrap_or_default();
+    let minor = version.next().unwrap_or_default();
+    let patch = version.next().unwrap_or_default();
+
+    let code = |x: u32| std::char::from_digit(x % 36, 36).unwrap_or('0') as u8;
+
+    [code(major), code(minor / 10), code(minor % 10), code(patch)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peer_id_has_client_prefix() {
+        let peer_id = generate_peer_id(false);
+
+        assert_eq!(&peer_id[..3], b"-rs");
+        assert_eq!(&peer_id[3..7], &version_code());
+        assert_eq!(peer_id[7], b'-');
+        assert!(peer_id[8..].iter().all(|x| x.is_ascii_alphanumeric()));
+        assert_ne!(peer_id, generate_peer_id(false));
+    }
+
+    #[test]
+    fn anonymous_peer_id_has_no_prefix() {
+        let peer_id = generate_peer_id(true);
+
+        assert!(peer_id.iter().all(|x| x.is_ascii_alphanumeric()));
+    }
+}
@@ -0,0 +1,29 @@
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// Weekly period of alternative speed limits.
+///
+/// Period with `from` later than `to` lasts over midnight. Empty `days` means every day.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AltSpeedPeriod {
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    pub from: NaiveTime,
+    pub to: NaiveTime,
+}
+
+impl AltSpeedPeriod {
+    fn on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    pub(crate) fn contains(&self, now: NaiveDateTime) -> bool {
+        let day = now.weekday();
+        let time = now.time();
+        if self.from <= self.to {
+            self.on(day) && self.from <= time && time < self.to
+        } else {
+            (self.on(day) && self.from <= time) || (self.on(day.pred()) && time < self.to)
+        }
+    }
+}
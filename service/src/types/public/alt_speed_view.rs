@@ -0,0 +1,12 @@
+use crate::types::public::AltSpeedPeriod;
+use serde::Serialize;
+
+/// Alternative speed limits state.
+#[derive(Debug, Serialize, Clone)]
+pub struct AltSpeedView {
+    pub enabled: bool,
+    pub upload_limit: Option<u64>,
+    pub download_limit: Option<u64>,
+    pub max_active_torrents: Option<usize>,
+    pub schedule: Vec<AltSpeedPeriod>,
+}
@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+
+/// Rate limits in bytes per second, none or zero means unlimited.
+#[derive(Debug, Serialize, Deserialize, Default, Copy, Clone, PartialEq)]
+pub struct BandwidthLimits {
+    pub upload: Option<u64>,
+    pub download: Option<u64>,
+}
@@ -0,0 +1,12 @@
+use serde::Serialize;
+
+/// Limits and current rates in bytes per second.
+#[derive(Debug, Serialize, Clone)]
+pub struct BandwidthView {
+    pub upload_limit: Option<u64>,
+    pub download_limit: Option<u64>,
+    pub upload_rate: u64,
+    pub download_rate: u64,
+    pub upload_overhead_rate: u64,
+    pub download_overhead_rate: u64,
+}
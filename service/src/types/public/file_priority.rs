@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+/// Download priority of torrent file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FilePriority {
+    /// File is not downloaded
+    Skip,
+    Low,
+    #[default]
+    Normal,
+    High,
+}
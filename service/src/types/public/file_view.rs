@@ -1,3 +1,4 @@
+use crate::types::public::FilePriority;
 use serde::Serialize;
 
 #[derive(Serialize, Clone, Debug)]
@@ -6,4 +7,5 @@ pub struct FileView {
     pub name: String,
     pub saved: usize,
     pub size: usize,
+    pub priority: FilePriority,
 }
@@ -0,0 +1,11 @@
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Global IP filter state.
+#[derive(Debug, Serialize, Clone)]
+pub struct IpFilterView {
+    pub paths: Vec<PathBuf>,
+    pub ranges: usize,
+    pub invalid_lines: usize,
+    pub blocked: u64,
+}
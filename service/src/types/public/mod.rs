@@ -1,16 +1,32 @@
+mod alt_speed_period;
+mod alt_speed_view;
 mod announce_view;
+mod bandwidth_limits;
+mod bandwidth_view;
+mod file_priority;
 mod file_view;
+mod ip_filter_view;
 mod peer_state_view;
+mod peer_transport;
 mod peer_view;
+mod status_view;
 mod torrent_action;
 mod torrent_download_state;
 mod torrent_download_view;
 mod torrent_statistics_event;
 
+pub use alt_speed_period::AltSpeedPeriod;
+pub use alt_speed_view::AltSpeedView;
 pub use announce_view::AnnounceView;
+pub use bandwidth_limits::BandwidthLimits;
+pub use bandwidth_view::BandwidthView;
+pub use file_priority::FilePriority;
 pub use file_view::FileView;
+pub use ip_filter_view::IpFilterView;
 pub use peer_state_view::PeerStateView;
+pub use peer_transport::PeerTransport;
 pub use peer_view::PeerView;
+pub use status_view::StatusView;
 pub use torrent_action::TorrentAction;
 pub use torrent_download_state::TorrentDownloadState;
 pub use torrent_download_view::TorrentDownloadView;
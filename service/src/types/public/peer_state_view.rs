@@ -1,4 +1,4 @@
-use crate::peer::TorrentPeerState;
+use crate::{peer::TorrentPeerState, types::public::PeerTransport};
 use serde::Serialize;
 use serde_with::skip_serializing_none;
 
@@ -11,33 +11,72 @@ pub enum PeerStateView {
     Connected {
         chocked: bool,
         interested: bool,
+        am_chocking: bool,
+        am_interested: bool,
         piece: Option<usize>,
         //FIXME: downloading_since: Option<Instant>,
         rx: usize,
         tx: usize,
+        rx_rate: u64,
+        tx_rate: u64,
+        /// Percentage of torrent pieces peer has
+        progress: f64,
+        client: Option<String>,
+        incoming: bool,
+        encrypted: bool,
+        transport: PeerTransport,
+        /// Connection duration in seconds
+        duration: u64,
     },
 }
 
-impl From<&TorrentPeerState> for PeerStateView {
-    fn from(value: &TorrentPeerState) -> Self {
-        match value {
+impl From<(&TorrentPeerState, usize)> for PeerStateView {
+    fn from(value: (&TorrentPeerState, usize)) -> Self {
+        let (state, pieces_count) = value;
+        match state {
             TorrentPeerState::Idle => PeerStateView::Idle {},
             TorrentPeerState::Connecting(_) => PeerStateView::Connecting {},
             TorrentPeerState::Connected {
                 chocked,
                 interested,
+                am_chocking,
+                am_interested,
                 downloading_piece,
-                downloading_since,
                 downloaded,
                 uploaded,
+                download_rate,
+                upload_rate,
+                pieces,
+                client,
+                incoming,
+                encrypted,
+                connected_since,
                 ..
             } => PeerStateView::Connected {
                 chocked: *chocked,
                 interested: *interested,
-                piece: downloading_piece.clone(),
+                am_chocking: *am_chocking,
+                am_interested: *am_interested,
+                piece: *downloading_piece,
                 rx: *downloaded,
                 tx: *uploaded,
+                rx_rate: download_rate.rate(),
+                tx_rate: upload_rate.rate(),
+                progress: progress(pieces, pieces_count),
+                client: client.clone(),
+                incoming: *incoming,
+                encrypted: *encrypted,
+                transport: PeerTransport::Tcp,
+                duration: connected_since.elapsed().as_secs(),
             },
         }
     }
 }
+
+fn progress(pieces: &[u8], pieces_count: usize) -> f64 {
+    if pieces_count == 0 {
+        return 0.0;
+    }
+    let available: u32 = pieces.iter().map(|x| x.count_ones()).sum();
+    f64::from(available) * 100.0 / pieces_count as f64
+}
@@ -0,0 +1,8 @@
+use serde::Serialize;
+
+/// Transport of peer connection.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerTransport {
+    Tcp,
+}
@@ -6,14 +6,17 @@ use std::net::SocketAddr;
 pub struct PeerView {
     addr: SocketAddr,
     state: PeerStateView,
+    hash_fails: usize,
 }
 
-impl From<&PeerState> for PeerView {
-    fn from(value: &PeerState) -> Self {
-        let state = &value.state;
+/// Peer view from peer state and count of torrent pieces.
+impl From<(&PeerState, usize)> for PeerView {
+    fn from(value: (&PeerState, usize)) -> Self {
+        let (value, pieces_count) = value;
         Self {
             addr: value.peer.clone().into(),
-            state: state.into(),
+            state: (&value.state, pieces_count).into(),
+            hash_fails: value.hash_fails,
         }
     }
 }
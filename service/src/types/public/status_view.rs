@@ -0,0 +1,13 @@
+use serde::Serialize;
+use std::net::IpAddr;
+
+/// Application status.
+#[derive(Debug, Serialize, Clone)]
+pub struct StatusView {
+    pub listen: IpAddr,
+    pub port: u16,
+    pub peer_id: String,
+    pub torrents: usize,
+    /// Address reported by gateway after port mapping
+    pub external_ip: Option<IpAddr>,
+}
@@ -1,8 +1,22 @@
 use serde::{Deserialize, Serialize};
 
-#[serde(rename_all = "lowercase")]
 #[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+#[serde(rename_all = "snake_case")]
 pub enum TorrentAction {
     Enable,
     Disable,
+    /// Super-seeding (BEP 16), complete torrent only
+    SuperSeed,
+    /// Stops super-seeding, all pieces are advertised
+    NormalSeed,
+    /// Downloads pieces in order
+    Sequential,
+    /// Stops downloading pieces in order
+    NonSequential,
+    /// Downloads first and last pieces of each file first
+    FirstLastPieces,
+    /// Stops preferring first and last pieces of each file
+    NoFirstLastPieces,
+    /// Verifies downloaded data against piece hashes
+    Recheck,
 }
@@ -2,4 +2,5 @@
 pub struct TorrentDownloadState {
     pub downloaded: u64,
     pub uploaded: u64,
+    pub hash_fails: u64,
 }
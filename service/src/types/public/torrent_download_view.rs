@@ -1,5 +1,9 @@
-use crate::process::{TorrentProcess, TorrentProcessStatus};
+use crate::{
+    process::{TorrentProcess, TorrentProcessStatus},
+    types::public::BandwidthView,
+};
 use serde::Serialize;
+use std::path::PathBuf;
 
 #[derive(Debug, Serialize, Clone)]
 pub struct TorrentDownloadView {
@@ -9,36 +13,64 @@ pub struct TorrentDownloadView {
     pub read: u64,
     pub tx: u64,
     pub rx: u64,
+    pub hash_fails: u64,
+    pub bandwidth: BandwidthView,
     pub pieces_total: u32,
     pub pieces_left: u32,
     pub piece_size: u32,
     pub length: usize,
     pub active: bool,
+    pub queued: bool,
+    pub super_seed: bool,
+    pub sequential: bool,
+    pub first_last_pieces: bool,
+    /// Count of verified pieces while data is checked.
+    pub checking: Option<u32>,
+    /// Directory of torrent data.
+    pub save_to: PathBuf,
+    /// Count of moved bytes while data is moved.
+    pub moving: Option<u64>,
+    /// Reason of last failed move.
+    pub move_error: Option<String>,
 }
 
 impl From<&TorrentProcess> for TorrentDownloadView {
     fn from(torrent: &TorrentProcess) -> Self {
-        let (read, write, pieces_left) = {
+        let (read, write, pieces_left, checking, save_to, moving, move_error) = {
             let storage_state = torrent.storage_state_watch.borrow();
             (
                 storage_state.bytes_read,
                 storage_state.bytes_write,
                 storage_state.pieces_left,
+                storage_state.checking,
+                storage_state.save_to.clone(),
+                storage_state.moving,
+                storage_state.move_error.clone(),
             )
         };
-        let (tx, rx) = {
+        let (tx, rx, hash_fails) = {
             let state = torrent.statistics_watch.borrow();
-            (state.uploaded, state.downloaded)
+            (state.uploaded, state.downloaded, state.hash_fails)
         };
         Self {
             id: torrent.id,
             name: torrent.name.clone(),
             active: torrent.header.state == TorrentProcessStatus::Enabled,
+            queued: torrent.queued,
+            super_seed: torrent.super_seed,
+            sequential: torrent.header.sequential,
+            first_last_pieces: torrent.header.first_last_pieces,
+            checking,
+            save_to,
+            moving,
+            move_error,
             length: torrent.process.info.length,
             write,
             read,
             tx,
             rx,
+            hash_fails,
+            bandwidth: torrent.process.bandwidth.torrent.view(),
             pieces_left,
             pieces_total: torrent.process.info.pieces.len() as u32,
             piece_size: torrent.process.info.piece_length as u32,
@@ -10,6 +10,9 @@ pub enum TorrentStatisticsEvent {
         write: u64,
         read: u64,
         left: u32,
+        checking: Option<u32>,
+        moving: Option<u64>,
+        move_error: Option<String>,
     },
     Stat {
         id: usize,
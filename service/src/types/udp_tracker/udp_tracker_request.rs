@@ -3,7 +3,7 @@ use crate::types::udp_tracker::{
 };
 use crate::{process::TorrentTokenProvider, types::configuration::PropertiesProvider};
 use rand::prelude::*;
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
 
 #[derive(Debug, Clone)]
 pub(crate) struct UdpTrackerRequest {
@@ -33,22 +33,30 @@ impl UdpTrackerRequest {
         torrent_process: Arc<TT>,
     ) -> Self {
         let left = torrent_process.info().len() as i64;
+        // only IPv4 address can be sent, zero lets tracker use source address
+        let ip = match torrent_process.external_ip() {
+            Some(IpAddr::V4(ip)) if !properties.anonymous() => ip.into(),
+            _ => 0,
+        };
+        let port = torrent_process
+            .external_port()
+            .unwrap_or_else(|| properties.port());
 
         Self {
             connection_id,
             transaction_id: random(),
             data: UdpTrackerRequestData::Announce {
                 info_hash: torrent_process.hash_id().clone(),
-                peer_id: crate::PEER_ID,
+                peer_id: *properties.peer_id(),
                 downloaded: 0,
                 uploaded: 0,
                 left,
                 event: 0,
-                ip: 0,
+                ip,
                 extensions: 0,
                 num_want: -1,
                 key: random(),
-                port: properties.port(),
+                port,
             },
             authentication: None,
             request_string: None,